@@ -4,6 +4,11 @@ const LAUNCH_PLIST: &str = include_str!("./resources/org.blackholefox.keeperofke
 const PATH_TEMPLATE: &str = "$BINARY_PATH";
 
 fn main() {
+    // The LaunchAgent is only used on macOS.
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("macos") {
+        return;
+    }
+
     let binary_parent_path = if cfg!(debug_assertions) {
         let project_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
         Cow::Owned(format!("{project_dir}/target/debug/bundle/osx"))
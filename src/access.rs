@@ -0,0 +1,295 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "linux")]
+use std::{
+    fs, io, mem,
+    os::unix::{
+        fs::FileTypeExt,
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    sync::Arc,
+    thread,
+};
+
+use crate::{baseline::wildcard_match, process::ProcessInfo};
+
+#[cfg(target_os = "linux")]
+mod fanotify;
+#[cfg(target_os = "linux")]
+pub use fanotify::start_file_monitor;
+#[cfg(target_os = "linux")]
+mod secret_service;
+#[cfg(target_os = "linux")]
+pub use secret_service::start_proxy;
+
+/// Where keystores that are only files keep them, relative to the home directory, along with
+/// what they're called and the executables that are expected to open them.
+const KEYSTORE_FILES: &[(&str, &str, &[&str])] = &[
+    (
+        ".local/share/keyrings/*.keyring",
+        "GNOME Keyring",
+        &["/usr/bin/gnome-keyring-daemon"],
+    ),
+    (
+        ".mozilla/firefox/*/key4.db",
+        "Firefox",
+        &[
+            "/usr/lib*/firefox*/firefox*",
+            "/opt/firefox/*",
+            "/snap/firefox/*",
+        ],
+    ),
+    (
+        ".mozilla/firefox/*/logins.json",
+        "Firefox",
+        &[
+            "/usr/lib*/firefox*/firefox*",
+            "/opt/firefox/*",
+            "/snap/firefox/*",
+        ],
+    ),
+    (
+        ".config/google-chrome/*/Login Data",
+        "Chrome",
+        &["/opt/google/chrome/*"],
+    ),
+    (
+        ".config/chromium/*/Login Data",
+        "Chromium",
+        &["/usr/lib*/chromium*/*", "/snap/chromium/*"],
+    ),
+];
+
+/// Sitting between apps and the keystore, to see every secret that gets read.
+// Only the Secret Service has a proxy so far.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct AccessProxyConfig {
+    pub(crate) enabled: bool,
+    /// Where apps connect instead of the session bus. Defaults to `keeper_of_keys/bus` in the
+    /// runtime directory.
+    pub(crate) socket: Option<PathBuf>,
+    /// The bus everything is passed on to. Defaults to the session bus.
+    pub(crate) upstream: Option<String>,
+    /// If reads by executables that aren't approved are refused, instead of only reported.
+    pub(crate) enforce: bool,
+    /// Paths of executables that may read secrets, where `*` matches any run of characters.
+    pub(crate) approved: Vec<String>,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+impl AccessProxyConfig {
+    /// Checks if `reader` may have secrets. Readers that couldn't be looked at never are.
+    pub fn approves(&self, reader: &ProcessInfo) -> bool {
+        let path = match reader.path.as_deref().and_then(|path| path.to_str()) {
+            Some(path) => path,
+            None => return false,
+        };
+
+        self.approved
+            .iter()
+            .any(|pattern| wildcard_match(pattern, path))
+    }
+}
+
+/// Watching which processes open the files keystores keep their secrets in, to see reads that
+/// never go through the keystore itself.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct FileAccessConfig {
+    pub(crate) enabled: bool,
+    /// Files to watch on top of the GNOME Keyring, Firefox and Chromium ones.
+    pub(crate) files: Vec<KeystoreFile>,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Debug, Deserialize)]
+pub(crate) struct KeystoreFile {
+    /// Relative to the home directory unless it's absolute. `*` matches any run of characters.
+    pub(crate) path: String,
+    /// What the file is reported as belonging to.
+    #[serde(default)]
+    pub(crate) keystore: Option<String>,
+    /// Paths of executables that are expected to open the file, which aren't reported.
+    #[serde(default)]
+    pub(crate) owners: Vec<String>,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+impl FileAccessConfig {
+    /// Every file to watch, with absolute paths.
+    pub fn resolve(&self, home: &Path) -> Vec<KeystoreFile> {
+        let absolute = |path: &str| home.join(path).to_string_lossy().into_owned();
+
+        KEYSTORE_FILES
+            .iter()
+            .map(|(path, keystore, owners)| KeystoreFile {
+                path: absolute(path),
+                keystore: Some((*keystore).to_owned()),
+                owners: owners.iter().map(|owner| (*owner).to_owned()).collect(),
+            })
+            .chain(self.files.iter().map(|file| KeystoreFile {
+                path: absolute(file.path.strip_prefix("~/").unwrap_or(&file.path)),
+                keystore: file.keystore.clone(),
+                owners: file.owners.clone(),
+            }))
+            .collect()
+    }
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+impl KeystoreFile {
+    /// Checks if `reader` is one of the file's own executables.
+    pub fn owned_by(&self, reader: &ProcessInfo) -> bool {
+        reader
+            .path
+            .as_deref()
+            .and_then(|path| path.to_str())
+            .is_some_and(|path| self.owners.iter().any(|owner| wildcard_match(owner, path)))
+    }
+}
+
+/// Where a proxy listens, which is `socket` if it's set and otherwise `name` in a directory of
+/// our own in the runtime directory.
+#[cfg(target_os = "linux")]
+pub fn proxy_socket(socket: Option<&Path>, name: &str) -> io::Result<PathBuf> {
+    match socket {
+        Some(socket) => Ok(socket.to_owned()),
+        None => std::env::var_os("XDG_RUNTIME_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| PathBuf::from(dir).join("keeper_of_keys").join(name))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "no socket is set for the proxy")
+            }),
+    }
+}
+
+/// Listens on `socket`, handing every client that runs as this user to `serve` on a thread of
+/// its own, along with who they are. `name` is what the proxy is called in logs.
+#[cfg(target_os = "linux")]
+pub fn serve_proxy<F>(socket: &Path, name: &str, serve: F) -> io::Result<()>
+where
+    F: Fn(UnixStream, libc::ucred) -> io::Result<()> + Send + Sync + 'static,
+{
+    if let Some(dir) = socket.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // Only a socket an earlier run left behind is cleared away, never anything else.
+    match fs::symlink_metadata(socket) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(socket)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is in the way, and isn't a socket", socket.display()),
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let listener = UnixListener::bind(socket)?;
+    let serve = Arc::new(serve);
+    let name = name.to_owned();
+
+    thread::Builder::new()
+        .name(format!("{name} Proxy"))
+        .spawn(move || {
+            for client in listener.incoming() {
+                let client = match client {
+                    Ok(client) => client,
+                    Err(e) => {
+                        log::warn!("failed to accept {name} client: {e}");
+                        continue;
+                    }
+                };
+
+                let serve = Arc::clone(&serve);
+                let client_name = name.clone();
+                let spawned = thread::Builder::new()
+                    .name(format!("{name} Proxy Client"))
+                    .spawn(move || {
+                        let served = admit(&client).and_then(|peer| serve(client, peer));
+                        if let Err(e) = served {
+                            log::debug!("{client_name} client went away: {e}");
+                        }
+                    });
+
+                if let Err(e) = spawned {
+                    log::warn!("failed to start {name} client thread: {e}");
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Finds out who a proxy client is, turning away anyone but this user since they could be
+/// trying to get at this user's secrets through us.
+#[cfg(target_os = "linux")]
+fn admit(client: &UnixStream) -> io::Result<libc::ucred> {
+    let credentials = peer_credentials(client)?;
+
+    if credentials.uid != unsafe { libc::getuid() } {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("refused client running as uid {}", credentials.uid),
+        ));
+    }
+
+    Ok(credentials)
+}
+
+/// Finds out who is on the other end of a socket.
+#[cfg(target_os = "linux")]
+fn peer_credentials(stream: &UnixStream) -> io::Result<libc::ucred> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut credentials as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(credentials)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxies_only_replace_sockets() {
+        let dir = std::env::temp_dir().join(format!("keeper-proxy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("file");
+        fs::write(&file, "not a socket").unwrap();
+        assert!(serve_proxy(&file, "Test", |_, _| Ok(())).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "not a socket");
+
+        // Like one an earlier run left behind.
+        let socket = dir.join("socket");
+        drop(UnixListener::bind(&socket).unwrap());
+        serve_proxy(&socket, "Test", |_, _| Ok(())).unwrap();
+        UnixStream::connect(&socket).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    fs::{self, File},
+    io::{self, Read},
+    mem,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{FromRawFd, RawFd},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{FileAccessConfig, KeystoreFile};
+use crate::{
+    baseline::wildcard_match,
+    clock::cf_now,
+    events::{Backend, EventData, InnerDetails},
+    items::keystore_files,
+    process::ProcessCache,
+};
+
+/// How long a process opening the same file again isn't reported again, since apps tend to open
+/// their files over and over.
+const REPEAT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Starts watching keystore files being opened, reporting everyone but their owners as an
+/// `Accessed` event.
+///
+/// The kernel only says who opened a file to listeners with `CAP_SYS_ADMIN`.
+pub fn start_file_monitor(
+    config: &FileAccessConfig,
+    home: &Path,
+    events: mpsc::Sender<EventData>,
+) -> io::Result<()> {
+    let fd = unsafe {
+        libc::fanotify_init(
+            libc::FAN_CLOEXEC | libc::FAN_CLASS_NOTIF,
+            (libc::O_RDONLY | libc::O_CLOEXEC | libc::O_LARGEFILE) as libc::c_uint,
+        )
+    };
+
+    if fd < 0 {
+        let e = io::Error::last_os_error();
+        return Err(if e.kind() == io::ErrorKind::PermissionDenied {
+            io::Error::new(e.kind(), "watching files being opened needs CAP_SYS_ADMIN")
+        } else {
+            e
+        });
+    }
+
+    let fanotify = unsafe { File::from_raw_fd(fd) };
+    let files = config.resolve(home);
+
+    // Marks on directories cover files that are replaced, which most of these are on every save.
+    let mut marked = 0;
+    for file in &files {
+        let directory = Path::new(&file.path).parent().unwrap_or(Path::new("/"));
+
+        for directory in expand(directory) {
+            match mark(fd, &directory) {
+                Ok(()) => marked += 1,
+                Err(e) => log::debug!("couldn't watch {}: {e}", directory.display()),
+            }
+        }
+    }
+
+    log::debug!("watching keystore files in {marked} directories");
+
+    thread::Builder::new()
+        .name(String::from("Keystore File Monitor"))
+        .spawn(move || {
+            if let Err(e) = monitor(fanotify, &files, &events) {
+                log::error!("stopped watching keystore files: {e}");
+            }
+        })?;
+
+    Ok(())
+}
+
+fn monitor(
+    mut fanotify: File,
+    files: &[KeystoreFile],
+    events: &mpsc::Sender<EventData>,
+) -> io::Result<()> {
+    let own_pid = std::process::id() as i32;
+    let mut processes = ProcessCache::default();
+    let mut reported: HashMap<(i32, PathBuf), Instant> = HashMap::new();
+
+    let header = mem::size_of::<libc::fanotify_event_metadata>();
+    let mut buffer = vec![0u8; 4096];
+
+    loop {
+        let read = fanotify.read(&mut buffer)?;
+        let mut at = 0;
+
+        while at + header <= read {
+            let event: libc::fanotify_event_metadata =
+                unsafe { std::ptr::read_unaligned(buffer[at..].as_ptr().cast()) };
+
+            if event.vers != libc::FANOTIFY_METADATA_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the kernel sent events in an unknown format",
+                ));
+            }
+
+            at += (event.event_len as usize).max(header);
+
+            if event.mask & libc::FAN_Q_OVERFLOW != 0 {
+                log::warn!("too many keystore files were opened at once, some were missed");
+                continue;
+            }
+
+            if event.fd == libc::FAN_NOFD {
+                continue;
+            }
+
+            // Every event comes with the file opened again for us, which has to be closed.
+            let opened = OpenedFile(event.fd);
+            let path = match fs::read_link(format!("/proc/self/fd/{}", opened.0)) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+
+            if event.pid == own_pid {
+                continue;
+            }
+
+            // Marks cover whole directories, so most of what's seen isn't a keystore at all.
+            let file = match files
+                .iter()
+                .find(|file| wildcard_match(&file.path, &path.to_string_lossy()))
+            {
+                Some(file) => file,
+                None => continue,
+            };
+
+            let now = Instant::now();
+            reported.retain(|_, at| now.duration_since(*at) < REPEAT_WINDOW);
+            if reported.contains_key(&(event.pid, path.clone())) {
+                continue;
+            }
+
+            let reader = processes.lookup(event.pid);
+            let name = reader.display_name().unwrap_or("Unknown");
+
+            if file.owned_by(&reader) {
+                log::debug!("{name} [{}] opened its own {}", event.pid, path.display());
+                continue;
+            }
+
+            log::info!("{name} [{}] opened {}", event.pid, path.display());
+            reported.insert((event.pid, path.clone()), now);
+
+            let (class, attributes) =
+                keystore_files::to_attributes(file.keystore.as_deref(), &path);
+            let item = class.resolve(&attributes);
+
+            let details = InnerDetails::new(Backend::KeystoreFile, item.title, cf_now(), reader)
+                .with_class(class.name, item.attributes, item.details);
+
+            if events.send(EventData::Accessed(details)).is_err() {
+                log::warn!("event stream receiver has shutdown");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A file descriptor the kernel handed over with an event.
+struct OpenedFile(RawFd);
+
+impl Drop for OpenedFile {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+fn mark(fanotify: RawFd, directory: &Path) -> io::Result<()> {
+    let path = CString::new(directory.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let result = unsafe {
+        libc::fanotify_mark(
+            fanotify,
+            libc::FAN_MARK_ADD,
+            libc::FAN_OPEN | libc::FAN_EVENT_ON_CHILD,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+        )
+    };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Finds every existing directory a path with wildcards in it matches.
+fn expand(pattern: &Path) -> Vec<PathBuf> {
+    let mut found = vec![PathBuf::new()];
+
+    for component in pattern.components() {
+        let component = component.as_os_str().to_string_lossy();
+
+        if !component.contains('*') {
+            for path in &mut found {
+                path.push(&*component);
+            }
+            continue;
+        }
+
+        found = found
+            .iter()
+            .flat_map(|parent| fs::read_dir(parent).into_iter().flatten().flatten())
+            .filter(|entry| wildcard_match(&component, &entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .collect();
+    }
+
+    found.retain(|path| path.is_dir());
+    found
+}
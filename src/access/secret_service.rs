@@ -0,0 +1,389 @@
+use std::{
+    io::{self, Write},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use super::AccessProxyConfig;
+use crate::{
+    clock::cf_now,
+    dbus::{self, Connection, Message, MessageType, Value},
+    events::{Backend, EventData, InnerDetails},
+    items::secret_service::{self, ITEM_INTERFACE, SERVICE_INTERFACE},
+    process::{ProcessCache, ProcessInfo},
+};
+
+const ACCESS_DENIED: &str = "org.freedesktop.DBus.Error.AccessDenied";
+
+/// What every client connection shares.
+struct Shared {
+    config: AccessProxyConfig,
+    upstream: String,
+    /// For looking up the items that get read. The proxy's own traffic never goes through itself.
+    bus: Mutex<Connection>,
+    processes: Mutex<ProcessCache>,
+    events: mpsc::Sender<EventData>,
+}
+
+/// Starts passing the session bus through a socket of its own, reporting every secret that's
+/// read through it as an `Accessed` event.
+///
+/// Apps only go through the proxy if they're pointed at it, so it's most useful with
+/// `DBUS_SESSION_BUS_ADDRESS` set for the whole session.
+pub fn start_proxy(config: &AccessProxyConfig, events: mpsc::Sender<EventData>) -> io::Result<()> {
+    let socket = super::proxy_socket(config.socket.as_deref(), "bus")?;
+
+    let upstream = match &config.upstream {
+        Some(upstream) => upstream.clone(),
+        None => dbus::session_address()?,
+    };
+
+    if upstream.contains(&*socket.to_string_lossy()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the proxy would pass everything on to itself, set `upstream` to the real bus",
+        ));
+    }
+
+    let shared = Arc::new(Shared {
+        config: config.clone(),
+        bus: Mutex::new(Connection::open(&upstream)?),
+        upstream,
+        processes: Mutex::new(ProcessCache::default()),
+        events,
+    });
+
+    super::serve_proxy(&socket, "Secret Service", move |client, peer| {
+        serve(client, peer.pid, &shared)
+    })?;
+
+    log::info!("proxying the session bus at {}", socket.display());
+
+    Ok(())
+}
+
+fn serve(mut client: UnixStream, pid: i32, shared: &Shared) -> io::Result<()> {
+    let mut bus = dbus::connect(&shared.upstream)?;
+    relay_auth(&mut client, &mut bus)?;
+
+    let to_client = Arc::new(Mutex::new(client.try_clone()?));
+
+    {
+        let to_client = Arc::clone(&to_client);
+        let mut from_bus = bus.try_clone()?;
+
+        thread::Builder::new()
+            .name(String::from("Secret Service Proxy Replies"))
+            .spawn(move || {
+                while let Ok(message) = Message::read_raw(&mut from_bus) {
+                    if to_client.lock().unwrap().write_all(&message).is_err() {
+                        break;
+                    }
+                }
+
+                let _ = to_client.lock().unwrap().shutdown(Shutdown::Both);
+            })?;
+    }
+
+    // Most clients never ask for a secret, so they're only looked up once they do.
+    let mut reader = None;
+
+    let result = loop {
+        let message = match Message::read_raw(&mut client) {
+            Ok(message) => message,
+            Err(e) => break Err(e),
+        };
+
+        if let Some(refusal) = inspect(&message, pid, &mut reader, shared) {
+            if let Err(e) = to_client.lock().unwrap().write_all(&refusal.to_bytes()) {
+                break Err(e);
+            }
+            continue;
+        }
+
+        if let Err(e) = bus.write_all(&message) {
+            break Err(e);
+        }
+    };
+
+    let _ = bus.shutdown(Shutdown::Both);
+    result
+}
+
+/// Passes the authentication conversation along, until the client says it's done.
+///
+/// Passing file descriptors can't be proxied, so clients are always told it's unavailable.
+fn relay_auth(client: &mut UnixStream, bus: &mut UnixStream) -> io::Result<()> {
+    let mut nul = [0u8; 1];
+    io::Read::read_exact(client, &mut nul)?;
+    bus.write_all(&nul)?;
+
+    loop {
+        let line = dbus::read_line(client)?;
+
+        if line == "NEGOTIATE_UNIX_FD" {
+            client.write_all(b"ERROR\r\n")?;
+            continue;
+        }
+
+        bus.write_all(format!("{line}\r\n").as_bytes())?;
+
+        if line == "BEGIN" {
+            return Ok(());
+        }
+
+        let reply = dbus::read_line(bus)?;
+        client.write_all(format!("{reply}\r\n").as_bytes())?;
+    }
+}
+
+/// Looks at a message on its way to the bus, returning a refusal to send back instead if the
+/// reader isn't allowed to make it.
+fn inspect(
+    raw: &[u8],
+    pid: i32,
+    reader: &mut Option<ProcessInfo>,
+    shared: &Shared,
+) -> Option<Message> {
+    let message = match Message::from_bytes(raw) {
+        Ok(message) => message,
+        // A call that can't be read could still be asking for secrets, so it isn't trusted to
+        // the bus when reads are being refused. Anything else is the bus' problem.
+        Err(e) if shared.config.enforce && raw[1] == MessageType::MethodCall as u8 => {
+            log::warn!("refused a call that couldn't be read: {e}");
+            return Message::error_to_raw(
+                raw,
+                ACCESS_DENIED,
+                "Keeper of Keys couldn't read this call",
+            )
+            .map(with_own_serial);
+        }
+        Err(_) => return None,
+    };
+
+    if message.kind != MessageType::MethodCall {
+        return None;
+    }
+
+    let (unlocking, objects) = if is_call(&message, SERVICE_INTERFACE, "GetSecrets") {
+        (false, object_paths(message.body.first()))
+    } else if is_call(&message, ITEM_INTERFACE, "GetSecret") {
+        (false, message.path.clone().into_iter().collect())
+    } else if is_call(&message, SERVICE_INTERFACE, "Unlock") {
+        (true, object_paths(message.body.first()))
+    } else {
+        return None;
+    };
+
+    // The client is certainly still around, since it's connected.
+    let reader = reader.get_or_insert_with(|| shared.processes.lock().unwrap().lookup(pid));
+
+    let name = reader.display_name().unwrap_or("Unknown");
+    let verb = if unlocking { "unlock" } else { "read" };
+
+    if shared.config.enforce && !shared.config.approves(reader) {
+        log::warn!(
+            "refused {name} [{}] asking to {verb} {}",
+            reader.pid,
+            objects.join(", ")
+        );
+
+        return Some(with_own_serial(Message::error(
+            &message,
+            ACCESS_DENIED,
+            "Keeper of Keys hasn't approved this app for reading secrets",
+        )));
+    }
+
+    log::info!(
+        "{name} [{}] asked to {verb} {}",
+        reader.pid,
+        objects.join(", ")
+    );
+
+    // Unlocking doesn't hand out any secrets by itself.
+    if !unlocking {
+        for path in objects {
+            report_read(&path, reader, shared);
+        }
+    }
+
+    None
+}
+
+/// Refusals are sent straight to the client, so they need a serial of their own.
+fn with_own_serial(mut refusal: Message) -> Message {
+    // Serials only have to be non-zero, nothing checks the ones coming from the bus for clashes.
+    refusal.serial = u32::MAX;
+    refusal
+}
+
+fn report_read(path: &str, reader: &ProcessInfo, shared: &Shared) {
+    let details = match secret_service::read_item(&mut shared.bus.lock().unwrap(), path) {
+        Ok((class, attributes)) => {
+            let item = class.resolve(&attributes);
+
+            InnerDetails::new(Backend::SecretService, item.title, cf_now(), reader.clone())
+                .with_class(class.name, item.attributes, item.details)
+        }
+        Err(e) => {
+            log::debug!("couldn't look up read item {path}: {e}");
+            InnerDetails::new(
+                Backend::SecretService,
+                path.to_owned(),
+                cf_now(),
+                reader.clone(),
+            )
+        }
+    };
+
+    let _ = shared.events.send(EventData::Accessed(details));
+}
+
+fn is_call(message: &Message, interface: &str, member: &str) -> bool {
+    message.member.as_deref() == Some(member)
+        // The interface can be left out, and the service can be called by its unique name, so a
+        // call without one could be for anything with that method.
+        && message
+            .interface
+            .as_deref()
+            .is_none_or(|called| called == interface)
+}
+
+fn object_paths(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Value::as_str)
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dbus::{testing::PrivateBus, BUS_NAME, BUS_PATH},
+        items::secret_service::{
+            testing::{mock_service, COLLECTION},
+            SERVICE_NAME, SERVICE_PATH,
+        },
+    };
+
+    /// Starts a proxy in front of a service holding one item, returning a client that's
+    /// connected through it along with the path of the item.
+    fn proxy(bus: &PrivateBus, enforce: bool) -> (Connection, String, mpsc::Receiver<EventData>) {
+        let item = format!("{COLLECTION}/1");
+
+        let service = bus.serve(SERVICE_NAME);
+        let existing = vec![(item.clone(), String::from("github.com"))];
+        thread::spawn(move || mock_service(service, existing, mpsc::channel().0));
+
+        let config = AccessProxyConfig {
+            enabled: true,
+            socket: Some(bus.dir().join("proxy")),
+            upstream: Some(bus.address().to_owned()),
+            enforce,
+            approved: Vec::new(),
+        };
+
+        let (tx, events) = mpsc::channel();
+        start_proxy(&config, tx).unwrap();
+
+        let client =
+            Connection::open(&format!("unix:path={}", bus.dir().join("proxy").display())).unwrap();
+
+        (client, item, events)
+    }
+
+    fn get_secrets(client: &mut Connection, item: &str) -> io::Result<Vec<Value>> {
+        client.call(
+            SERVICE_NAME,
+            SERVICE_PATH,
+            SERVICE_INTERFACE,
+            "GetSecrets",
+            vec![
+                Value::Array(String::from("o"), vec![Value::ObjectPath(item.to_owned())]),
+                Value::ObjectPath(String::from("/org/freedesktop/secrets/session/1")),
+            ],
+        )
+    }
+
+    /// Sends a message the way `call` can't, and waits for what comes back.
+    fn reply_to(client: &mut Connection, message: Message) -> Message {
+        let serial = client.send(message).unwrap();
+        loop {
+            let reply = client.receive().unwrap();
+            if reply.reply_serial == Some(serial) {
+                return reply;
+            }
+        }
+    }
+
+    #[test]
+    fn reports_reads_it_passes_on() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let (mut client, item, events) = proxy(&bus, false);
+
+        get_secrets(&mut client, &item).unwrap();
+
+        let read = events
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap()
+            .assume_filtered();
+        assert_eq!(read.kind_name(), "accessed");
+        assert_eq!(read.item_title(), Some("github.com"));
+        assert_eq!(read.changer().pid, std::process::id() as i32);
+    }
+
+    #[test]
+    fn refuses_unapproved_reads_when_enforcing() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let (mut client, item, events) = proxy(&bus, true);
+
+        let refused = get_secrets(&mut client, &item).unwrap_err();
+        assert!(refused.to_string().contains(ACCESS_DENIED));
+
+        // Leaving out the interface and calling the service by its unique name doesn't get
+        // around it.
+        let owner = client
+            .call(
+                BUS_NAME,
+                BUS_PATH,
+                BUS_NAME,
+                "GetNameOwner",
+                vec![Value::Str(SERVICE_NAME.to_owned())],
+            )
+            .unwrap();
+        let mut sneaky = Message::method_call(
+            owner[0].as_str().unwrap(),
+            &item,
+            ITEM_INTERFACE,
+            "GetSecret",
+            vec![Value::ObjectPath(String::from("/"))],
+        );
+        sneaky.interface = None;
+        let reply = reply_to(&mut client, sneaky);
+        assert_eq!(reply.error_name.as_deref(), Some(ACCESS_DENIED));
+
+        // Neither does sending a call that can't be read.
+        let unreadable = Message::method_call(
+            SERVICE_NAME,
+            SERVICE_PATH,
+            SERVICE_INTERFACE,
+            "GetSecrets",
+            vec![Value::Array(String::from("()"), Vec::new())],
+        );
+        let reply = reply_to(&mut client, unreadable);
+        assert_eq!(reply.error_name.as_deref(), Some(ACCESS_DENIED));
+
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -0,0 +1,416 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{clock::unix_now, events::FilteredEventData, state};
+
+/// Settings for learning what normal activity looks like.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct LearningConfig {
+    /// If learning mode should be used at all.
+    pub(crate) enabled: bool,
+    /// How long to only journal events and learn from them, before notifying about anything new.
+    pub(crate) duration_hours: u64,
+}
+
+impl Default for LearningConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration_hours: 72,
+        }
+    }
+}
+
+/// A kind of change that is considered normal.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BaselineEntry {
+    /// The item's title. A `*` matches any run of characters, and `\*` only a `*`.
+    pub item: String,
+    /// The changer's executable path, or its name if the path wasn't known. Matched like `item`.
+    pub changer: String,
+    /// `added`, `updated`, `removed`, `accessed`, `locked`, `unlocked`, or `*` for any of them.
+    pub kind: String,
+}
+
+/// The item, changer and kind of a change, as they're compared to baseline entries.
+struct Change<'a> {
+    item: &'a str,
+    changer: String,
+    kind: &'a str,
+}
+
+impl<'a> Change<'a> {
+    fn of(event: &'a FilteredEventData) -> Self {
+        let changer = event.changer();
+
+        let changer = match (&changer.path, changer.display_name()) {
+            (Some(path), _) => path.to_string_lossy().into_owned(),
+            (None, Some(name)) => name.to_owned(),
+            (None, None) => String::from("Unknown"),
+        };
+
+        Self {
+            item: event.item_title().unwrap_or("Unknown"),
+            changer,
+            kind: event.kind_name(),
+        }
+    }
+}
+
+impl BaselineEntry {
+    /// An entry that only covers `change`. Titles and paths can have a `*` in them, which
+    /// mustn't turn into a wildcard.
+    fn exactly(change: &Change) -> Self {
+        Self {
+            item: escape(change.item),
+            changer: escape(&change.changer),
+            kind: change.kind.to_owned(),
+        }
+    }
+
+    fn covers(&self, change: &Change) -> bool {
+        escaped_match(&self.item, change.item)
+            && escaped_match(&self.changer, &change.changer)
+            && wildcard_match(&self.kind, change.kind)
+    }
+}
+
+/// Makes `text` only ever match itself in a baseline entry.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('*', "\\*")
+}
+
+/// Like `wildcard_match`, but a `*` or `\` with a `\` in front only matches itself.
+fn escaped_match(pattern: &str, text: &str) -> bool {
+    let mut parts = vec![String::new()];
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().expect("there's always a part");
+
+        match c {
+            '\\' => match chars.next_if(|next| matches!(next, '*' | '\\')) {
+                Some(escaped) => part.push(escaped),
+                // Anything else keeps its backslash, like in a baseline from before escaping.
+                None => part.push(c),
+            },
+            '*' => parts.push(String::new()),
+            c => part.push(c),
+        }
+    }
+
+    match_parts(parts.iter().map(String::as_str), text)
+}
+
+/// Matches `text` against `pattern`, where `*` in the pattern stands for any run of characters.
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    match_parts(pattern.split('*'), text)
+}
+
+/// Matches `text` against the parts of a pattern that came between its wildcards.
+fn match_parts<'a>(parts: impl Iterator<Item = &'a str>, text: &str) -> bool {
+    let mut parts = parts.peekable();
+
+    // There is always at least one part, even for an empty pattern.
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    // No wildcards at all, so it has to be an exact match.
+    if parts.peek().is_none() {
+        return rest.is_empty();
+    }
+
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The final part has to line up with the end of the text.
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BaselineData {
+    /// Seconds since the Unix epoch that learning began at, if it has.
+    pub learning_started_at: Option<u64>,
+    pub entries: Vec<BaselineEntry>,
+}
+
+/// What a baseline thinks of an event.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Still learning, so the event was added to the baseline.
+    Learning,
+    /// The event matches something in the baseline.
+    Known,
+    /// The event hasn't been seen before.
+    Deviation,
+}
+
+/// The normal activity of this machine, as seen during learning mode.
+pub struct Baseline {
+    data: BaselineData,
+    path: PathBuf,
+    learning_period: Duration,
+    /// If this baseline is allowed to learn and write changes back to disk.
+    writable: bool,
+    loaded_at: Option<SystemTime>,
+}
+
+impl Baseline {
+    const FILE_NAME: &'static str = "baseline.json";
+
+    pub fn path(data_dir: &Path) -> PathBuf {
+        state::path_for(data_dir, Self::FILE_NAME)
+    }
+
+    pub fn load(data_dir: &Path, config: &LearningConfig) -> Self {
+        Self::open(data_dir, config, true)
+    }
+
+    /// Loads the baseline without ever learning from events or changing it on disk.
+    pub fn load_read_only(data_dir: &Path, config: &LearningConfig) -> Self {
+        Self::open(data_dir, config, false)
+    }
+
+    fn open(data_dir: &Path, config: &LearningConfig, writable: bool) -> Self {
+        let mut baseline = Self {
+            data: BaselineData::default(),
+            path: Self::path(data_dir),
+            learning_period: Duration::from_secs(config.duration_hours * 60 * 60),
+            writable,
+            loaded_at: None,
+        };
+
+        baseline.reload();
+        baseline
+    }
+
+    fn modified_on_disk(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Picks up any edits made to the baseline since it was loaded.
+    fn reload(&mut self) {
+        let modified = self.modified_on_disk();
+
+        if modified.is_some() && modified == self.loaded_at {
+            return;
+        }
+
+        self.data = state::load(&self.path);
+        self.loaded_at = modified;
+
+        if self.data.learning_started_at.is_none() && self.writable {
+            log::info!(
+                "starting learning mode for the next {} hours",
+                self.learning_period.as_secs() / 60 / 60
+            );
+            self.data.learning_started_at = Some(unix_now());
+            self.persist();
+        }
+    }
+
+    fn persist(&mut self) {
+        if !self.writable {
+            return;
+        }
+
+        match state::save(&self.path, &self.data) {
+            Ok(()) => self.loaded_at = self.modified_on_disk(),
+            Err(e) => log::warn!("failed to save baseline: {e}"),
+        }
+    }
+
+    fn is_learning(&self) -> bool {
+        let started = match self.data.learning_started_at {
+            Some(started) => started,
+            None => return false,
+        };
+
+        unix_now() < started + self.learning_period.as_secs()
+    }
+
+    /// Compares an event to the baseline, learning from it if learning mode is still going.
+    pub fn judge(&mut self, event: &FilteredEventData) -> Verdict {
+        self.reload();
+
+        let change = Change::of(event);
+        let known = self.data.entries.iter().any(|known| known.covers(&change));
+
+        if self.is_learning() {
+            if !known && self.writable {
+                self.data.entries.push(BaselineEntry::exactly(&change));
+                self.persist();
+            }
+
+            Verdict::Learning
+        } else if known {
+            Verdict::Known
+        } else {
+            Verdict::Deviation
+        }
+    }
+}
+
+/// Handles `baseline <list|export|edit|reset>`.
+pub fn run_command(mut args: impl Iterator<Item = String>, data_dir: &Path) -> Result<(), ()> {
+    let path = Baseline::path(data_dir);
+
+    match args.next().as_deref() {
+        Some("list") => {
+            let data: BaselineData = state::load(&path);
+
+            match data.learning_started_at {
+                Some(started) => {
+                    println!("learning started at {started} (seconds since the Unix epoch)")
+                }
+                None => println!("learning hasn't started yet"),
+            }
+
+            for (idx, entry) in data.entries.iter().enumerate() {
+                println!(
+                    "{idx:>4}: {} by {} ({})",
+                    entry.item, entry.changer, entry.kind
+                );
+            }
+
+            Ok(())
+        }
+        Some("export") => {
+            let data: BaselineData = state::load(&path);
+            let export =
+                serde_json::to_string_pretty(&data).expect("baselines are always serializable");
+
+            match args.next() {
+                Some(dest) => fs::write(&dest, export).map_err(|e| {
+                    log::error!("failed to export baseline to {dest}: {e}");
+                }),
+                None => {
+                    println!("{export}");
+                    Ok(())
+                }
+            }
+        }
+        Some("edit") => edit(&path),
+        Some("reset") => {
+            // Removing the file starts learning over the next time the monitor sees an event.
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    log::error!("failed to reset baseline: {e}");
+                    return Err(());
+                }
+            }
+
+            log::info!("baseline reset, learning will start again");
+            Ok(())
+        }
+        other => {
+            log::error!(
+                "unknown baseline command {:?}, expected one of list, export, edit or reset",
+                other.unwrap_or_default()
+            );
+            Err(())
+        }
+    }
+}
+
+/// Opens the baseline in `$EDITOR`, only saving the result if it's still valid.
+fn edit(path: &Path) -> Result<(), ()> {
+    let data: BaselineData = state::load(path);
+
+    let scratch = path.with_extension("edit.json");
+    let contents = serde_json::to_string_pretty(&data).expect("baselines are always serializable");
+
+    if let Err(e) = fs::create_dir_all(path.parent().unwrap_or(path))
+        .and_then(|_| fs::write(&scratch, contents))
+    {
+        log::error!("failed to prepare baseline for editing: {e}");
+        return Err(());
+    }
+
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| String::from("vi"));
+
+    let result = match std::process::Command::new(&editor).arg(&scratch).status() {
+        Ok(status) if status.success() => fs::read(&scratch)
+            .map_err(|e| log::error!("failed to read edited baseline: {e}"))
+            .and_then(|edited| {
+                serde_json::from_slice::<BaselineData>(&edited).map_err(|e| {
+                    log::error!("edited baseline is invalid, keeping the old one: {e}")
+                })
+            })
+            .and_then(|edited| {
+                state::save(path, &edited).map_err(|e| log::error!("failed to save baseline: {e}"))
+            }),
+        Ok(status) => {
+            log::error!("{editor} exited with {status}, keeping the old baseline");
+            Err(())
+        }
+        Err(e) => {
+            log::error!("failed to start {editor}: {e}");
+            Err(())
+        }
+    };
+
+    let _ = fs::remove_file(&scratch);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn learned_titles_only_match_themselves() {
+        let change = Change {
+            item: "api*token",
+            changer: String::from("/usr/bin/security"),
+            kind: "added",
+        };
+        let entry = BaselineEntry::exactly(&change);
+
+        assert_eq!(entry.item, "api\\*token");
+        assert!(entry.covers(&change));
+        assert!(!entry.covers(&Change {
+            item: "api-other-token",
+            ..change
+        }));
+    }
+
+    #[test]
+    fn edited_entries_keep_wildcards() {
+        let entry = BaselineEntry {
+            item: String::from("com.apple.*"),
+            changer: String::from("/usr/libexec/*"),
+            kind: String::from("*"),
+        };
+
+        assert!(entry.covers(&Change {
+            item: "com.apple.account.IdentityServices.token",
+            changer: String::from("/usr/libexec/sharingd"),
+            kind: "updated",
+        }));
+        assert!(escaped_match("C:\\\\Temp\\\\*", "C:\\Temp\\x"));
+        assert!(escaped_match("old\\path", "old\\path"));
+    }
+}
@@ -20,6 +20,13 @@ pub struct SecKeychainCallbackInfo {
     pub pid: i32,
 }
 
+/// An opaque `SecStaticCode` object.
+pub type SecStaticCodeRef = *mut c_void;
+
+pub const kSecCSDefaultFlags: u32 = 0;
+/// `kSecCSDoNotValidateExecutable | kSecCSDoNotValidateResources`
+pub const kSecCSBasicValidateOnly: u32 = (1 << 1) | (1 << 2);
+
 // TODO: Put these in `security-framework-sys`?
 type SecKeychainCallback = extern "C" fn(
     keychainEvent: SecKeychainEvent,
@@ -39,10 +46,26 @@ extern "C" {
 
     pub fn SecItemCopyMatching(query: CFDictionaryRef, result: *mut CFTypeRef) -> OSStatus;
 
+    pub fn SecItemAdd(attributes: CFDictionaryRef, result: *mut CFTypeRef) -> OSStatus;
+
+    pub fn SecStaticCodeCreateWithPath(
+        path: CFURLRef,
+        flags: u32,
+        staticCode: *mut SecStaticCodeRef,
+    ) -> OSStatus;
+
+    pub fn SecStaticCodeCheckValidity(
+        staticCode: SecStaticCodeRef,
+        flags: u32,
+        requirement: *mut c_void,
+    ) -> OSStatus;
+
     pub static kSecMatchItemList: CFStringRef;
 
     pub static kSecMatchLimitOne: CFStringRef;
 
+    pub static kSecMatchLimitAll: CFStringRef;
+
     pub static kSecAttrModificationDate: CFStringRef;
 }
 
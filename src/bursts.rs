@@ -0,0 +1,514 @@
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    events::FilteredEventData,
+    pipeline::{Alert, Severity},
+    process::ProcessInfo,
+};
+
+/// How many events from one changer, within how long, count as a burst.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct BurstConfig {
+    pub(crate) events: usize,
+    pub(crate) window_secs: u64,
+}
+
+impl Default for BurstConfig {
+    fn default() -> Self {
+        Self {
+            events: 10,
+            window_secs: 60,
+        }
+    }
+}
+
+/// Settings for summarizing bursts of changes instead of notifying about each one.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct BurstsConfig {
+    pub(crate) enabled: bool,
+    /// The threshold used for any changer without one of its own.
+    #[serde(flatten)]
+    pub(crate) default: BurstConfig,
+    /// Thresholds for specific changers, like apps that are known to sync lots of items at once.
+    pub(crate) changers: Vec<ChangerBurstConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChangerBurstConfig {
+    /// An executable path, a directory containing executables, or a process or app name.
+    ///
+    /// Example: `/Applications/Google Chrome.app`
+    pub(crate) changer: String,
+    #[serde(flatten)]
+    pub(crate) threshold: BurstConfig,
+}
+
+impl BurstsConfig {
+    fn threshold_for(&self, changer: &ProcessInfo) -> &BurstConfig {
+        self.changers
+            .iter()
+            .find(|config| {
+                let by_path = changer
+                    .path
+                    .as_deref()
+                    .is_some_and(|path| path.starts_with(Path::new(&config.changer)));
+
+                by_path || changer.display_name() == Some(config.changer.as_str())
+            })
+            .map_or(&self.default, |config| &config.threshold)
+    }
+}
+
+/// What changes are grouped by when looking for bursts.
+fn changer_key(changer: &ProcessInfo) -> String {
+    match &changer.path {
+        Some(path) => path.to_string_lossy().into_owned(),
+        None => format!("pid {}", changer.pid),
+    }
+}
+
+/// Timestamps of the recent events from one changer.
+#[derive(Default)]
+struct RecentEvents {
+    times: VecDeque<f64>,
+}
+
+impl RecentEvents {
+    /// Records an event at `at`, returning how many events happened within the window before it.
+    fn record(&mut self, at: f64, window_secs: u64) -> usize {
+        let window = window_secs as f64;
+
+        self.times.push_back(at);
+
+        while let Some(first) = self.times.front() {
+            if at - first > window {
+                self.times.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        self.times.len()
+    }
+
+    fn last(&self) -> Option<f64> {
+        self.times.back().copied()
+    }
+}
+
+/// Everything one changer did during a burst.
+#[derive(Debug)]
+pub struct BurstSummary {
+    pub changer: ProcessInfo,
+    /// The most severe event that was part of the burst.
+    pub severity: Severity,
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub accessed: usize,
+    /// Keystores that were locked or unlocked.
+    pub lock_changes: usize,
+    /// The title of every item that was touched, in order.
+    pub items: Vec<String>,
+}
+
+impl BurstSummary {
+    fn new(changer: ProcessInfo) -> Self {
+        Self {
+            changer,
+            severity: Severity::Log,
+            added: 0,
+            updated: 0,
+            removed: 0,
+            accessed: 0,
+            lock_changes: 0,
+            items: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, alert: &Alert) {
+        self.severity = self.severity.max(alert.severity);
+
+        match &alert.event {
+            FilteredEventData::Added(_) => self.added += 1,
+            FilteredEventData::Updated(_) => self.updated += 1,
+            FilteredEventData::Removed { .. } => self.removed += 1,
+            FilteredEventData::Accessed(_) => self.accessed += 1,
+            FilteredEventData::LockChanged { .. } => self.lock_changes += 1,
+        }
+
+        self.items
+            .push(alert.event.item_title().unwrap_or("Unknown").to_owned());
+    }
+
+    pub fn total(&self) -> usize {
+        self.added + self.updated + self.removed + self.accessed + self.lock_changes
+    }
+
+    /// Describes what happened to items during the burst.
+    ///
+    /// Example: `added 37 items, removed 4`
+    pub fn describe(&self) -> String {
+        let counts: Vec<String> = [
+            ("added", self.added),
+            ("updated", self.updated),
+            ("removed", self.removed),
+            ("read", self.accessed),
+            ("locked or unlocked", self.lock_changes),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .enumerate()
+        .map(|(idx, (kind, count))| {
+            if idx == 0 {
+                let noun = if count == 1 { "item" } else { "items" };
+                format!("{kind} {count} {noun}")
+            } else {
+                format!("{kind} {count}")
+            }
+        })
+        .collect();
+
+        counts.join(", ")
+    }
+}
+
+struct Activity {
+    recent: RecentEvents,
+    burst: Option<Burst>,
+    window_secs: u64,
+}
+
+struct Burst {
+    summary: BurstSummary,
+    /// When the last event of the burst happened, as an event timestamp.
+    last_at: f64,
+    /// When the last event of the burst was handled, for ending bursts while no events arrive.
+    last_seen: Instant,
+    window: Duration,
+}
+
+/// Holds back events from changers that are making lots of changes at once, so they can be
+/// summarized instead of notified about one by one.
+#[derive(Default)]
+pub struct BurstAggregator {
+    activity: HashMap<String, Activity>,
+}
+
+impl BurstAggregator {
+    /// Records an alert, returning if it should be held back from sinks that interrupt the user
+    /// along with any bursts that ended because of it.
+    pub fn observe(&mut self, config: &BurstsConfig, alert: &Alert) -> (bool, Vec<BurstSummary>) {
+        if !config.enabled {
+            return (false, Vec::new());
+        }
+
+        let changer = alert.event.changer();
+        let changed_at = alert.event.changed_at();
+        let threshold = config.threshold_for(changer);
+
+        let mut ended = Vec::new();
+
+        let activity = self.activity_of(changer, changed_at, threshold);
+
+        // Going quiet for a whole window ends a burst, even if nothing noticed in the meantime.
+        if let Some(burst) = &activity.burst {
+            if changed_at - burst.last_at > threshold.window_secs as f64 {
+                ended.extend(activity.burst.take().map(|burst| burst.summary));
+            }
+        }
+
+        let recent = activity.recent.record(changed_at, threshold.window_secs);
+
+        // Critical events are always important enough to interrupt on their own.
+        if alert.severity >= Severity::Critical {
+            return (false, ended);
+        }
+
+        let burst = match &mut activity.burst {
+            Some(burst) => burst,
+            None if recent > threshold.events => {
+                log::info!(
+                    "{} is making lots of changes, summarizing them until it stops",
+                    changer.display_name().unwrap_or("Unknown")
+                );
+
+                activity.burst.insert(Burst {
+                    summary: BurstSummary::new(changer.clone()),
+                    last_at: changed_at,
+                    last_seen: Instant::now(),
+                    window: Duration::from_secs(threshold.window_secs),
+                })
+            }
+            None => return (false, ended),
+        };
+
+        burst.summary.add(alert);
+        burst.last_at = changed_at;
+        burst.last_seen = Instant::now();
+
+        (true, ended)
+    }
+
+    /// Records a change by `changer` without holding anything back, returning how many changes
+    /// it made within the window of `threshold`, counting this one.
+    pub fn record(
+        &mut self,
+        changer: &ProcessInfo,
+        changed_at: f64,
+        threshold: &BurstConfig,
+    ) -> usize {
+        self.activity_of(changer, changed_at, threshold)
+            .recent
+            .record(changed_at, threshold.window_secs)
+    }
+
+    fn activity_of(
+        &mut self,
+        changer: &ProcessInfo,
+        changed_at: f64,
+        threshold: &BurstConfig,
+    ) -> &mut Activity {
+        // Changers that went quiet don't need remembering anymore.
+        self.activity.retain(|_, activity| {
+            activity.burst.is_some()
+                || activity
+                    .recent
+                    .last()
+                    .is_some_and(|last| changed_at - last <= activity.window_secs as f64)
+        });
+
+        self.activity
+            .entry(changer_key(changer))
+            .or_insert_with(|| Activity {
+                recent: RecentEvents::default(),
+                burst: None,
+                window_secs: threshold.window_secs,
+            })
+    }
+
+    /// Ends every burst that hasn't had a new event for a whole window.
+    pub fn expire(&mut self) -> Vec<BurstSummary> {
+        self.activity
+            .values_mut()
+            .filter(|activity| {
+                activity
+                    .burst
+                    .as_ref()
+                    .is_some_and(|burst| burst.last_seen.elapsed() >= burst.window)
+            })
+            .filter_map(|activity| activity.burst.take())
+            .map(|burst| burst.summary)
+            .collect()
+    }
+
+    /// Ends every burst, for when no more events are coming.
+    pub fn drain(&mut self) -> Vec<BurstSummary> {
+        self.activity
+            .values_mut()
+            .filter_map(|activity| activity.burst.take())
+            .map(|burst| burst.summary)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Backend, InnerDetails};
+
+    /// Bursts of more than 3 events within 10 seconds, or 1 for `/usr/bin/sync-tool`.
+    fn config() -> BurstsConfig {
+        BurstsConfig {
+            enabled: true,
+            default: BurstConfig {
+                events: 3,
+                window_secs: 10,
+            },
+            changers: vec![ChangerBurstConfig {
+                changer: String::from("/usr/bin/sync-tool"),
+                threshold: BurstConfig {
+                    events: 1,
+                    window_secs: 10,
+                },
+            }],
+        }
+    }
+
+    fn changer(path: &str) -> ProcessInfo {
+        ProcessInfo {
+            path: Some(path.into()),
+            ..ProcessInfo::unknown(100)
+        }
+    }
+
+    fn alert(changer: &ProcessInfo, at: f64, severity: Severity) -> Alert {
+        Alert {
+            event: FilteredEventData::Added(InnerDetails::new(
+                Backend::Keychain,
+                format!("item at {at}"),
+                at,
+                changer.clone(),
+            )),
+            severity,
+            reasons: Vec::new(),
+            score: None,
+            detected_by_scan: false,
+        }
+    }
+
+    /// Observes an event from `changer` at each time, returning which were held back.
+    fn observe(
+        bursts: &mut BurstAggregator,
+        changer: &ProcessInfo,
+        times: &[f64],
+    ) -> (Vec<bool>, Vec<BurstSummary>) {
+        let mut ended = Vec::new();
+        let held = times
+            .iter()
+            .map(|at| {
+                let (held, summaries) =
+                    bursts.observe(&config(), &alert(changer, *at, Severity::Notice));
+                ended.extend(summaries);
+                held
+            })
+            .collect();
+
+        (held, ended)
+    }
+
+    #[test]
+    fn holds_back_events_past_the_threshold() {
+        let mut bursts = BurstAggregator::default();
+        let shell = changer("/usr/bin/shell");
+
+        let (held, ended) = observe(&mut bursts, &shell, &[0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(held, [false, false, false, true, true]);
+        assert!(ended.is_empty());
+
+        let summaries = bursts.drain();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].added, 2);
+        assert_eq!(summaries[0].items, ["item at 3", "item at 4"]);
+    }
+
+    #[test]
+    fn only_events_within_the_window_count() {
+        let mut bursts = BurstAggregator::default();
+        let shell = changer("/usr/bin/shell");
+
+        let (held, _) = observe(&mut bursts, &shell, &[0.0, 5.0, 11.0, 16.0, 22.0, 27.0]);
+        assert_eq!(held, [false; 6]);
+        assert!(bursts.drain().is_empty());
+    }
+
+    #[test]
+    fn going_quiet_for_a_window_ends_a_burst() {
+        let mut bursts = BurstAggregator::default();
+        let shell = changer("/usr/bin/shell");
+
+        observe(&mut bursts, &shell, &[0.0, 1.0, 2.0, 3.0]);
+
+        // The next event ends the burst, and isn't part of one itself.
+        let (held, ended) = observe(&mut bursts, &shell, &[14.0]);
+        assert_eq!(held, [false]);
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].total(), 1);
+        assert!(bursts.drain().is_empty());
+    }
+
+    #[test]
+    fn ends_bursts_without_new_events() {
+        let mut bursts = BurstAggregator::default();
+        let config = BurstsConfig {
+            default: BurstConfig {
+                events: 1,
+                window_secs: 0,
+            },
+            ..config()
+        };
+        let shell = changer("/usr/bin/shell");
+
+        for at in [0.0, 0.0] {
+            bursts.observe(&config, &alert(&shell, at, Severity::Notice));
+        }
+
+        let expired = bursts.expire();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].changer.path, shell.path);
+        assert!(bursts.expire().is_empty());
+    }
+
+    #[test]
+    fn groups_events_by_changer() {
+        let mut bursts = BurstAggregator::default();
+        let (shell, editor) = (changer("/usr/bin/shell"), changer("/usr/bin/editor"));
+
+        for at in [0.0, 1.0, 2.0] {
+            observe(&mut bursts, &shell, &[at]);
+            observe(&mut bursts, &editor, &[at]);
+        }
+        assert!(bursts.drain().is_empty());
+
+        // Changers with a threshold of their own burst sooner.
+        let (held, _) = observe(&mut bursts, &changer("/usr/bin/sync-tool"), &[0.0, 1.0]);
+        assert_eq!(held, [false, true]);
+
+        let (held, _) = observe(&mut bursts, &shell, &[3.0]);
+        assert_eq!(held, [true]);
+
+        let mut summaries: Vec<_> = bursts
+            .drain()
+            .into_iter()
+            .map(|summary| (summary.total(), summary.changer.path.unwrap()))
+            .collect();
+        summaries.sort();
+        assert_eq!(
+            summaries,
+            [
+                (1, "/usr/bin/shell".into()),
+                (1, "/usr/bin/sync-tool".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn critical_events_are_never_held_back() {
+        let mut bursts = BurstAggregator::default();
+        let shell = changer("/usr/bin/shell");
+
+        observe(&mut bursts, &shell, &[0.0, 1.0, 2.0, 3.0]);
+
+        let (held, ended) = bursts.observe(&config(), &alert(&shell, 4.0, Severity::Critical));
+        assert!(!held);
+        assert!(ended.is_empty());
+
+        // They still count towards the burst, without being part of its summary.
+        let summaries = bursts.drain();
+        assert_eq!(summaries[0].total(), 1);
+        assert_eq!(summaries[0].severity, Severity::Notice);
+    }
+
+    #[test]
+    fn nothing_is_held_back_when_disabled() {
+        let mut bursts = BurstAggregator::default();
+        let config = BurstsConfig {
+            enabled: false,
+            ..config()
+        };
+        let shell = changer("/usr/bin/shell");
+
+        for at in [0.0, 1.0, 2.0, 3.0, 4.0] {
+            let (held, _) = bursts.observe(&config, &alert(&shell, at, Severity::Notice));
+            assert!(!held);
+        }
+        assert!(bursts.drain().is_empty());
+    }
+}
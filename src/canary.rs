@@ -0,0 +1,385 @@
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    fs::File,
+    io::{self, Read},
+};
+
+use crate::events::{Backend, FilteredEventData};
+
+#[cfg(target_os = "macos")]
+mod keychain;
+#[cfg(target_os = "linux")]
+mod secret_service;
+
+/// A decoy item that nothing should ever touch.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct CanaryConfig {
+    /// The item's title, which is how events to the keystore it's planted in are matched to it.
+    pub(crate) item: String,
+    /// The account name to plant the item with. Pick something tempting.
+    #[serde(default = "default_account")]
+    pub(crate) account: String,
+}
+
+fn default_account() -> String {
+    String::from("admin")
+}
+
+/// Somewhere canaries can be planted and checked on.
+pub trait CanaryStore {
+    /// Plants the canary with a made up secret, unless it's already there.
+    fn create(&mut self, canary: &CanaryConfig, secret: &str) -> Result<(), String>;
+
+    /// Checks if the canary is still there, without reading its secret.
+    fn exists(&mut self, canary: &CanaryConfig) -> Result<bool, String>;
+
+    /// Which keystore the canaries are planted in.
+    fn backend(&self) -> Backend;
+}
+
+/// The canary store for this platform's keystore, if it can be written to.
+#[cfg(target_os = "macos")]
+pub fn platform_store() -> Option<Box<dyn CanaryStore>> {
+    Some(Box::new(keychain::KeychainCanaries))
+}
+
+#[cfg(target_os = "linux")]
+pub fn platform_store() -> Option<Box<dyn CanaryStore>> {
+    Some(Box::new(secret_service::SecretServiceCanaries::default()))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn platform_store() -> Option<Box<dyn CanaryStore>> {
+    None
+}
+
+/// The configured canaries, along with what's known about them.
+pub struct Canaries {
+    canaries: Vec<CanaryConfig>,
+    store: Option<Box<dyn CanaryStore>>,
+    /// Canaries that were already found missing, so they're only reported once.
+    missing: HashSet<String>,
+}
+
+impl Canaries {
+    pub fn new(canaries: &[CanaryConfig]) -> Self {
+        Self {
+            canaries: canaries.to_vec(),
+            store: None,
+            missing: HashSet::new(),
+        }
+    }
+
+    /// Lets removals be matched to canaries, by checking which ones went missing.
+    pub fn set_store(&mut self, store: Box<dyn CanaryStore>) {
+        self.store = Some(store);
+        self.missing = self.find_missing().into_iter().collect();
+
+        for item in &self.missing {
+            log::warn!(
+                "canary {item} hasn't been planted, create it with `keeper_of_keys canary create`"
+            );
+        }
+    }
+
+    /// Returns the canary `event` touched, if any. Items in other keystores can share a
+    /// canary's title without being it.
+    pub fn touched_by(&mut self, event: &FilteredEventData) -> Option<String> {
+        if self.store.as_ref()?.backend() != event.backend() {
+            return None;
+        }
+
+        if let Some(item_title) = event.item_title() {
+            if let Some(canary) = self.canaries.iter().find(|c| c.item == item_title) {
+                self.missing.remove(&canary.item);
+                return Some(canary.item.clone());
+            }
+
+            return None;
+        }
+
+        // Removals don't say what was removed, so the only way to tell is looking for what's gone.
+        let missing = self.find_missing();
+        let newly_missing = missing
+            .iter()
+            .find(|item| !self.missing.contains(*item))
+            .cloned();
+
+        self.missing = missing.into_iter().collect();
+        newly_missing
+    }
+
+    fn find_missing(&mut self) -> Vec<String> {
+        let store = match &mut self.store {
+            Some(store) => store,
+            None => return Vec::new(),
+        };
+
+        self.canaries
+            .iter()
+            .filter(|canary| match store.exists(canary) {
+                Ok(exists) => !exists,
+                Err(e) => {
+                    log::warn!("failed to check on canary {}: {e}", canary.item);
+                    false
+                }
+            })
+            .map(|canary| canary.item.clone())
+            .collect()
+    }
+}
+
+/// A random secret that looks real enough to be worth stealing.
+fn make_secret() -> io::Result<String> {
+    let mut bytes = [0u8; 24];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+
+    let mut secret = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(secret, "{byte:02x}");
+    }
+
+    Ok(secret)
+}
+
+/// Handles `canary <list|create|verify>`.
+pub fn run_command(
+    mut args: impl Iterator<Item = String>,
+    canaries: &[CanaryConfig],
+) -> Result<(), ()> {
+    let command = args.next();
+
+    if command.as_deref() == Some("list") {
+        for canary in canaries {
+            println!("{} (account {})", canary.item, canary.account);
+        }
+
+        return Ok(());
+    }
+
+    let mut store = match platform_store() {
+        Some(store) => store,
+        None => {
+            log::error!("canaries can't be planted or checked on this platform");
+            return Err(());
+        }
+    };
+
+    match command.as_deref() {
+        Some("create") => {
+            let mut result = Ok(());
+
+            for canary in canaries {
+                let created = make_secret()
+                    .map_err(|e| format!("failed to make up a secret: {e}"))
+                    .and_then(|secret| store.create(canary, &secret));
+
+                match created {
+                    Ok(()) => log::info!("planted canary {}", canary.item),
+                    Err(e) => {
+                        log::error!("failed to plant canary {}: {e}", canary.item);
+                        result = Err(());
+                    }
+                }
+            }
+
+            result
+        }
+        Some("verify") => {
+            let mut result = Ok(());
+
+            for canary in canaries {
+                match store.exists(canary) {
+                    Ok(true) => println!("{}: present", canary.item),
+                    Ok(false) => {
+                        println!("{}: missing", canary.item);
+                        result = Err(());
+                    }
+                    Err(e) => {
+                        log::error!("failed to check on canary {}: {e}", canary.item);
+                        result = Err(());
+                    }
+                }
+            }
+
+            result
+        }
+        other => {
+            log::error!(
+                "unknown canary command {:?}, expected one of list, create or verify",
+                other.unwrap_or_default()
+            );
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{events::InnerDetails, process::ProcessInfo};
+
+    /// Canaries in the Secret Service, which are only there while they're in `planted`.
+    struct FakeStore {
+        planted: Rc<RefCell<HashSet<String>>>,
+        /// How many times a canary was checked on.
+        checks: Rc<RefCell<usize>>,
+    }
+
+    impl CanaryStore for FakeStore {
+        fn create(&mut self, canary: &CanaryConfig, _secret: &str) -> Result<(), String> {
+            self.planted.borrow_mut().insert(canary.item.clone());
+            Ok(())
+        }
+
+        fn exists(&mut self, canary: &CanaryConfig) -> Result<bool, String> {
+            *self.checks.borrow_mut() += 1;
+            Ok(self.planted.borrow().contains(&canary.item))
+        }
+
+        fn backend(&self) -> Backend {
+            Backend::SecretService
+        }
+    }
+
+    /// Canaries named after each of `items`, all planted.
+    fn planted(items: &[&str]) -> (Canaries, FakeStore) {
+        watching(items, items)
+    }
+
+    /// Canaries named after each of `items`, with only the ones in `planted` there.
+    fn watching(items: &[&str], planted: &[&str]) -> (Canaries, FakeStore) {
+        let configs: Vec<CanaryConfig> = items
+            .iter()
+            .map(|item| CanaryConfig {
+                item: (*item).to_owned(),
+                account: default_account(),
+            })
+            .collect();
+
+        let store = FakeStore {
+            planted: Rc::new(RefCell::new(
+                planted.iter().map(|item| (*item).to_owned()).collect(),
+            )),
+            checks: Rc::new(RefCell::new(0)),
+        };
+        let handle = FakeStore {
+            planted: Rc::clone(&store.planted),
+            checks: Rc::clone(&store.checks),
+        };
+
+        let mut canaries = Canaries::new(&configs);
+        canaries.set_store(Box::new(store));
+        (canaries, handle)
+    }
+
+    fn added(backend: Backend, title: &str) -> FilteredEventData {
+        FilteredEventData::Added(InnerDetails::new(
+            backend,
+            title.to_owned(),
+            0.0,
+            ProcessInfo::unknown(100),
+        ))
+    }
+
+    fn removed(backend: Backend, title: Option<&str>) -> FilteredEventData {
+        FilteredEventData::Removed {
+            backend,
+            seen_at: 0.0,
+            modified_by: ProcessInfo::unknown(100),
+            item_name: title.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn only_items_in_the_canarys_keystore_touch_it() {
+        let (mut canaries, _store) = planted(&["aws-root"]);
+
+        for backend in [Backend::SshAgent, Backend::CredentialFile, Backend::KWallet] {
+            assert_eq!(canaries.touched_by(&added(backend, "aws-root")), None);
+            assert_eq!(canaries.touched_by(&removed(backend, None)), None);
+        }
+
+        assert_eq!(
+            canaries.touched_by(&added(Backend::SecretService, "aws-root")),
+            Some(String::from("aws-root"))
+        );
+    }
+
+    #[test]
+    fn titled_events_touch_canaries_without_checking_on_them() {
+        let (mut canaries, store) = planted(&["aws-root", "prod-db"]);
+        let checks = *store.checks.borrow();
+
+        assert_eq!(
+            canaries.touched_by(&added(Backend::SecretService, "prod-db")),
+            Some(String::from("prod-db"))
+        );
+        assert_eq!(
+            canaries.touched_by(&removed(Backend::SecretService, Some("aws-root"))),
+            Some(String::from("aws-root"))
+        );
+        assert_eq!(
+            canaries.touched_by(&added(Backend::SecretService, "github.com")),
+            None
+        );
+        assert_eq!(*store.checks.borrow(), checks);
+    }
+
+    #[test]
+    fn untitled_removals_touch_canaries_that_went_missing() {
+        let (mut canaries, store) = planted(&["aws-root", "prod-db"]);
+        let removal = removed(Backend::SecretService, None);
+
+        // Something else was removed.
+        assert_eq!(canaries.touched_by(&removal), None);
+
+        store.planted.borrow_mut().remove("prod-db");
+        assert_eq!(canaries.touched_by(&removal), Some(String::from("prod-db")));
+        // It's only reported the first time it's found missing.
+        assert_eq!(canaries.touched_by(&removal), None);
+
+        store.planted.borrow_mut().remove("aws-root");
+        assert_eq!(
+            canaries.touched_by(&removal),
+            Some(String::from("aws-root"))
+        );
+    }
+
+    #[test]
+    fn canaries_missing_from_the_start_are_reported_once_replanted() {
+        let (mut canaries, store) = watching(&["aws-root", "prod-db"], &["aws-root"]);
+        assert_eq!(canaries.find_missing(), [String::from("prod-db")]);
+
+        // Missing before anything was removed, so no removal took it.
+        let removal = removed(Backend::SecretService, None);
+        assert_eq!(canaries.touched_by(&removal), None);
+
+        // Once it's back, taking it again counts.
+        store.planted.borrow_mut().insert(String::from("prod-db"));
+        assert_eq!(
+            canaries.touched_by(&added(Backend::SecretService, "prod-db")),
+            Some(String::from("prod-db"))
+        );
+        store.planted.borrow_mut().remove("prod-db");
+        assert_eq!(canaries.touched_by(&removal), Some(String::from("prod-db")));
+    }
+
+    #[test]
+    fn nothing_touches_canaries_without_a_store() {
+        let mut canaries = Canaries::new(&[CanaryConfig {
+            item: String::from("aws-root"),
+            account: default_account(),
+        }]);
+
+        assert_eq!(canaries.find_missing(), Vec::<String>::new());
+        assert_eq!(
+            canaries.touched_by(&added(Backend::SecretService, "aws-root")),
+            None
+        );
+    }
+}
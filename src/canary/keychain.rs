@@ -0,0 +1,109 @@
+use core_foundation::{
+    base::{CFType, TCFType},
+    boolean::CFBoolean,
+    data::CFData,
+    dictionary::CFDictionary,
+    string::CFString,
+};
+use security_framework_sys::{
+    base::{errSecDuplicateItem, errSecItemNotFound},
+    item::{
+        kSecAttrAccount, kSecAttrLabel, kSecAttrService, kSecClass, kSecClassGenericPassword,
+        kSecMatchLimit, kSecReturnAttributes, kSecValueData,
+    },
+};
+
+use super::{CanaryConfig, CanaryStore};
+use crate::{bindings, events::Backend};
+
+/// Plants canaries as generic passwords in the login keychain.
+pub struct KeychainCanaries;
+
+impl CanaryStore for KeychainCanaries {
+    fn create(&mut self, canary: &CanaryConfig, secret: &str) -> Result<(), String> {
+        let label = CFString::new(&canary.item);
+        let account = CFString::new(&canary.account);
+        let secret = CFData::from_buffer(secret.as_bytes());
+
+        let attributes = unsafe {
+            CFDictionary::from_CFType_pairs(&[
+                (
+                    CFString::wrap_under_get_rule(kSecClass),
+                    CFType::wrap_under_get_rule(kSecClassGenericPassword.cast()),
+                ),
+                (
+                    CFString::wrap_under_get_rule(kSecAttrLabel),
+                    label.as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(kSecAttrService),
+                    label.as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(kSecAttrAccount),
+                    account.as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(kSecValueData),
+                    secret.as_CFType(),
+                ),
+            ])
+        };
+
+        let status =
+            unsafe { bindings::SecItemAdd(attributes.as_concrete_TypeRef(), std::ptr::null_mut()) };
+
+        match status {
+            0 => Ok(()),
+            errSecDuplicateItem => {
+                log::info!("canary {} was already planted", canary.item);
+                Ok(())
+            }
+            code => Err(format!("keychain error {code}")),
+        }
+    }
+
+    fn exists(&mut self, canary: &CanaryConfig) -> Result<bool, String> {
+        let label = CFString::new(&canary.item);
+
+        let query = unsafe {
+            CFDictionary::from_CFType_pairs(&[
+                (
+                    CFString::wrap_under_get_rule(kSecClass),
+                    CFType::wrap_under_get_rule(kSecClassGenericPassword.cast()),
+                ),
+                (
+                    CFString::wrap_under_get_rule(kSecAttrLabel),
+                    label.as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(kSecReturnAttributes),
+                    CFBoolean::true_value().as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(kSecMatchLimit),
+                    CFType::wrap_under_get_rule(bindings::kSecMatchLimitOne.cast()),
+                ),
+            ])
+        };
+
+        let mut attributes = std::ptr::null();
+
+        let status =
+            unsafe { bindings::SecItemCopyMatching(query.as_concrete_TypeRef(), &mut attributes) };
+
+        match status {
+            0 => {
+                // Only the attributes were asked for, but they still need releasing.
+                drop(unsafe { CFType::wrap_under_create_rule(attributes) });
+                Ok(true)
+            }
+            errSecItemNotFound => Ok(false),
+            code => Err(format!("keychain error {code}")),
+        }
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Keychain
+    }
+}
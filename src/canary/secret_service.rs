@@ -0,0 +1,148 @@
+use super::{CanaryConfig, CanaryStore};
+use crate::{
+    dbus::{Connection, Value},
+    events::Backend,
+    items::secret_service::{
+        COLLECTION_INTERFACE, ITEM_INTERFACE, SERVICE_INTERFACE, SERVICE_NAME, SERVICE_PATH,
+    },
+};
+
+/// Where new items go when nothing says otherwise, usually the login collection.
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+const GENERIC_SCHEMA: &str = "org.freedesktop.Secret.Generic";
+
+/// Plants canaries as generic secrets in the default collection.
+#[derive(Default)]
+pub struct SecretServiceCanaries {
+    /// The session bus, connected to the first time it's needed since canaries are checked on
+    /// every removal.
+    connection: Option<Connection>,
+}
+
+impl SecretServiceCanaries {
+    /// Calls a method of the Secret Service. A connection that failed is dropped, so the next
+    /// call connects again in case the bus went away.
+    fn call(
+        &mut self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        args: Vec<Value>,
+    ) -> Result<Vec<Value>, String> {
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => self
+                .connection
+                .insert(Connection::session().map_err(|e| e.to_string())?),
+        };
+
+        connection
+            .call(SERVICE_NAME, path, interface, method, args)
+            .map_err(|e| {
+                self.connection = None;
+                e.to_string()
+            })
+    }
+}
+
+impl CanaryStore for SecretServiceCanaries {
+    fn create(&mut self, canary: &CanaryConfig, secret: &str) -> Result<(), String> {
+        if self.exists(canary)? {
+            log::info!("canary {} was already planted", canary.item);
+            return Ok(());
+        }
+
+        // The secret is made up anyway, so there's no point in encrypting it on the way over.
+        let session = self.call(
+            SERVICE_PATH,
+            SERVICE_INTERFACE,
+            "OpenSession",
+            vec![
+                Value::Str(String::from("plain")),
+                Value::variant(Value::Str(String::new())),
+            ],
+        )?;
+
+        let session = match session.get(1) {
+            Some(Value::ObjectPath(path)) => path.clone(),
+            _ => return Err(String::from("OpenSession didn't return a session")),
+        };
+
+        let properties = Value::dict(
+            "s",
+            "v",
+            vec![
+                (
+                    Value::Str(format!("{ITEM_INTERFACE}.Label")),
+                    Value::variant(Value::Str(canary.item.clone())),
+                ),
+                (
+                    Value::Str(format!("{ITEM_INTERFACE}.Attributes")),
+                    Value::variant(lookup_attributes(canary, true)),
+                ),
+            ],
+        );
+
+        let secret = Value::Struct(vec![
+            Value::ObjectPath(session),
+            Value::Array(String::from("y"), Vec::new()),
+            Value::Array(String::from("y"), secret.bytes().map(Value::Byte).collect()),
+            Value::Str(String::from("text/plain")),
+        ]);
+
+        let reply = self.call(
+            DEFAULT_COLLECTION,
+            COLLECTION_INTERFACE,
+            "CreateItem",
+            vec![properties, secret, Value::Bool(false)],
+        )?;
+
+        // A prompt means the collection has to be unlocked first, which can't be done unattended.
+        match reply.get(1) {
+            Some(Value::ObjectPath(prompt)) if prompt != "/" => {
+                Err(String::from("default collection is locked"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn exists(&mut self, canary: &CanaryConfig) -> Result<bool, String> {
+        let found = self.call(
+            SERVICE_PATH,
+            SERVICE_INTERFACE,
+            "SearchItems",
+            vec![lookup_attributes(canary, false)],
+        )?;
+
+        // Locked items count too, since they're still there.
+        Ok(found
+            .iter()
+            .any(|items| items.as_array().is_some_and(|items| !items.is_empty())))
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::SecretService
+    }
+}
+
+fn lookup_attributes(canary: &CanaryConfig, with_schema: bool) -> Value {
+    let mut attributes = vec![
+        (
+            Value::Str(String::from("service")),
+            Value::Str(canary.item.clone()),
+        ),
+        (
+            Value::Str(String::from("username")),
+            Value::Str(canary.account.clone()),
+        ),
+    ];
+
+    if with_schema {
+        attributes.push((
+            Value::Str(String::from("xdg:schema")),
+            Value::Str(String::from(GENERIC_SCHEMA)),
+        ));
+    }
+
+    Value::dict("s", "s", attributes)
+}
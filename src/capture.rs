@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{events::EventData, pipeline::EventSource};
+
+/// Subdirectory of the data directory that captures are written into.
+pub const CAPTURE_DIR: &str = "Captures";
+
+/// A raw event as it came out of a monitor, before any coalescing or filtering.
+///
+/// Capture files are made of one of these per line, serialized as JSON.
+#[derive(Debug, Deserialize)]
+pub struct CapturedEvent {
+    /// Milliseconds since the capture started.
+    at_ms: u64,
+    event: EventData,
+}
+
+#[derive(Serialize)]
+struct CapturedEventRef<'a> {
+    at_ms: u64,
+    event: &'a EventData,
+}
+
+/// Wraps an event source, appending everything received from it to a capture file.
+pub struct Recorder<S> {
+    source: S,
+    file: File,
+    started: Instant,
+}
+
+impl<S: EventSource> Recorder<S> {
+    pub fn create(source: S, data_dir: &Path) -> io::Result<Self> {
+        let dir = data_dir.join(CAPTURE_DIR);
+        fs::create_dir_all(&dir)?;
+
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let path = dir.join(format!("capture-{stamp}.jsonl"));
+        let file = File::options().create_new(true).append(true).open(&path)?;
+
+        log::info!("capturing raw events to {}", path.display());
+
+        Ok(Self {
+            source,
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    fn record(&mut self, event: &EventData) {
+        let captured = CapturedEventRef {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            event,
+        };
+
+        // Each line is written in one go so that a crash can't leave half an event behind.
+        let mut line = serde_json::to_string(&captured).expect("events are always serializable");
+        line.push('\n');
+
+        if let Err(e) = self.file.write_all(line.as_bytes()) {
+            log::warn!("failed to write captured event: {e}");
+        }
+    }
+}
+
+impl<S: EventSource> EventSource for Recorder<S> {
+    fn recv(&mut self) -> Option<EventData> {
+        let event = self.source.recv()?;
+        self.record(&event);
+        Some(event)
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<EventData, RecvTimeoutError> {
+        let event = self.source.recv_timeout(timeout)?;
+        self.record(&event);
+        Ok(event)
+    }
+}
+
+/// Loads every event from a capture file, in the order they were recorded.
+pub fn read_capture(path: &Path) -> io::Result<Vec<CapturedEvent>> {
+    let reader = BufReader::new(File::open(path)?);
+
+    let mut events = Vec::new();
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {e}", idx + 1))
+        })?;
+
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+/// Replays a capture as fast as possible.
+///
+/// Instead of sleeping, a virtual clock is moved forward so that timeouts behave exactly
+/// like they did when the events were recorded. This keeps coalescing deterministic.
+pub struct FastReplay {
+    events: VecDeque<CapturedEvent>,
+    now_ms: u64,
+}
+
+impl FastReplay {
+    pub fn new(events: Vec<CapturedEvent>) -> Self {
+        Self {
+            events: events.into(),
+            now_ms: 0,
+        }
+    }
+}
+
+impl EventSource for FastReplay {
+    fn recv(&mut self) -> Option<EventData> {
+        let next = self.events.pop_front()?;
+        self.now_ms = self.now_ms.max(next.at_ms);
+        Some(next.event)
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<EventData, RecvTimeoutError> {
+        let deadline = self.now_ms + timeout.as_millis() as u64;
+
+        match self.events.front() {
+            Some(next) if next.at_ms <= deadline => {
+                self.recv().ok_or(RecvTimeoutError::Disconnected)
+            }
+            Some(_) => {
+                self.now_ms = deadline;
+                Err(RecvTimeoutError::Timeout)
+            }
+            None => Err(RecvTimeoutError::Disconnected),
+        }
+    }
+}
+
+/// Replays a capture with the same gaps between events as when it was recorded.
+pub fn replay_realtime(events: Vec<CapturedEvent>) -> mpsc::Receiver<EventData> {
+    let (tx, event_source) = mpsc::channel();
+
+    thread::Builder::new()
+        .name(String::from("Capture Replay"))
+        .spawn(move || {
+            let started = Instant::now();
+
+            for captured in events {
+                let at = Duration::from_millis(captured.at_ms);
+                if let Some(wait) = at.checked_sub(started.elapsed()) {
+                    thread::sleep(wait);
+                }
+
+                if tx.send(captured.event).is_err() {
+                    break;
+                }
+            }
+        })
+        .expect("failed to start capture replay");
+
+    event_source
+}
@@ -0,0 +1,101 @@
+use std::{
+    mem,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Seconds between the Unix epoch and the Core Foundation one, which event timestamps are relative to.
+const CF_EPOCH_OFFSET: f64 = 978_307_200.0;
+
+/// Converts an event timestamp into seconds since the Unix epoch.
+pub fn cf_to_unix(abs_time: f64) -> i64 {
+    (abs_time + CF_EPOCH_OFFSET).floor() as i64
+}
+
+/// Converts seconds since the Unix epoch into a Core Foundation absolute time.
+pub fn unix_to_cf(secs: u64) -> f64 {
+    secs as f64 - CF_EPOCH_OFFSET
+}
+
+/// Converts a date and time in UTC into a Core Foundation absolute time.
+pub fn utc_to_cf(year: i32, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> f64 {
+    let mut tm: libc::tm = unsafe { mem::zeroed() };
+    tm.tm_year = year - 1900;
+    tm.tm_mon = i32::from(month) - 1;
+    tm.tm_mday = i32::from(day);
+    tm.tm_hour = i32::from(hour);
+    tm.tm_min = i32::from(minute);
+    tm.tm_sec = i32::from(second);
+
+    let secs = unsafe { libc::timegm(&mut tm) };
+    secs as f64 - CF_EPOCH_OFFSET
+}
+
+/// Formats a Core Foundation absolute time as a date in UTC, like `2031-05-01`.
+pub fn format_date(abs_time: f64) -> String {
+    let time = cf_to_unix(abs_time) as libc::time_t;
+    let mut tm: libc::tm = unsafe { mem::zeroed() };
+    unsafe { libc::gmtime_r(&time, &mut tm) };
+
+    format!(
+        "{:04}-{:02}-{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday
+    )
+}
+
+/// The current time as a Core Foundation absolute time, like event timestamps.
+pub fn cf_now() -> f64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+
+    (now - CF_EPOCH_OFFSET).floor()
+}
+
+/// A point in time, as seen on the wall clock of this machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalTime {
+    /// Days since Sunday, from 0 to 6.
+    pub weekday: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl LocalTime {
+    pub fn at_unix(secs: i64) -> Self {
+        let time = secs as libc::time_t;
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+
+        // If this fails, `tm` is left zeroed which reads as midnight on a Sunday. That's not
+        // right, but it's a valid time that can't cause anything to panic.
+        unsafe { libc::localtime_r(&time, &mut tm) };
+
+        Self {
+            weekday: tm.tm_wday as u8,
+            hour: tm.tm_hour as u8,
+            minute: tm.tm_min as u8,
+            // Leap seconds show up as 60.
+            second: tm.tm_sec.min(59) as u8,
+        }
+    }
+
+    pub fn minute_of_day(&self) -> u16 {
+        u16::from(self.hour) * 60 + u16::from(self.minute)
+    }
+
+    /// Seconds since the last midnight.
+    pub fn second_of_day(&self) -> u64 {
+        u64::from(self.hour) * 3600 + u64::from(self.minute) * 60 + u64::from(self.second)
+    }
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -1,32 +1,132 @@
 use serde::Deserialize;
 use std::path::Path;
 
+use crate::{
+    access::{AccessProxyConfig, FileAccessConfig},
+    baseline::LearningConfig,
+    bursts::BurstsConfig,
+    canary::CanaryConfig,
+    events::{CredentialFilesConfig, GpgAgentConfig, SshAgentConfig, TrustStoreConfig},
+    inventory::InventoryConfig,
+    items::IgnoredItem,
+    lists::ListConfig,
+    scoring::ScoringConfig,
+    sinks::{DigestConfig, QuietHours, SinkConfig},
+};
+
 /// Configuration for Keeper of Keys.
 ///
 /// This config is meant to be stored in ~/Library/Containers/<bundleid>/Data/config.toml.
 #[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 pub(crate) struct Config {
     /// List of keychain items that notifications should be
-    /// suppressed for.
+    /// suppressed for, either by title or by a table of attributes to match.
+    ///
+    /// Example: `handoff-own-encryption-key` or `{ server = "github.com", account = "bot-*" }`
+    pub(crate) ignored_items: Vec<IgnoredItem>,
+
+    /// If every raw keychain event should be recorded to a file in the
+    /// data directory's `Captures` folder.
+    ///
+    /// These files can be fed back through the app with `keeper_of_keys replay <file>`.
+    pub(crate) capture_events: bool,
+
+    /// Executables that are known to be bad.
+    ///
+    /// Changes made by these, or by anything they launched, always
+    /// notify at critical priority, even if the item is ignored.
+    pub(crate) blocklist: ListConfig,
+
+    /// Executables that are known to be good.
+    ///
+    /// Changes made by these are only logged, unless their executable
+    /// was modified since it was first seen.
+    pub(crate) allowlist: ListConfig,
+
+    /// Learning mode, which quietly builds a baseline of normal activity
+    /// and afterwards only notifies about changes that deviate from it.
+    pub(crate) learning: LearningConfig,
+
+    /// Scoring how unusual each change looks, which then decides its severity.
+    pub(crate) scoring: ScoringConfig,
+
+    /// Which severities each kind of output is used for.
+    ///
+    /// Example: `notifications = "elevated"` only shows banners for unusual changes.
+    pub(crate) sinks: SinkConfig,
+
+    /// Summarizing bursts of changes from one changer into a single notification.
+    pub(crate) bursts: BurstsConfig,
+
+    /// Sending a summary of changes on a schedule, instead of a notification for each one.
+    pub(crate) digest: DigestConfig,
+
+    /// Times when notifications are saved up and sent as one catch-up summary afterwards.
     ///
-    /// Example: `handoff-own-encryption-key`
-    pub(crate) ignored_items: Vec<String>,
+    /// Example: `[[quiet_hours]]` with `days = ["mon", "tue"]`, `start = "22:00"` and `end = "07:00"`
+    pub(crate) quiet_hours: Vec<QuietHours>,
+
+    /// Decoy items that nothing should ever touch.
+    ///
+    /// Any change to one of these is always notified about at critical
+    /// priority, no matter what else the config says.
+    pub(crate) canaries: Vec<CanaryConfig>,
+
+    /// Taking stock of every item at startup and periodically, to catch
+    /// changes that were made while the monitor wasn't running.
+    pub(crate) inventory: InventoryConfig,
+
+    /// Passing the session bus through a proxy that sees every secret
+    /// that's read, and can refuse reads by executables that aren't approved.
+    ///
+    /// Only available on Linux.
+    pub(crate) access_proxy: AccessProxyConfig,
+
+    /// Watching which processes open the files keystores keep secrets in,
+    /// and reporting the ones that aren't the keystore's own app.
+    ///
+    /// Only available on Linux.
+    pub(crate) file_access: FileAccessConfig,
+
+    /// Passing SSH agent requests through a proxy that sees every
+    /// signature, and keys being added or removed.
+    ///
+    /// Only available on Linux.
+    pub(crate) ssh_agent: SshAgentConfig,
+
+    /// Passing gpg-agent requests through a proxy that sees every private
+    /// key and passphrase that's used, and keys being imported or deleted.
+    ///
+    /// Only available on Linux.
+    pub(crate) gpg_agent: GpgAgentConfig,
+
+    /// Watching files that hold credentials outside of any keystore, like
+    /// `~/.netrc` or `~/.aws/credentials`, for entries being added, changed or removed.
+    pub(crate) credential_files: CredentialFilesConfig,
+
+    /// Watching the certificates the system trusts, for root certificates
+    /// being added, removed or trusted differently.
+    pub(crate) trust_store: TrustStoreConfig,
 }
 
 impl Config {
     const FILE_NAME: &'static str = "config.toml";
 
     pub(crate) fn read_from_dir(data_dir: &Path) -> Self {
+        Self::read_from_file(&data_dir.join(Self::FILE_NAME))
+    }
+
+    pub(crate) fn read_from_file(path: &Path) -> Self {
         let fallback = Config::default();
-        match std::fs::read_to_string(data_dir.join(Self::FILE_NAME)) {
-            Ok(val) => {
-                if let Ok(config) = toml::from_str(&val) {
-                    config
-                } else {
-                    log::warn!("incorrect config found, ignoring");
+        match std::fs::read_to_string(path) {
+            Ok(val) => match toml::from_str(&val) {
+                Ok(config) => config,
+                Err(e) => {
+                    log::warn!("incorrect config found, ignoring: {e}");
                     fallback
                 }
-            }
+            },
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
                 log::debug!("config not found, ignoring");
                 fallback
@@ -0,0 +1,1189 @@
+use std::{
+    collections::VecDeque,
+    env,
+    io::{self, Read, Write},
+    os::unix::{io::AsRawFd, net::UnixStream},
+    time::Duration,
+};
+
+#[cfg(test)]
+pub mod testing;
+
+pub const BUS_NAME: &str = "org.freedesktop.DBus";
+pub const BUS_PATH: &str = "/org/freedesktop/DBus";
+pub const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const MONITORING_INTERFACE: &str = "org.freedesktop.DBus.Monitoring";
+
+/// Messages over this size are refused by every bus, so anything bigger means the stream is garbage.
+const MAX_MESSAGE_SIZE: usize = 128 * 1024 * 1024;
+
+/// A single D-Bus value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Byte(u8),
+    Bool(bool),
+    Int16(i16),
+    Uint16(u16),
+    Int32(i32),
+    Uint32(u32),
+    Int64(i64),
+    Uint64(u64),
+    Double(f64),
+    Str(String),
+    ObjectPath(String),
+    Signature(String),
+    /// The signature of a single element, which is needed even when there aren't any, and
+    /// the elements themselves.
+    Array(String, Vec<Value>),
+    Struct(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+    Variant(Box<Value>),
+    /// An index into the file descriptors sent along with the message.
+    UnixFd(u32),
+}
+
+impl Value {
+    pub fn signature(&self) -> String {
+        match self {
+            Value::Byte(_) => String::from("y"),
+            Value::Bool(_) => String::from("b"),
+            Value::Int16(_) => String::from("n"),
+            Value::Uint16(_) => String::from("q"),
+            Value::Int32(_) => String::from("i"),
+            Value::Uint32(_) => String::from("u"),
+            Value::Int64(_) => String::from("x"),
+            Value::Uint64(_) => String::from("t"),
+            Value::Double(_) => String::from("d"),
+            Value::Str(_) => String::from("s"),
+            Value::ObjectPath(_) => String::from("o"),
+            Value::Signature(_) => String::from("g"),
+            Value::Array(element, _) => format!("a{element}"),
+            Value::Struct(fields) => {
+                let fields: String = fields.iter().map(Value::signature).collect();
+                format!("({fields})")
+            }
+            Value::DictEntry(key, value) => format!("{{{}{}}}", key.signature(), value.signature()),
+            Value::Variant(_) => String::from("v"),
+            Value::UnixFd(_) => String::from("h"),
+        }
+    }
+
+    /// A dictionary, like `a{sv}`.
+    pub fn dict(key_signature: &str, value_signature: &str, entries: Vec<(Value, Value)>) -> Self {
+        Value::Array(
+            format!("{{{key_signature}{value_signature}}}"),
+            entries
+                .into_iter()
+                .map(|(key, value)| Value::DictEntry(Box::new(key), Box::new(value)))
+                .collect(),
+        )
+    }
+
+    pub fn variant(value: Value) -> Self {
+        Value::Variant(Box::new(value))
+    }
+
+    /// Looks through any variants wrapped around the value.
+    pub fn unwrap_variant(&self) -> &Value {
+        match self {
+            Value::Variant(inner) => inner.unwrap_variant(),
+            other => other,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.unwrap_variant() {
+            Value::Str(s) | Value::ObjectPath(s) | Value::Signature(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self.unwrap_variant() {
+            Value::Byte(n) => Some(u64::from(n)),
+            Value::Uint16(n) => Some(u64::from(n)),
+            Value::Uint32(n) => Some(u64::from(n)),
+            Value::Uint64(n) => Some(n),
+            Value::Int16(n) => u64::try_from(n).ok(),
+            Value::Int32(n) => u64::try_from(n).ok(),
+            Value::Int64(n) => u64::try_from(n).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self.unwrap_variant() {
+            Value::Array(_, elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// The key and value pairs of a dictionary.
+    pub fn dict_entries(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.as_array()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|entry| match entry {
+                Value::DictEntry(key, value) => Some((&**key, &**value)),
+                _ => None,
+            })
+    }
+
+    /// Finds the value for a string key in a dictionary.
+    pub fn dict_get(&self, key: &str) -> Option<&Value> {
+        self.dict_entries()
+            .find(|(k, _)| k.as_str() == Some(key))
+            .map(|(_, value)| value)
+    }
+}
+
+/// A parsed signature, which is what's needed to read values back.
+#[derive(Clone, Debug, PartialEq)]
+enum Type {
+    Basic(u8),
+    Array(Box<Type>),
+    Struct(Vec<Type>),
+    DictEntry(Box<Type>, Box<Type>),
+    Variant,
+}
+
+impl Type {
+    fn alignment(&self) -> usize {
+        match self {
+            Type::Basic(b'y' | b'g') | Type::Variant => 1,
+            Type::Basic(b'n' | b'q') => 2,
+            Type::Basic(b'x' | b't' | b'd') | Type::Struct(_) | Type::DictEntry(..) => 8,
+            Type::Basic(_) | Type::Array(_) => 4,
+        }
+    }
+
+    fn signature(&self) -> String {
+        match self {
+            Type::Basic(code) => char::from(*code).to_string(),
+            Type::Array(element) => format!("a{}", element.signature()),
+            Type::Struct(fields) => {
+                let fields: String = fields.iter().map(Type::signature).collect();
+                format!("({fields})")
+            }
+            Type::DictEntry(key, value) => format!("{{{}{}}}", key.signature(), value.signature()),
+            Type::Variant => String::from("v"),
+        }
+    }
+}
+
+fn invalid(what: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.into())
+}
+
+fn parse_signature(signature: &str) -> io::Result<Vec<Type>> {
+    /// How deeply arrays and structs can each be nested, which every bus enforces too.
+    const MAX_NESTING: usize = 32;
+
+    fn parse_one(sig: &[u8], pos: &mut usize, arrays: usize, structs: usize) -> io::Result<Type> {
+        if arrays > MAX_NESTING || structs > MAX_NESTING {
+            return Err(invalid("signature is nested too deeply"));
+        }
+
+        let code = *sig
+            .get(*pos)
+            .ok_or_else(|| invalid("signature ended early"))?;
+        *pos += 1;
+
+        match code {
+            b'y' | b'b' | b'n' | b'q' | b'i' | b'u' | b'x' | b't' | b'd' | b's' | b'o' | b'g'
+            | b'h' => Ok(Type::Basic(code)),
+            b'v' => Ok(Type::Variant),
+            b'a' => Ok(Type::Array(Box::new(parse_one(
+                sig,
+                pos,
+                arrays + 1,
+                structs,
+            )?))),
+            b'(' => {
+                let mut fields = Vec::new();
+                while sig.get(*pos) != Some(&b')') {
+                    fields.push(parse_one(sig, pos, arrays, structs + 1)?);
+                }
+                *pos += 1;
+
+                // An empty struct takes up no space, so an array of them would never end.
+                if fields.is_empty() {
+                    return Err(invalid("structs need at least one field"));
+                }
+                Ok(Type::Struct(fields))
+            }
+            b'{' => {
+                let key = parse_one(sig, pos, arrays, structs + 1)?;
+                let value = parse_one(sig, pos, arrays, structs + 1)?;
+                if sig.get(*pos) != Some(&b'}') {
+                    return Err(invalid("dictionary entries need exactly two types"));
+                }
+                *pos += 1;
+                Ok(Type::DictEntry(Box::new(key), Box::new(value)))
+            }
+            other => Err(invalid(format!(
+                "unknown type {:?} in signature",
+                char::from(other)
+            ))),
+        }
+    }
+
+    let sig = signature.as_bytes();
+    let mut pos = 0;
+    let mut types = Vec::new();
+
+    while pos < sig.len() {
+        types.push(parse_one(sig, &mut pos, 0, 0)?);
+    }
+
+    Ok(types)
+}
+
+fn alignment_of(signature: &str) -> usize {
+    parse_signature(signature)
+        .ok()
+        .and_then(|types| types.first().map(Type::alignment))
+        .unwrap_or(1)
+}
+
+/// Marshals values, always in little endian.
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn pad(&mut self, alignment: usize) {
+        while !self.buf.len().is_multiple_of(alignment) {
+            self.buf.push(0);
+        }
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.pad(4);
+        self.buf.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn string(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn signature(&mut self, s: &str) {
+        self.buf.push(s.len() as u8);
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Byte(n) => self.buf.push(*n),
+            Value::Bool(b) => self.u32(u32::from(*b)),
+            Value::Int16(n) => {
+                self.pad(2);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Uint16(n) => {
+                self.pad(2);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Int32(n) => {
+                self.pad(4);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Uint32(n) | Value::UnixFd(n) => self.u32(*n),
+            Value::Int64(n) => {
+                self.pad(8);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Uint64(n) => {
+                self.pad(8);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Double(n) => {
+                self.pad(8);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Str(s) | Value::ObjectPath(s) => self.string(s),
+            Value::Signature(s) => self.signature(s),
+            Value::Array(element, elements) => {
+                self.u32(0);
+                let length_at = self.buf.len() - 4;
+
+                // The padding to the first element doesn't count towards the length.
+                self.pad(alignment_of(element));
+                let start = self.buf.len();
+
+                for element in elements {
+                    self.value(element);
+                }
+
+                let length = (self.buf.len() - start) as u32;
+                self.buf[length_at..length_at + 4].copy_from_slice(&length.to_le_bytes());
+            }
+            Value::Struct(fields) => {
+                self.pad(8);
+                for field in fields {
+                    self.value(field);
+                }
+            }
+            Value::DictEntry(key, value) => {
+                self.pad(8);
+                self.value(key);
+                self.value(value);
+            }
+            Value::Variant(inner) => {
+                self.signature(&inner.signature());
+                self.value(inner);
+            }
+        }
+    }
+}
+
+/// Unmarshals values out of a whole message, since alignment is relative to its start.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+    /// How many containers the value being read is inside of, counting variants.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn align(&mut self, alignment: usize) -> io::Result<()> {
+        let padded = self.pos.div_ceil(alignment) * alignment;
+        if padded > self.buf.len() {
+            return Err(invalid("message ended early"));
+        }
+        self.pos = padded;
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("message ended early"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn fixed<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        self.align(N)?;
+        let mut bytes: [u8; N] = self.take(N)?.try_into().expect("took exactly N bytes");
+        if self.big_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.fixed::<4>().map(u32::from_le_bytes)
+    }
+
+    fn string(&mut self, len: usize) -> io::Result<String> {
+        let bytes = self.take(len)?;
+        self.take(1)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("string wasn't UTF-8"))
+    }
+
+    fn signature(&mut self) -> io::Result<String> {
+        let len = usize::from(self.take(1)?[0]);
+        self.string(len)
+    }
+
+    fn value(&mut self, ty: &Type) -> io::Result<Value> {
+        /// Signatures are limited on their own, but variants can still nest them any deeper.
+        const MAX_DEPTH: usize = 64;
+
+        if matches!(ty, Type::Basic(_)) {
+            return self.contents(ty);
+        }
+
+        if self.depth == MAX_DEPTH {
+            return Err(invalid("values are nested too deeply"));
+        }
+
+        self.depth += 1;
+        let value = self.contents(ty);
+        self.depth -= 1;
+        value
+    }
+
+    fn contents(&mut self, ty: &Type) -> io::Result<Value> {
+        Ok(match ty {
+            Type::Basic(b'y') => Value::Byte(self.take(1)?[0]),
+            Type::Basic(b'b') => Value::Bool(self.u32()? != 0),
+            Type::Basic(b'n') => Value::Int16(i16::from_le_bytes(self.fixed()?)),
+            Type::Basic(b'q') => Value::Uint16(u16::from_le_bytes(self.fixed()?)),
+            Type::Basic(b'i') => Value::Int32(i32::from_le_bytes(self.fixed()?)),
+            Type::Basic(b'u') => Value::Uint32(self.u32()?),
+            Type::Basic(b'h') => Value::UnixFd(self.u32()?),
+            Type::Basic(b'x') => Value::Int64(i64::from_le_bytes(self.fixed()?)),
+            Type::Basic(b't') => Value::Uint64(u64::from_le_bytes(self.fixed()?)),
+            Type::Basic(b'd') => Value::Double(f64::from_le_bytes(self.fixed()?)),
+            Type::Basic(b's') => {
+                let len = self.u32()? as usize;
+                Value::Str(self.string(len)?)
+            }
+            Type::Basic(b'o') => {
+                let len = self.u32()? as usize;
+                Value::ObjectPath(self.string(len)?)
+            }
+            Type::Basic(b'g') => Value::Signature(self.signature()?),
+            Type::Basic(other) => {
+                return Err(invalid(format!("unknown type {:?}", char::from(*other))))
+            }
+            Type::Array(element) => {
+                let len = self.u32()? as usize;
+                self.align(element.alignment())?;
+
+                let end = self.pos + len;
+                if end > self.buf.len() {
+                    return Err(invalid("array ran past the end of the message"));
+                }
+
+                let mut elements = Vec::new();
+                while self.pos < end {
+                    let start = self.pos;
+                    elements.push(self.value(element)?);
+
+                    if self.pos == start {
+                        return Err(invalid("array elements must take up space"));
+                    }
+                }
+
+                Value::Array(element.signature(), elements)
+            }
+            Type::Struct(fields) => {
+                self.align(8)?;
+                Value::Struct(
+                    fields
+                        .iter()
+                        .map(|field| self.value(field))
+                        .collect::<io::Result<_>>()?,
+                )
+            }
+            Type::DictEntry(key, value) => {
+                self.align(8)?;
+                let key = self.value(key)?;
+                let value = self.value(value)?;
+                Value::DictEntry(Box::new(key), Box::new(value))
+            }
+            Type::Variant => {
+                let signature = self.signature()?;
+                let types = parse_signature(&signature)?;
+                match types.as_slice() {
+                    [ty] => Value::variant(self.value(ty)?),
+                    _ => return Err(invalid("variants must hold exactly one value")),
+                }
+            }
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    MethodCall = 1,
+    MethodReturn = 2,
+    Error = 3,
+    Signal = 4,
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub kind: MessageType,
+    pub flags: u8,
+    pub serial: u32,
+    pub path: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub error_name: Option<String>,
+    pub reply_serial: Option<u32>,
+    pub destination: Option<String>,
+    pub sender: Option<String>,
+    pub body: Vec<Value>,
+}
+
+impl Message {
+    fn new(kind: MessageType) -> Self {
+        Self {
+            kind,
+            flags: 0,
+            serial: 0,
+            path: None,
+            interface: None,
+            member: None,
+            error_name: None,
+            reply_serial: None,
+            destination: None,
+            sender: None,
+            body: Vec::new(),
+        }
+    }
+
+    pub fn method_call(
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        body: Vec<Value>,
+    ) -> Self {
+        Self {
+            destination: Some(destination.to_owned()),
+            path: Some(path.to_owned()),
+            interface: Some(interface.to_owned()),
+            member: Some(member.to_owned()),
+            body,
+            ..Self::new(MessageType::MethodCall)
+        }
+    }
+
+    pub fn error(call: &Message, name: &str, text: &str) -> Self {
+        Self {
+            destination: call.sender.clone(),
+            // Replies look like they came from whoever the call was for.
+            sender: call.destination.clone(),
+            reply_serial: Some(call.serial),
+            error_name: Some(name.to_owned()),
+            body: vec![Value::Str(text.to_owned())],
+            ..Self::new(MessageType::Error)
+        }
+    }
+
+    /// An error in reply to a message that couldn't be parsed, which only the serial of is known.
+    pub fn error_to_raw(raw: &[u8], name: &str, text: &str) -> Option<Self> {
+        let serial: [u8; 4] = raw.get(8..12)?.try_into().ok()?;
+        let serial = match is_big_endian(raw).ok()? {
+            true => u32::from_be_bytes(serial),
+            false => u32::from_le_bytes(serial),
+        };
+
+        Some(Self {
+            reply_serial: Some(serial),
+            error_name: Some(name.to_owned()),
+            body: vec![Value::Str(text.to_owned())],
+            ..Self::new(MessageType::Error)
+        })
+    }
+
+    pub fn is_signal(&self, interface: &str, member: &str) -> bool {
+        self.kind == MessageType::Signal
+            && self.interface.as_deref() == Some(interface)
+            && self.member.as_deref() == Some(member)
+    }
+
+    fn body_signature(&self) -> String {
+        self.body.iter().map(Value::signature).collect()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Writer::default();
+        for value in &self.body {
+            body.value(value);
+        }
+
+        let mut fields = Vec::new();
+        let mut field = |code: u8, value: Value| {
+            fields.push(Value::Struct(vec![
+                Value::Byte(code),
+                Value::variant(value),
+            ]));
+        };
+
+        if let Some(path) = &self.path {
+            field(1, Value::ObjectPath(path.clone()));
+        }
+        if let Some(interface) = &self.interface {
+            field(2, Value::Str(interface.clone()));
+        }
+        if let Some(member) = &self.member {
+            field(3, Value::Str(member.clone()));
+        }
+        if let Some(error_name) = &self.error_name {
+            field(4, Value::Str(error_name.clone()));
+        }
+        if let Some(reply_serial) = self.reply_serial {
+            field(5, Value::Uint32(reply_serial));
+        }
+        if let Some(destination) = &self.destination {
+            field(6, Value::Str(destination.clone()));
+        }
+        if let Some(sender) = &self.sender {
+            field(7, Value::Str(sender.clone()));
+        }
+        if !self.body.is_empty() {
+            field(8, Value::Signature(self.body_signature()));
+        }
+
+        let mut header = Writer::default();
+        header
+            .buf
+            .extend_from_slice(&[b'l', self.kind as u8, self.flags, 1]);
+        header.u32(body.buf.len() as u32);
+        header.u32(self.serial);
+        header.value(&Value::Array(String::from("(yv)"), fields));
+        header.pad(8);
+
+        header.buf.extend_from_slice(&body.buf);
+        header.buf
+    }
+
+    /// Reads one whole message off a stream.
+    pub fn read_from(stream: &mut impl Read) -> io::Result<Self> {
+        Self::from_bytes(&Self::read_raw(stream)?)
+    }
+
+    /// Reads the bytes of one whole message off a stream, without looking inside it.
+    pub fn read_raw(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+        let mut fixed = [0u8; 16];
+        stream.read_exact(&mut fixed)?;
+
+        let big_endian = is_big_endian(&fixed)?;
+
+        let read_u32 = |bytes: &[u8]| {
+            let bytes: [u8; 4] = bytes.try_into().expect("always 4 bytes");
+            if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            }
+        };
+
+        let body_len = read_u32(&fixed[4..8]) as usize;
+        let fields_len = read_u32(&fixed[12..16]) as usize;
+        let header_len = (16 + fields_len).div_ceil(8) * 8;
+
+        if header_len + body_len > MAX_MESSAGE_SIZE {
+            return Err(invalid("message is too big"));
+        }
+
+        let mut buf = vec![0u8; header_len + body_len];
+        buf[..16].copy_from_slice(&fixed);
+        stream.read_exact(&mut buf[16..])?;
+
+        Ok(buf)
+    }
+
+    /// Parses a whole message, like one from `read_raw`.
+    pub fn from_bytes(buf: &[u8]) -> io::Result<Self> {
+        if buf.len() < 16 {
+            return Err(invalid("message is too short"));
+        }
+
+        Self::parse(buf, is_big_endian(buf)?)
+    }
+
+    fn parse(buf: &[u8], big_endian: bool) -> io::Result<Self> {
+        let kind = match buf[1] {
+            1 => MessageType::MethodCall,
+            2 => MessageType::MethodReturn,
+            3 => MessageType::Error,
+            4 => MessageType::Signal,
+            other => return Err(invalid(format!("unknown message type {other}"))),
+        };
+
+        let mut reader = Reader {
+            buf,
+            pos: 8,
+            big_endian,
+            depth: 0,
+        };
+
+        let mut message = Self::new(kind);
+        message.flags = buf[2];
+        message.serial = reader.u32()?;
+
+        let fields = reader.value(&Type::Array(Box::new(Type::Struct(vec![
+            Type::Basic(b'y'),
+            Type::Variant,
+        ]))))?;
+
+        let mut signature = String::new();
+
+        for field in fields.as_array().unwrap_or_default() {
+            let (code, value) = match field {
+                Value::Struct(parts) => match parts.as_slice() {
+                    [Value::Byte(code), value] => (*code, value.unwrap_variant()),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            let text = value.as_str().map(str::to_owned);
+
+            match code {
+                1 => message.path = text,
+                2 => message.interface = text,
+                3 => message.member = text,
+                4 => message.error_name = text,
+                5 => message.reply_serial = value.as_u64().map(|serial| serial as u32),
+                6 => message.destination = text,
+                7 => message.sender = text,
+                8 => signature = text.unwrap_or_default(),
+                // Unknown fields have to be ignored.
+                _ => {}
+            }
+        }
+
+        reader.align(8)?;
+
+        for ty in parse_signature(&signature)? {
+            message.body.push(reader.value(&ty)?);
+        }
+
+        Ok(message)
+    }
+}
+
+fn is_big_endian(header: &[u8]) -> io::Result<bool> {
+    match header[0] {
+        b'l' => Ok(false),
+        b'B' => Ok(true),
+        _ => Err(invalid("unknown byte order")),
+    }
+}
+
+/// A connection to a message bus, with just enough of a client to talk to desktop services
+/// like the Secret Service.
+///
+/// Only Unix sockets and the `EXTERNAL` authentication mechanism are supported, which is
+/// everything a session bus on Linux needs.
+pub struct Connection {
+    stream: UnixStream,
+    next_serial: u32,
+    /// Messages that arrived while waiting for a reply.
+    pending: VecDeque<Message>,
+}
+
+impl Connection {
+    /// Connects to the session bus of the current login.
+    pub fn session() -> io::Result<Self> {
+        Self::open(&session_address()?)
+    }
+
+    /// Connects to the first address that works, out of a semicolon separated list.
+    pub fn open(addresses: &str) -> io::Result<Self> {
+        Self::handshake(connect(addresses)?)
+    }
+
+    /// Authenticates an already connected stream and says hello to the bus.
+    pub fn handshake(mut stream: UnixStream) -> io::Result<Self> {
+        let uid = unsafe { libc::getuid() }.to_string();
+        let hex_uid: String = uid.bytes().map(|b| format!("{b:02x}")).collect();
+
+        stream.write_all(b"\0")?;
+        stream.write_all(format!("AUTH EXTERNAL {hex_uid}\r\n").as_bytes())?;
+
+        let reply = read_line(&mut stream)?;
+        if !reply.starts_with("OK ") {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("bus refused authentication: {reply}"),
+            ));
+        }
+
+        stream.write_all(b"BEGIN\r\n")?;
+
+        let mut connection = Self {
+            stream,
+            next_serial: 1,
+            pending: VecDeque::new(),
+        };
+
+        // The bus ignores everything else until it's been greeted.
+        connection.call(BUS_NAME, BUS_PATH, BUS_NAME, "Hello", Vec::new())?;
+
+        Ok(connection)
+    }
+
+    /// Sends a message, returning the serial it was given.
+    pub fn send(&mut self, mut message: Message) -> io::Result<u32> {
+        message.serial = self.next_serial;
+        self.next_serial = self.next_serial.wrapping_add(1).max(1);
+
+        self.stream.write_all(&message.to_bytes())?;
+        Ok(message.serial)
+    }
+
+    /// Calls a method and waits for its reply, keeping anything else that arrives meanwhile
+    /// for `receive`.
+    pub fn call(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        body: Vec<Value>,
+    ) -> io::Result<Vec<Value>> {
+        let serial = self.send(Message::method_call(
+            destination,
+            path,
+            interface,
+            member,
+            body,
+        ))?;
+
+        loop {
+            let message = Message::read_from(&mut self.stream)?;
+
+            if message.reply_serial != Some(serial) {
+                self.pending.push_back(message);
+                continue;
+            }
+
+            return match message.kind {
+                MessageType::Error => {
+                    let name = message.error_name.unwrap_or_default();
+                    let text = message
+                        .body
+                        .first()
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_owned();
+
+                    Err(io::Error::other(format!("{name}: {text}")))
+                }
+                _ => Ok(message.body),
+            };
+        }
+    }
+
+    /// Gets every property of an interface on an object.
+    pub fn get_all_properties(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+    ) -> io::Result<Value> {
+        let mut reply = self.call(
+            destination,
+            path,
+            PROPERTIES_INTERFACE,
+            "GetAll",
+            vec![Value::Str(interface.to_owned())],
+        )?;
+
+        if reply.is_empty() {
+            return Err(invalid("GetAll didn't return anything"));
+        }
+
+        Ok(reply.swap_remove(0))
+    }
+
+    /// Gets a single property of an interface on an object.
+    pub fn get_property(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        name: &str,
+    ) -> io::Result<Value> {
+        let mut reply = self.call(
+            destination,
+            path,
+            PROPERTIES_INTERFACE,
+            "Get",
+            vec![
+                Value::Str(interface.to_owned()),
+                Value::Str(name.to_owned()),
+            ],
+        )?;
+
+        if reply.is_empty() {
+            return Err(invalid("Get didn't return anything"));
+        }
+
+        Ok(reply.swap_remove(0))
+    }
+
+    /// Asks the bus to send along messages matching `rule`.
+    pub fn add_match(&mut self, rule: &str) -> io::Result<()> {
+        self.call(
+            BUS_NAME,
+            BUS_PATH,
+            BUS_NAME,
+            "AddMatch",
+            vec![Value::Str(rule.to_owned())],
+        )
+        .map(drop)
+    }
+
+    /// Turns this connection into a monitor that gets a copy of every message matching one of
+    /// `rules`, no matter who it's for. A monitor can't send anything anymore, only receive.
+    pub fn become_monitor(&mut self, rules: &[String]) -> io::Result<()> {
+        self.call(
+            BUS_NAME,
+            BUS_PATH,
+            MONITORING_INTERFACE,
+            "BecomeMonitor",
+            vec![
+                Value::Array(
+                    String::from("s"),
+                    rules.iter().cloned().map(Value::Str).collect(),
+                ),
+                Value::Uint32(0),
+            ],
+        )
+        .map(drop)
+    }
+
+    /// Asks the bus which process is behind a connection, like `:1.42`.
+    pub fn connection_pid(&mut self, name: &str) -> io::Result<u32> {
+        let reply = self.call(
+            BUS_NAME,
+            BUS_PATH,
+            BUS_NAME,
+            "GetConnectionUnixProcessID",
+            vec![Value::Str(name.to_owned())],
+        )?;
+
+        reply
+            .first()
+            .and_then(Value::as_u64)
+            .and_then(|pid| u32::try_from(pid).ok())
+            .ok_or_else(|| invalid("GetConnectionUnixProcessID didn't return a pid"))
+    }
+
+    /// Blocks until the next message that isn't a reply to one of this connection's calls.
+    pub fn receive(&mut self) -> io::Result<Message> {
+        match self.pending.pop_front() {
+            Some(message) => Ok(message),
+            None => Message::read_from(&mut self.stream),
+        }
+    }
+
+    /// Like `receive`, but gives up once nothing has arrived for `timeout`.
+    pub fn receive_timeout(&mut self, timeout: Duration) -> io::Result<Option<Message>> {
+        if let Some(message) = self.pending.pop_front() {
+            return Ok(Some(message));
+        }
+
+        // Waiting for the socket instead of timing out reads means a message is never cut off
+        // halfway through.
+        let mut socket = libc::pollfd {
+            fd: self.stream.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+
+        match unsafe { libc::poll(&mut socket, 1, timeout) } {
+            0 => Ok(None),
+            n if n < 0 => {
+                let error = io::Error::last_os_error();
+                match error.kind() {
+                    io::ErrorKind::Interrupted => Ok(None),
+                    _ => Err(error),
+                }
+            }
+            _ => Message::read_from(&mut self.stream).map(Some),
+        }
+    }
+}
+
+/// The address of the session bus of the current login.
+pub fn session_address() -> io::Result<String> {
+    match env::var("DBUS_SESSION_BUS_ADDRESS") {
+        Ok(address) if !address.is_empty() => Ok(address),
+        _ => match env::var("XDG_RUNTIME_DIR") {
+            Ok(dir) => Ok(format!("unix:path={dir}/bus")),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no session bus address is set",
+            )),
+        },
+    }
+}
+
+/// Opens a socket to the first address that works, out of a semicolon separated list, without
+/// authenticating.
+pub fn connect(addresses: &str) -> io::Result<UnixStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no usable bus address");
+
+    for address in addresses.split(';').filter(|a| !a.is_empty()) {
+        match connect_one(address) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
+
+fn connect_one(address: &str) -> io::Result<UnixStream> {
+    let (transport, params) = address
+        .split_once(':')
+        .ok_or_else(|| invalid(format!("bad bus address {address}")))?;
+
+    if transport != "unix" {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported bus transport {transport}"),
+        ));
+    }
+
+    for param in params.split(',') {
+        match param.split_once('=') {
+            Some(("path", path)) => return UnixStream::connect(unescape(path)?),
+            Some(("abstract", name)) => {
+                use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+                let addr = SocketAddr::from_abstract_name(unescape(name)?.as_bytes())?;
+                return UnixStream::connect_addr(&addr);
+            }
+            _ => {}
+        }
+    }
+
+    Err(invalid(format!("bus address {address} has no socket")))
+}
+
+/// Undoes the `%xx` escaping used in bus addresses.
+fn unescape(value: &str) -> io::Result<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut chars = value.bytes();
+
+    while let Some(b) = chars.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = chars.by_ref().take(2).collect();
+            let hex = std::str::from_utf8(&hex).map_err(|_| invalid("bad escape"))?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid("bad escape"))?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|_| invalid("bus address wasn't UTF-8"))
+}
+
+/// Reads one line of the authentication conversation, without the line ending.
+pub fn read_line(stream: &mut impl Read) -> io::Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+
+    while !line.ends_with(b"\r\n") {
+        stream.read_exact(&mut byte)?;
+        line.push(byte[0]);
+
+        if line.len() > 4096 {
+            return Err(invalid("authentication line is too long"));
+        }
+    }
+
+    line.truncate(line.len() - 2);
+    String::from_utf8(line).map_err(|_| invalid("authentication line wasn't UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(body: Vec<Value>) -> Message {
+        let mut message = Message::method_call(
+            "org.freedesktop.secrets",
+            "/org/freedesktop/secrets",
+            "org.freedesktop.Secret.Service",
+            "SearchItems",
+            body,
+        );
+        message.serial = 7;
+        message
+    }
+
+    fn round_trip(message: &Message) -> io::Result<Message> {
+        Message::from_bytes(&message.to_bytes())
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        let body = vec![
+            Value::Str(String::from("org.freedesktop.Secret.Item")),
+            Value::dict(
+                "s",
+                "v",
+                vec![
+                    (
+                        Value::Str(String::from("Label")),
+                        Value::variant(Value::Str(String::from("github.com"))),
+                    ),
+                    (
+                        Value::Str(String::from("Modified")),
+                        Value::variant(Value::Uint64(1_700_000_000)),
+                    ),
+                ],
+            ),
+            Value::Struct(vec![
+                Value::ObjectPath(String::from("/org/freedesktop/secrets/session/1")),
+                Value::Array(String::from("y"), vec![Value::Byte(1), Value::Byte(2)]),
+                Value::Bool(true),
+                Value::Int16(-2),
+                Value::Double(1.5),
+            ]),
+            Value::Array(String::from("(ot)"), Vec::new()),
+        ];
+
+        let message = call(body.clone());
+        let parsed = round_trip(&message).unwrap();
+
+        assert_eq!(parsed.kind, MessageType::MethodCall);
+        assert_eq!(parsed.serial, 7);
+        assert_eq!(parsed.destination, message.destination);
+        assert_eq!(parsed.path, message.path);
+        assert_eq!(parsed.interface, message.interface);
+        assert_eq!(parsed.member, message.member);
+        assert_eq!(parsed.body, body);
+    }
+
+    #[test]
+    fn refuses_empty_structs() {
+        assert!(parse_signature("()").is_err());
+        assert!(parse_signature("a()").is_err());
+
+        // An array of them used to be read forever.
+        let message = call(vec![Value::Array(String::from("()"), Vec::new())]);
+        assert!(round_trip(&message).is_err());
+    }
+
+    #[test]
+    fn refuses_deeply_nested_signatures() {
+        let arrays = |depth| format!("{}y", "a".repeat(depth));
+        assert!(parse_signature(&arrays(32)).is_ok());
+        assert!(parse_signature(&arrays(33)).is_err());
+
+        let structs = |depth| format!("{}y{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(parse_signature(&structs(32)).is_ok());
+        assert!(parse_signature(&structs(33)).is_err());
+
+        let entries = |depth| format!("{}y{}", "a{y".repeat(depth), "}".repeat(depth));
+        assert!(parse_signature(&entries(32)).is_ok());
+        assert!(parse_signature(&entries(33)).is_err());
+    }
+
+    #[test]
+    fn refuses_deeply_nested_variants() {
+        let nested = |depth| (0..depth).fold(Value::Byte(1), |inner, _| Value::variant(inner));
+
+        assert!(round_trip(&call(vec![nested(64)])).is_ok());
+        assert!(round_trip(&call(vec![nested(65)])).is_err());
+    }
+
+    #[test]
+    fn refuses_messages_that_end_early() {
+        let bytes = call(vec![Value::Array(
+            String::from("u"),
+            vec![Value::Uint32(1)],
+        )])
+        .to_bytes();
+        assert!(Message::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Message::from_bytes(&bytes[..12]).is_err());
+
+        // The array's length is the first thing in the body.
+        let body_len = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let body = bytes.len() - body_len;
+        let mut overlong = bytes.clone();
+        overlong[body..body + 4].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+        assert!(Message::from_bytes(&overlong).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+//! A private bus for tests to run fake services on, so nothing touches the real session bus.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::{Connection, Message, MessageType, Value, BUS_NAME, BUS_PATH};
+
+/// A `dbus-daemon` of its own, which is stopped and cleaned up when dropped.
+pub struct PrivateBus {
+    daemon: Child,
+    dir: PathBuf,
+    address: String,
+}
+
+impl PrivateBus {
+    /// Starts a bus, or returns `None` when `dbus-daemon` isn't installed so the test can be
+    /// skipped.
+    pub fn start() -> Option<Self> {
+        static STARTED: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "keeper-bus-{}-{}",
+            std::process::id(),
+            STARTED.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).expect("failed to make a directory for the bus");
+
+        let config = dir.join("bus.conf");
+        fs::write(
+            &config,
+            format!(
+                r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:path={}</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#,
+                dir.join("bus").display()
+            ),
+        )
+        .expect("failed to write the bus config");
+
+        let daemon = Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", config.display()))
+            .args(["--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let mut daemon = match daemon {
+            Ok(daemon) => daemon,
+            Err(e) => {
+                eprintln!("skipping, dbus-daemon can't be started: {e}");
+                let _ = fs::remove_dir_all(&dir);
+                return None;
+            }
+        };
+
+        // The address is only printed once the bus is listening.
+        let mut address = String::new();
+        let stdout = daemon.stdout.take().expect("stdout is piped");
+        BufReader::new(stdout)
+            .read_line(&mut address)
+            .expect("dbus-daemon didn't print its address");
+
+        Some(Self {
+            daemon,
+            dir,
+            address: address.trim().to_owned(),
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Somewhere to put other sockets, which is cleaned up along with the bus.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn connect(&self) -> Connection {
+        Connection::open(&self.address).expect("failed to connect to the private bus")
+    }
+
+    /// Connects and takes a well known name, like a service would.
+    pub fn serve(&self, name: &str) -> Connection {
+        let mut connection = self.connect();
+        connection
+            .call(
+                BUS_NAME,
+                BUS_PATH,
+                BUS_NAME,
+                "RequestName",
+                vec![Value::Str(name.to_owned()), Value::Uint32(0)],
+            )
+            .expect("failed to take a name on the private bus");
+        connection
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A successful reply to a call.
+pub fn reply(call: &Message, body: Vec<Value>) -> Message {
+    Message {
+        destination: call.sender.clone(),
+        reply_serial: Some(call.serial),
+        body,
+        ..Message::new(MessageType::MethodReturn)
+    }
+}
+
+pub fn signal(path: &str, interface: &str, member: &str, body: Vec<Value>) -> Message {
+    Message {
+        path: Some(path.to_owned()),
+        interface: Some(interface.to_owned()),
+        member: Some(member.to_owned()),
+        body,
+        ..Message::new(MessageType::Signal)
+    }
+}
+
+/// Waits for a method call, skipping the signals the bus sends, like `NameAcquired`.
+pub fn next_call(connection: &mut Connection) -> io::Result<Message> {
+    loop {
+        let message = connection.receive()?;
+        if message.kind == MessageType::MethodCall {
+            return Ok(message);
+        }
+    }
+}
@@ -1,52 +1,250 @@
-use core::ffi::c_void;
-use core_foundation::{
-    array::CFArray,
-    base::{OSStatus, TCFType},
-    boolean::CFBoolean,
-    date::CFDate,
-    dictionary::{CFDictionary, CFMutableDictionary},
-    runloop::{self, CFRunLoop},
-    string::CFString,
+use serde::{Deserialize, Serialize};
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use std::{path::Path, sync::mpsc};
+
+#[cfg(target_os = "linux")]
+use crate::access;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use crate::config::Config;
+use crate::{
+    items::{ItemAttributes, ItemDetail},
+    process::{self, ProcessInfo},
 };
-use security_framework_sys::item::{
-    kSecAttrLabel, kSecClass, kSecClassGenericPassword, kSecClassInternetPassword, kSecMatchLimit,
-    kSecReturnAttributes,
-};
-use std::{sync::mpsc, thread, time::Duration};
 
-use crate::bindings::{self, SecKeychainCallbackInfo, SecKeychainEvent, SecKeychainEventMask};
+mod credential_files;
+pub(crate) use credential_files::CredentialFilesConfig;
+mod gpg_agent;
+pub(crate) use gpg_agent::GpgAgentConfig;
+mod snapshot;
+mod ssh_agent;
+pub(crate) use ssh_agent::SshAgentConfig;
+mod trust_store;
+pub(crate) use trust_store::TrustStoreConfig;
+
+#[cfg(target_os = "macos")]
+mod keychain;
+
+#[cfg(target_os = "linux")]
+mod keyring;
+#[cfg(target_os = "linux")]
+mod kwallet;
+#[cfg(target_os = "linux")]
+mod secret_service;
+
+/// Starts the keychain monitor, along with the credential file and trust store watchers if
+/// they're enabled.
+#[cfg(target_os = "macos")]
+pub fn start_desktop_monitors(config: &Config, home: &Path) -> mpsc::Receiver<EventData> {
+    let (tx, event_source) = mpsc::channel::<EventData>();
 
-#[derive(Debug)]
+    if config.credential_files.enabled {
+        credential_files::start_credential_file_watcher(&config.credential_files, home, tx.clone());
+    }
+
+    if config.trust_store.enabled {
+        trust_store::start_trust_store_monitor(&config.trust_store, home, tx.clone());
+    }
+
+    keychain::start_keychain_monitor(tx);
+
+    event_source
+}
+
+/// Starts a monitor for every keystore a Linux desktop might have, which all feed one event
+/// stream. Monitors for keystores that aren't around give up on their own.
+#[cfg(target_os = "linux")]
+pub fn start_desktop_monitors(config: &Config, home: &Path) -> mpsc::Receiver<EventData> {
+    let (tx, event_source) = mpsc::channel::<EventData>();
+
+    if config.access_proxy.enabled {
+        if let Err(e) = access::start_proxy(&config.access_proxy, tx.clone()) {
+            log::error!("failed to start the Secret Service proxy, reads won't be seen: {e}");
+        }
+    }
+
+    if config.file_access.enabled {
+        if let Err(e) = access::start_file_monitor(&config.file_access, home, tx.clone()) {
+            log::error!("failed to start watching keystore files, their reads won't be seen: {e}");
+        }
+    }
+
+    if config.ssh_agent.enabled {
+        if let Err(e) = ssh_agent::start_ssh_agent_proxy(&config.ssh_agent, tx.clone()) {
+            log::error!("failed to start the SSH agent proxy, key use won't be seen: {e}");
+        }
+    }
+
+    if config.gpg_agent.enabled {
+        if let Err(e) = gpg_agent::start_gpg_agent_proxy(&config.gpg_agent, tx.clone()) {
+            log::error!("failed to start the GnuPG agent proxy, key use won't be seen: {e}");
+        }
+    }
+
+    if config.credential_files.enabled {
+        credential_files::start_credential_file_watcher(&config.credential_files, home, tx.clone());
+    }
+
+    if config.trust_store.enabled {
+        trust_store::start_trust_store_monitor(&config.trust_store, home, tx.clone());
+    }
+
+    secret_service::start_secret_service_monitor(tx.clone());
+    kwallet::start_kwallet_monitor(tx.clone());
+    keyring::start_keyring_monitor(tx);
+
+    event_source
+}
+
+/// Which keystore an event came from.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Older captures don't say, and they're all from the keychain.
+    #[default]
+    Keychain,
+    SecretService,
+    #[serde(rename = "kwallet")]
+    KWallet,
+    KernelKeyring,
+    CredentialFile,
+    TrustStore,
+    SshAgent,
+    GpgAgent,
+    /// A keystore's files being read directly instead of through its service.
+    KeystoreFile,
+}
+
+impl Backend {
+    /// How notifications refer to the keystore, like "the keychain".
+    pub fn described(self) -> &'static str {
+        match self {
+            Backend::Keychain => "the keychain",
+            Backend::SecretService => "the Secret Service",
+            Backend::KWallet => "KWallet",
+            Backend::KernelKeyring => "the kernel keyring",
+            Backend::CredentialFile => "a credential file",
+            Backend::TrustStore => "the trust store",
+            Backend::SshAgent => "the SSH agent",
+            Backend::GpgAgent => "the GnuPG agent",
+            Backend::KeystoreFile => "a keystore file",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 enum AddedOrUpdated {
     Added,
     Updated,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct InnerDetails {
+    #[serde(default)]
+    backend: Backend,
     item_name: String,
     modified_at: f64,
-    modified_by: i32,
+    #[serde(deserialize_with = "process::deserialize_changer")]
+    modified_by: ProcessInfo,
+    /// The name of the item's class in `items::CLASSES`. Older captures don't have this.
+    #[serde(default)]
+    item_class: Option<String>,
+    /// Attributes that tell apart items with the same name.
+    #[serde(default)]
+    attributes: Box<ItemAttributes>,
+    /// Class-specific attributes, like a certificate's subject.
+    #[serde(default)]
+    details: Vec<ItemDetail>,
 }
 
-#[derive(Debug)]
+impl InnerDetails {
+    pub fn new(
+        backend: Backend,
+        item_name: String,
+        modified_at: f64,
+        modified_by: ProcessInfo,
+    ) -> Self {
+        Self {
+            backend,
+            item_name,
+            modified_at,
+            modified_by,
+            item_class: None,
+            attributes: Box::default(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn with_class(
+        mut self,
+        item_class: &str,
+        attributes: ItemAttributes,
+        details: Vec<ItemDetail>,
+    ) -> Self {
+        self.item_class = Some(item_class.to_owned());
+        self.attributes = Box::new(attributes);
+        self.details = details;
+        self
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct EventDetails {
     pub details: InnerDetails,
     kind: AddedOrUpdated,
 }
 
+#[derive(Debug)]
 pub enum FilteredEventData {
     Added(InnerDetails),
     Updated(InnerDetails),
-    Removed { seen_at: f64, modified_by: i32 },
+    Removed {
+        backend: Backend,
+        seen_at: f64,
+        modified_by: ProcessInfo,
+        /// Not every backend can tell what was removed.
+        item_name: Option<String>,
+    },
+    /// An item's secret was asked for. `modified_at` is when that happened.
+    Accessed(InnerDetails),
+    /// A whole keystore was locked or unlocked, like a wallet being closed or opened.
+    LockChanged {
+        backend: Backend,
+        seen_at: f64,
+        modified_by: ProcessInfo,
+        keystore: String,
+        locked: bool,
+    },
 }
 
 impl FilteredEventData {
-    pub fn changer_pid(&self) -> i32 {
+    pub fn backend(&self) -> Backend {
+        match self {
+            FilteredEventData::Added(InnerDetails { backend, .. })
+            | FilteredEventData::Updated(InnerDetails { backend, .. })
+            | FilteredEventData::Removed { backend, .. }
+            | FilteredEventData::Accessed(InnerDetails { backend, .. })
+            | FilteredEventData::LockChanged { backend, .. } => *backend,
+        }
+    }
+
+    pub fn changer(&self) -> &ProcessInfo {
+        match self {
+            FilteredEventData::Added(InnerDetails { modified_by, .. }) => modified_by,
+            FilteredEventData::Updated(InnerDetails { modified_by, .. }) => modified_by,
+            FilteredEventData::Removed { modified_by, .. } => modified_by,
+            FilteredEventData::Accessed(InnerDetails { modified_by, .. }) => modified_by,
+            FilteredEventData::LockChanged { modified_by, .. } => modified_by,
+        }
+    }
+
+    /// When the change happened, as a Core Foundation absolute time.
+    pub fn changed_at(&self) -> f64 {
         match self {
-            FilteredEventData::Added(InnerDetails { modified_by, .. }) => *modified_by,
-            FilteredEventData::Updated(InnerDetails { modified_by, .. }) => *modified_by,
-            FilteredEventData::Removed { modified_by, .. } => *modified_by,
+            FilteredEventData::Added(InnerDetails { modified_at, .. }) => *modified_at,
+            FilteredEventData::Updated(InnerDetails { modified_at, .. }) => *modified_at,
+            FilteredEventData::Removed { seen_at, .. } => *seen_at,
+            FilteredEventData::Accessed(InnerDetails { modified_at, .. }) => *modified_at,
+            FilteredEventData::LockChanged { seen_at, .. } => *seen_at,
         }
     }
 
@@ -54,22 +252,108 @@ impl FilteredEventData {
         match self {
             FilteredEventData::Added(InnerDetails { item_name, .. }) => Some(item_name.as_str()),
             FilteredEventData::Updated(InnerDetails { item_name, .. }) => Some(item_name.as_str()),
-            FilteredEventData::Removed { .. } => None,
+            FilteredEventData::Removed { item_name, .. } => item_name.as_deref(),
+            FilteredEventData::Accessed(InnerDetails { item_name, .. }) => Some(item_name.as_str()),
+            // The keystore stands in for an item, so it can be ignored like one.
+            FilteredEventData::LockChanged { keystore, .. } => Some(keystore.as_str()),
+        }
+    }
+
+    pub fn item_class(&self) -> Option<&str> {
+        match self {
+            FilteredEventData::Added(InnerDetails { item_class, .. })
+            | FilteredEventData::Updated(InnerDetails { item_class, .. })
+            | FilteredEventData::Accessed(InnerDetails { item_class, .. }) => item_class.as_deref(),
+            FilteredEventData::Removed { .. } | FilteredEventData::LockChanged { .. } => None,
+        }
+    }
+
+    /// The item's attributes, which aren't known for removals.
+    pub fn attributes(&self) -> Option<&ItemAttributes> {
+        match self {
+            FilteredEventData::Added(InnerDetails { attributes, .. })
+            | FilteredEventData::Updated(InnerDetails { attributes, .. })
+            | FilteredEventData::Accessed(InnerDetails { attributes, .. }) => Some(attributes),
+            FilteredEventData::Removed { .. } | FilteredEventData::LockChanged { .. } => None,
+        }
+    }
+
+    pub fn details(&self) -> &[ItemDetail] {
+        match self {
+            FilteredEventData::Added(InnerDetails { details, .. })
+            | FilteredEventData::Updated(InnerDetails { details, .. })
+            | FilteredEventData::Accessed(InnerDetails { details, .. }) => details,
+            FilteredEventData::Removed { .. } | FilteredEventData::LockChanged { .. } => &[],
+        }
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            FilteredEventData::Added(_) => "added",
+            FilteredEventData::Updated(_) => "updated",
+            FilteredEventData::Removed { .. } => "removed",
+            FilteredEventData::Accessed(_) => "accessed",
+            FilteredEventData::LockChanged { locked: true, .. } => "locked",
+            FilteredEventData::LockChanged { locked: false, .. } => "unlocked",
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum EventData {
     AddOrUpdate(EventDetails),
-    RemovedOrUpdate { seen_at: f64, modified_by: i32 },
+    RemovedOrUpdate {
+        #[serde(default)]
+        backend: Backend,
+        seen_at: f64,
+        #[serde(deserialize_with = "process::deserialize_changer")]
+        modified_by: ProcessInfo,
+        /// Only known by backends that can still tell what was removed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        item_name: Option<String>,
+    },
+    Accessed(InnerDetails),
+    LockChanged {
+        #[serde(default)]
+        backend: Backend,
+        seen_at: f64,
+        #[serde(deserialize_with = "process::deserialize_changer")]
+        modified_by: ProcessInfo,
+        keystore: String,
+        locked: bool,
+    },
 }
 
 impl EventData {
+    /// Checks if this is a removal that `next` undoes straight away, which is how some
+    /// keystores edit items. Both have to be the same process changing the same item in the
+    /// same keystore at the same time.
+    pub fn is_replaced_by(&self, next: &EventData) -> bool {
+        let EventData::RemovedOrUpdate {
+            backend, item_name, ..
+        } = self
+        else {
+            return false;
+        };
+        let EventData::AddOrUpdate(EventDetails { details, .. }) = next else {
+            return false;
+        };
+
+        *backend == details.backend
+            && self.changer_pid() == next.changer_pid()
+            && self.changed_at() == next.changed_at()
+            // Older captures don't say what was removed.
+            && item_name
+                .as_ref()
+                .is_none_or(|item_name| *item_name == details.item_name)
+    }
+
     pub fn changer_pid(&self) -> i32 {
         match &self {
-            EventData::AddOrUpdate(EventDetails { details, .. }) => details.modified_by,
-            EventData::RemovedOrUpdate { modified_by, .. } => *modified_by,
+            EventData::AddOrUpdate(EventDetails { details, .. }) => details.modified_by.pid,
+            EventData::RemovedOrUpdate { modified_by, .. } => modified_by.pid,
+            EventData::Accessed(details) => details.modified_by.pid,
+            EventData::LockChanged { modified_by, .. } => modified_by.pid,
         }
     }
 
@@ -77,6 +361,8 @@ impl EventData {
         match &self {
             EventData::AddOrUpdate(EventDetails { details, .. }) => details.modified_at,
             EventData::RemovedOrUpdate { seen_at, .. } => *seen_at,
+            EventData::Accessed(details) => details.modified_at,
+            EventData::LockChanged { seen_at, .. } => *seen_at,
         }
     }
 
@@ -87,185 +373,30 @@ impl EventData {
                 AddedOrUpdated::Updated => FilteredEventData::Updated(details.details),
             },
             EventData::RemovedOrUpdate {
+                backend,
                 seen_at,
                 modified_by,
+                item_name,
             } => FilteredEventData::Removed {
+                backend,
                 seen_at,
                 modified_by,
+                item_name,
             },
-        }
-    }
-}
-
-pub fn start_keychain_monitor() -> mpsc::Receiver<EventData> {
-    let (tx, event_source) = mpsc::channel::<EventData>();
-
-    thread::Builder::new()
-        .name(String::from("Keychain Monitor"))
-        .spawn(move || {
-            let tx = Box::into_raw(Box::new(tx));
-
-            let status = unsafe {
-                bindings::SecKeychainAddCallback(
-                    callback_handler,
-                    SecKeychainEventMask::kSecAddEventMask
-                        | SecKeychainEventMask::kSecDeleteEventMask
-                        | SecKeychainEventMask::kSecUpdateEventMask,
-                    tx.cast(),
-                )
-            };
-            assert_eq!(status, 0, "failed to register callback");
-
-            // There has got to be a better way than this D:
-            // at some point, look into a custom runloop mode with a different runloop mode?
-            // let loop_mode = CFString::from_static_string("org.blackholefox.KeychainMonitor");
-            loop {
-                CFRunLoop::run_in_mode(
-                    unsafe { runloop::kCFRunLoopDefaultMode },
-                    Duration::from_secs(10),
-                    true,
-                );
-
-                thread::sleep(Duration::from_millis(100));
-
-                // How does the loop end? When the system shuts down the daemon. When is the callback removed?
-                // The heat death of the process.
-            }
-        })
-        .expect("failed to start keychain monitor");
-
-    event_source
-}
-
-#[allow(non_snake_case)]
-extern "C" fn callback_handler(
-    keychainEvent: SecKeychainEvent,
-    info: *mut SecKeychainCallbackInfo,
-    ctx: *mut c_void,
-) -> OSStatus {
-    let sender = unsafe { &*(ctx as *const c_void as *const mpsc::Sender<EventData>) };
-
-    log::trace!("received callback for {:?} event", keychainEvent);
-
-    let info = unsafe { &*info };
-
-    if info.item.is_null() {
-        log::warn!("received unusable event with no item");
-        return 0;
-    }
-
-    let items = CFArray::from_copyable(&[info.item]);
-
-    let mut query = unsafe {
-        CFMutableDictionary::from_CFType_pairs(&[
-            (bindings::kSecMatchItemList.cast(), items.as_CFTypeRef()),
-            (
-                kSecReturnAttributes.cast(),
-                CFBoolean::true_value().as_CFTypeRef(),
-            ),
-            (kSecMatchLimit.cast(), bindings::kSecMatchLimitOne.cast()),
-        ])
-    };
-
-    // TODO: support more types, like keys?
-    let mut supported_item_types =
-        unsafe { [kSecClassGenericPassword, kSecClassInternetPassword].into_iter() };
-
-    let attributes = loop {
-        let class = match supported_item_types.next() {
-            Some(c) => c,
-            None => {
-                let now = CFDate::now();
-
-                log::trace!("item was removed or not supported");
-
-                // item wasnt there (or not supported), so consider that it may be deleted.
-                // we only know for sure if there isn't another event right after it.
-                //
-                // branch is taken when `kSecClassInternetPassword` items are updated.
-                send_event(
-                    sender,
-                    EventData::RemovedOrUpdate {
-                        seen_at: now.abs_time().floor(),
-                        modified_by: info.pid,
-                    },
-                );
-
-                return 0;
-            }
-        };
-
-        query.set(unsafe { kSecClass.cast() }, class.cast());
-
-        let mut attributes = std::ptr::null();
-
-        let status =
-            unsafe { bindings::SecItemCopyMatching(query.as_concrete_TypeRef(), &mut attributes) };
-
-        match status {
-            0 => {
-                let attributes: CFDictionary<CFString, *const c_void> =
-                    unsafe { CFDictionary::wrap_under_create_rule(attributes.cast()) };
-
-                break attributes;
-            }
-            security_framework_sys::base::errSecItemNotFound => {
-                continue;
-            }
-            code => panic!("failed to get item attributes {code}"),
-        }
-    };
-
-    let label = unsafe {
-        attributes
-            .find(kSecAttrLabel)
-            .map(|ptr| CFString::wrap_under_get_rule(ptr.cast()))
-            .unwrap()
-    };
-
-    let mtime = unsafe {
-        attributes
-            .find(bindings::kSecAttrModificationDate)
-            .map(|ptr| CFDate::wrap_under_get_rule(ptr.cast()))
-            .unwrap()
-    };
-
-    let kind = match keychainEvent {
-        _ if keychainEvent.contains(SecKeychainEvent::kSecAddEvent) => AddedOrUpdated::Added,
-        _ if keychainEvent.contains(SecKeychainEvent::kSecDeleteEvent) => {
-            send_event(
-                sender,
-                EventData::RemovedOrUpdate {
-                    seen_at: mtime.abs_time(),
-                    modified_by: info.pid,
-                },
-            );
-            return 0;
-        }
-        // below is dead code but whatever. This event never fires on modern macOS versions :(
-        _ if keychainEvent.contains(SecKeychainEvent::kSecUpdateEvent) => AddedOrUpdated::Updated,
-        _ => unreachable!("system returned unwanted event type"),
-    };
-
-    log::trace!("item was added or updated");
-
-    send_event(
-        sender,
-        EventData::AddOrUpdate(EventDetails {
-            details: InnerDetails {
-                item_name: label.to_string(),
-                modified_at: mtime.abs_time(),
-                modified_by: info.pid,
+            EventData::Accessed(details) => FilteredEventData::Accessed(details),
+            EventData::LockChanged {
+                backend,
+                seen_at,
+                modified_by,
+                keystore,
+                locked,
+            } => FilteredEventData::LockChanged {
+                backend,
+                seen_at,
+                modified_by,
+                keystore,
+                locked,
             },
-            kind,
-        }),
-    );
-
-    0
-}
-
-fn send_event(sender: &mpsc::Sender<EventData>, event: EventData) {
-    if sender.send(event).is_err() {
-        log::warn!("event stream receiver has shutdown")
+        }
     }
 }
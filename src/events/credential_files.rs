@@ -0,0 +1,225 @@
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use super::{
+    snapshot::{self, SnapshotItem},
+    Backend, EventData,
+};
+use crate::{
+    clock::unix_to_cf,
+    items::{
+        attr,
+        credential_files::{self, Entry, Format},
+        AttributeValue, ClassSpec, ResolvedItem,
+    },
+    process::ProcessInfo,
+};
+
+#[cfg(target_os = "linux")]
+mod inotify;
+#[cfg(target_os = "linux")]
+pub(super) use inotify::Watcher;
+#[cfg(target_os = "macos")]
+mod kqueue;
+#[cfg(target_os = "macos")]
+use kqueue::Watcher;
+
+/// Where the usual credential files live, relative to the home directory.
+const DEFAULT_FILES: &[&str] = &[
+    ".netrc",
+    ".git-credentials",
+    ".pgpass",
+    ".aws/credentials",
+    ".docker/config.json",
+    ".kube/config",
+];
+
+/// How long to wait after a change for the rest of it to land, since tools often write a file
+/// in several steps.
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// Watching files that hold credentials outside of any keystore, like `~/.netrc`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct CredentialFilesConfig {
+    pub(crate) enabled: bool,
+    /// The files to watch, relative to the home directory unless they're absolute. Defaults to
+    /// where netrc, git, PostgreSQL, AWS, Docker and Kubernetes keep credentials.
+    pub(crate) files: Vec<CredentialFile>,
+}
+
+/// A file to watch, either by path alone or along with the format it's in.
+///
+/// Example: `"~/work/.netrc"` or `{ path = "clusters/prod.yaml", format = "kubeconfig" }`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum CredentialFile {
+    Path(PathBuf),
+    WithFormat { path: PathBuf, format: Format },
+}
+
+impl CredentialFilesConfig {
+    /// Every file to watch, along with its format. Files in a format that can't be worked out
+    /// are left out.
+    fn resolve(&self, home: &Path) -> Vec<(PathBuf, Format)> {
+        let configured: Vec<(PathBuf, Option<Format>)> = if self.files.is_empty() {
+            DEFAULT_FILES
+                .iter()
+                .map(|file| (PathBuf::from(file), None))
+                .collect()
+        } else {
+            self.files
+                .iter()
+                .map(|file| match file {
+                    CredentialFile::Path(path) => (path.clone(), None),
+                    CredentialFile::WithFormat { path, format } => (path.clone(), Some(*format)),
+                })
+                .collect()
+        };
+
+        configured
+            .into_iter()
+            .filter_map(|(path, format)| {
+                let path = match path.strip_prefix("~") {
+                    Ok(relative) => home.join(relative),
+                    Err(_) => home.join(path),
+                };
+
+                match format.or_else(|| Format::detect(&path)) {
+                    Some(format) => Some((path, format)),
+                    None => {
+                        log::warn!(
+                            "can't tell what format {} is in, set one for it",
+                            path.display()
+                        );
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// A watched file, and what was in it when it was last read.
+struct WatchedFile {
+    path: PathBuf,
+    format: Format,
+    entries: HashMap<String, Entry>,
+}
+
+pub fn start_credential_file_watcher(
+    config: &CredentialFilesConfig,
+    home: &Path,
+    sender: mpsc::Sender<EventData>,
+) {
+    let files = config.resolve(home);
+
+    thread::Builder::new()
+        .name(String::from("Credential File Watcher"))
+        .spawn(move || {
+            if let Err(e) = watch(files, &sender) {
+                log::error!("stopped watching credential files: {e}");
+            }
+        })
+        .expect("failed to start credential file watcher");
+}
+
+fn watch(files: Vec<(PathBuf, Format)>, sender: &mpsc::Sender<EventData>) -> io::Result<()> {
+    let mut watcher = Watcher::new(files.iter().map(|(path, _)| path.clone()).collect())?;
+
+    let mut watched: Vec<WatchedFile> = files
+        .into_iter()
+        .map(|(path, format)| {
+            let entries = read_entries(&path, format).unwrap_or_else(|e| {
+                log::warn!("couldn't read {}: {e}", path.display());
+                HashMap::new()
+            });
+
+            WatchedFile {
+                path,
+                format,
+                entries,
+            }
+        })
+        .collect();
+
+    log::debug!("watching {} credential files", watched.len());
+
+    loop {
+        watcher.wait(SETTLE_TIME)?;
+
+        // Only the files themselves can say what changed, so all of them are looked at again.
+        for file in &mut watched {
+            let entries = match read_entries(&file.path, file.format) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    // Whatever was in the file is still assumed to be there.
+                    log::warn!("couldn't read {}: {e}", file.path.display());
+                    continue;
+                }
+            };
+
+            let changes = snapshot::changes(
+                Backend::CredentialFile,
+                &file.entries,
+                &entries,
+                &ProcessInfo::unknown(0),
+            );
+            for event in changes {
+                if sender.send(event).is_err() {
+                    log::warn!("event stream receiver has shutdown");
+                    return Ok(());
+                }
+            }
+
+            file.entries = entries;
+        }
+    }
+}
+
+/// Reads a file's entries, by their ids. A file that doesn't exist has none.
+fn read_entries(path: &Path, format: Format) -> Result<HashMap<String, Entry>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let modified_at = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|at| at.duration_since(UNIX_EPOCH).ok())
+        .map(|at| unix_to_cf(at.as_secs()));
+
+    let entries = credential_files::parse(format, &path.to_string_lossy(), &contents)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|mut entry| {
+            if let Some(at) = modified_at {
+                entry
+                    .attributes
+                    .insert(attr::MODIFIED_AT.to_owned(), AttributeValue::Date(at));
+            }
+
+            (entry.id.clone(), entry)
+        })
+        .collect())
+}
+
+impl SnapshotItem for Entry {
+    fn resolve(&self) -> (&'static ClassSpec, ResolvedItem) {
+        (self.class, self.class.resolve(&self.attributes))
+    }
+
+    fn differs_from(&self, previous: &Self) -> bool {
+        self.digest != previous.digest
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    ffi::{CString, OsStr},
+    fs::File,
+    io::{self, Read},
+    mem,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, FromRawFd},
+    },
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// What's watched in directories. Files are usually replaced rather than written to, which only
+/// their directory sees.
+const DIRECTORY_EVENTS: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_MOVED_TO
+    | libc::IN_MOVED_FROM
+    | libc::IN_CREATE
+    | libc::IN_DELETE;
+
+pub struct Watcher {
+    inotify: File,
+    /// The files to watch, or directories to watch everything in.
+    files: Vec<PathBuf>,
+    /// The directory each watch is on.
+    directories: HashMap<i32, PathBuf>,
+}
+
+impl Watcher {
+    pub fn new(files: Vec<PathBuf>) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            inotify: unsafe { File::from_raw_fd(fd) },
+            files,
+            directories: HashMap::new(),
+        })
+    }
+
+    /// Waits until one of the files might have changed, then a little longer for the rest of
+    /// the change to land.
+    pub fn wait(&mut self, settle: Duration) -> io::Result<()> {
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            self.add_watches();
+
+            let read = self.inotify.read(&mut buffer)?;
+            if self.any_relevant(&buffer[..read]) {
+                break;
+            }
+        }
+
+        thread::sleep(settle);
+
+        // Whatever else happened meanwhile is covered by the same look at the files.
+        while self.readable() && self.inotify.read(&mut buffer)? > 0 {}
+
+        Ok(())
+    }
+
+    /// Watches every file's directory, or the closest one that exists yet, so the file is seen
+    /// once it's created. Watches that are already there are left as they are.
+    fn add_watches(&mut self) {
+        for file in &self.files {
+            if file.is_dir() {
+                if let Ok(watch) = add_watch(&self.inotify, file, DIRECTORY_EVENTS) {
+                    self.directories.insert(watch, file.to_owned());
+                }
+            } else {
+                // Symlinked files are only seen changing through a watch of their own.
+                let _ = add_watch(&self.inotify, file, libc::IN_CLOSE_WRITE);
+            }
+
+            if let Some(directory) = file.ancestors().skip(1).find(|dir| dir.is_dir()) {
+                match add_watch(&self.inotify, directory, DIRECTORY_EVENTS) {
+                    Ok(watch) => {
+                        self.directories.insert(watch, directory.to_owned());
+                    }
+                    Err(e) => log::debug!("couldn't watch {}: {e}", directory.display()),
+                }
+            }
+        }
+    }
+
+    /// Checks if any of the events are about one of the files, something in one of the
+    /// directories, or a directory on the way to one.
+    fn any_relevant(&self, mut events: &[u8]) -> bool {
+        let header = mem::size_of::<libc::inotify_event>();
+        let mut relevant = false;
+
+        while events.len() >= header {
+            let event: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(events.as_ptr().cast()) };
+            let name_len = event.len as usize;
+            let name = &events[header..(header + name_len).min(events.len())];
+            let name = Path::new(OsStr::from_bytes(
+                name.split(|b| *b == 0).next().unwrap_or_default(),
+            ));
+
+            relevant |= match self.directories.get(&event.wd) {
+                Some(directory) => {
+                    let changed = directory.join(name);
+                    self.files
+                        .iter()
+                        .any(|file| file.starts_with(&changed) || changed.starts_with(file))
+                }
+                // Watches on the files themselves.
+                None => true,
+            };
+
+            events = &events[(header + name_len).min(events.len())..];
+        }
+
+        relevant
+    }
+
+    fn readable(&self) -> bool {
+        let mut poll = libc::pollfd {
+            fd: self.inotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        unsafe { libc::poll(&mut poll, 1, 0) > 0 }
+    }
+}
+
+fn add_watch(inotify: &File, path: &Path, mask: u32) -> io::Result<i32> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let watch = unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), path.as_ptr(), mask) };
+    if watch < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(watch)
+}
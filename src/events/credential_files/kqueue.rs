@@ -0,0 +1,125 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, FromRawFd},
+    },
+    path::{Path, PathBuf},
+    ptr, thread,
+    time::Duration,
+};
+
+/// Writing to a directory means something in it was created, removed or renamed.
+const VNODE_EVENTS: u32 = libc::NOTE_WRITE
+    | libc::NOTE_EXTEND
+    | libc::NOTE_DELETE
+    | libc::NOTE_RENAME
+    | libc::NOTE_ATTRIB;
+
+pub struct Watcher {
+    queue: File,
+    files: Vec<PathBuf>,
+    /// What's currently watched. Closing these removes their watches.
+    open: Vec<File>,
+}
+
+impl Watcher {
+    pub fn new(files: Vec<PathBuf>) -> io::Result<Self> {
+        let fd = unsafe { libc::kqueue() };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            queue: unsafe { File::from_raw_fd(fd) },
+            files,
+            open: Vec::new(),
+        })
+    }
+
+    /// Waits until one of the files might have changed, then a little longer for the rest of
+    /// the change to land.
+    pub fn wait(&mut self, settle: Duration) -> io::Result<()> {
+        // Files that were replaced are new files, so everything is watched again every time.
+        self.open.clear();
+
+        for file in &self.files {
+            if let Ok(opened) = open_for_events(file) {
+                self.open.push(opened);
+            }
+
+            // The file's directory, or the closest one that exists yet, sees it being created.
+            if let Some(directory) = file.ancestors().skip(1).find(|dir| dir.is_dir()) {
+                match open_for_events(directory) {
+                    Ok(opened) => self.open.push(opened),
+                    Err(e) => log::debug!("couldn't watch {}: {e}", directory.display()),
+                }
+            }
+        }
+
+        let changes: Vec<libc::kevent> = self
+            .open
+            .iter()
+            .map(|file| libc::kevent {
+                ident: file.as_raw_fd() as libc::uintptr_t,
+                filter: libc::EVFILT_VNODE,
+                flags: libc::EV_ADD | libc::EV_CLEAR,
+                fflags: VNODE_EVENTS,
+                data: 0,
+                udata: ptr::null_mut(),
+            })
+            .collect();
+
+        self.kevent(&changes, None)?;
+        thread::sleep(settle);
+
+        // Whatever else happened meanwhile is covered by the same look at the files.
+        while self.kevent(&[], Some(Duration::ZERO))? {}
+
+        Ok(())
+    }
+
+    /// Makes changes to the queue, then waits for an event. Returns if there was one.
+    fn kevent(&self, changes: &[libc::kevent], timeout: Option<Duration>) -> io::Result<bool> {
+        let timeout = timeout.map(|timeout| libc::timespec {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_nsec: timeout.subsec_nanos() as libc::c_long,
+        });
+
+        let mut event = std::mem::MaybeUninit::<libc::kevent>::uninit();
+
+        let found = unsafe {
+            libc::kevent(
+                self.queue.as_raw_fd(),
+                changes.as_ptr(),
+                changes.len() as libc::c_int,
+                event.as_mut_ptr(),
+                1,
+                timeout
+                    .as_ref()
+                    .map_or(ptr::null(), |timeout| timeout as *const libc::timespec),
+            )
+        };
+
+        if found < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(found > 0)
+    }
+}
+
+/// Opens something only to be told about changes to it, which doesn't keep its volume busy.
+fn open_for_events(path: &Path) -> io::Result<File> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_EVTONLY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
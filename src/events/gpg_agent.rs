@@ -0,0 +1,21 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[cfg(target_os = "linux")]
+mod proxy;
+#[cfg(target_os = "linux")]
+pub use proxy::start_gpg_agent_proxy;
+
+/// Sitting between GnuPG and its agent, to see every private key and passphrase that's used,
+/// along with keys being imported or deleted.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct GpgAgentConfig {
+    pub(crate) enabled: bool,
+    /// Where clients connect instead of the agent. Defaults to `keeper_of_keys/S.gpg-agent` in
+    /// the runtime directory.
+    pub(crate) socket: Option<PathBuf>,
+    /// The agent everything is passed on to. Defaults to where `gpgconf` says it is.
+    pub(crate) upstream: Option<PathBuf>,
+}
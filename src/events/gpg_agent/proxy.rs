@@ -0,0 +1,613 @@
+use std::{
+    collections::HashSet,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{mpsc, Arc, Mutex},
+};
+
+use super::GpgAgentConfig;
+use crate::{
+    access,
+    clock::cf_now,
+    events::{AddedOrUpdated, Backend, EventData, EventDetails, InnerDetails},
+    items::{gpg_agent, ItemDetail},
+    process::{ProcessCache, ProcessInfo},
+};
+
+/// Assuan lines are at most 1000 bytes, so anything much longer isn't the agent protocol.
+const MAX_LINE_LEN: u64 = 4096;
+
+/// Keygrips of the keys the agent holds.
+type Keys = HashSet<String>;
+
+/// What every client connection shares.
+struct Shared {
+    upstream: PathBuf,
+    processes: Mutex<ProcessCache>,
+    /// What the agent held when it was last asked.
+    keys: Mutex<Keys>,
+    events: mpsc::Sender<EventData>,
+}
+
+/// What a client told the agent about the key it's going to use next.
+#[derive(Default)]
+struct Session {
+    keygrip: Option<String>,
+    /// The description GnuPG gives for the key, which has its user ID in it.
+    description: Option<String>,
+}
+
+/// Starts passing gpg-agent requests through a socket of its own, reporting every private key
+/// and passphrase that's used as an `Accessed` event, and keys being imported or deleted as
+/// they're added or removed.
+///
+/// GnuPG only ever looks for the agent in one place, so clients have to be pointed at the proxy
+/// with a redirect there.
+pub fn start_gpg_agent_proxy(
+    config: &GpgAgentConfig,
+    events: mpsc::Sender<EventData>,
+) -> io::Result<()> {
+    let socket = access::proxy_socket(config.socket.as_deref(), "S.gpg-agent")?;
+
+    let upstream = match &config.upstream {
+        Some(upstream) => upstream.clone(),
+        None => agent_socket()?,
+    };
+
+    // A redirect would send everything straight back to the proxy.
+    if upstream == socket || upstream.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the proxy would pass everything on to itself, set `upstream` to the real agent",
+        ));
+    }
+
+    let keys = list_keys(&upstream)?;
+
+    let keys_held = keys.len();
+    let shared = Arc::new(Shared {
+        upstream,
+        processes: Mutex::new(ProcessCache::default()),
+        keys: Mutex::new(keys),
+        events,
+    });
+
+    access::serve_proxy(&socket, "GnuPG Agent", move |client, peer| {
+        serve(client, peer.pid, &shared)
+    })?;
+
+    log::info!(
+        "proxying the GnuPG agent holding {keys_held} keys at {}",
+        socket.display()
+    );
+
+    Ok(())
+}
+
+/// Passes a client's commands on one at a time, since the agent finishes each before the next.
+fn serve(client: UnixStream, pid: i32, shared: &Shared) -> io::Result<()> {
+    let agent = UnixStream::connect(&shared.upstream)?;
+    let mut to_agent = agent.try_clone()?;
+    let mut from_agent = BufReader::new(agent);
+    let mut to_client = client.try_clone()?;
+    let mut from_client = BufReader::new(client);
+
+    // The agent greets every client first.
+    relay_response(
+        &mut from_agent,
+        &mut to_client,
+        &mut from_client,
+        &mut to_agent,
+    )?;
+
+    let mut session = Session::default();
+    // Most clients only ask about keys, so they're only looked up once they use one.
+    let mut looked_up = None;
+
+    while let Some(line) = read_line(&mut from_client)? {
+        to_agent.write_all(&line)?;
+
+        let line = String::from_utf8_lossy(&line);
+        let (command, arguments) = split_command(&line);
+
+        match command.as_str() {
+            "SIGKEY" | "SETKEY" => session.keygrip = Some(arguments.to_owned()),
+            "SETKEYDESC" => session.description = Some(unescape(arguments)),
+            "RESET" => session = Session::default(),
+            _ => {}
+        }
+
+        let succeeded = relay_response(
+            &mut from_agent,
+            &mut to_client,
+            &mut from_client,
+            &mut to_agent,
+        )?;
+        if !succeeded {
+            continue;
+        }
+
+        let mut caller = || {
+            looked_up
+                .get_or_insert_with(|| shared.processes.lock().unwrap().lookup(pid))
+                .clone()
+        };
+
+        match command.as_str() {
+            "PKSIGN" => report_key_use("sign", &session, caller(), shared),
+            "PKDECRYPT" => report_key_use("decrypt", &session, caller(), shared),
+            "EXPORT_KEY" => {
+                let exported = Session {
+                    keygrip: last_argument(arguments),
+                    description: session.description.clone(),
+                };
+                report_key_use("export", &exported, caller(), shared);
+            }
+            "GET_PASSPHRASE" => report_passphrase(arguments, caller(), shared),
+            "IMPORT_KEY" | "GENKEY" | "DELETE_KEY" => {
+                // Deleting a key comes with the key's description.
+                let deleted = (command == "DELETE_KEY")
+                    .then(|| last_argument(arguments))
+                    .flatten()
+                    .zip(session.description.as_deref().and_then(user_id));
+
+                report_key_changes(caller(), deleted, shared);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Passes the agent's response to a command back to the client, along with whatever the client
+/// answers its inquiries with, until the agent says if the command worked.
+fn relay_response(
+    from_agent: &mut BufReader<UnixStream>,
+    to_client: &mut UnixStream,
+    from_client: &mut BufReader<UnixStream>,
+    to_agent: &mut UnixStream,
+) -> io::Result<bool> {
+    loop {
+        let line = read_line(from_agent)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+        to_client.write_all(&line)?;
+
+        match split_command(&String::from_utf8_lossy(&line)).0.as_str() {
+            "OK" => return Ok(true),
+            "ERR" => return Ok(false),
+            // Inquiries are answered with data lines, until the client says it's done.
+            "INQUIRE" => loop {
+                let answer = read_line(from_client)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+                to_agent.write_all(&answer)?;
+
+                if matches!(
+                    split_command(&String::from_utf8_lossy(&answer)).0.as_str(),
+                    "END" | "CAN"
+                ) {
+                    break;
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+fn report_key_use(operation: &str, session: &Session, user: ProcessInfo, shared: &Shared) {
+    let keygrip = match &session.keygrip {
+        Some(keygrip) => keygrip,
+        None => return,
+    };
+
+    let user_id = session.description.as_deref().and_then(user_id);
+    let name = user.display_name().unwrap_or("Unknown");
+    log::info!("{name} [{}] used {keygrip} to {operation}", user.pid);
+
+    let mut details = key_details(keygrip, user_id.as_deref(), user);
+    details.details.push(ItemDetail {
+        name: String::from("operation"),
+        value: operation.to_owned(),
+    });
+
+    let _ = shared.events.send(EventData::Accessed(details));
+}
+
+/// Reports a passphrase being handed out, which is only ever asked for by its cache ID along
+/// with what to ask the user with.
+fn report_passphrase(arguments: &str, user: ProcessInfo, shared: &Shared) {
+    // Options come first, then the cache ID, error message, prompt and description.
+    let mut arguments = arguments.split(' ').filter(|arg| !arg.starts_with("--"));
+    let cache_id = match arguments.next() {
+        Some(cache_id) if !cache_id.is_empty() => unescape(cache_id),
+        _ => return,
+    };
+    let user_id = arguments.nth(2).map(unescape).as_deref().and_then(user_id);
+
+    let name = user.display_name().unwrap_or("Unknown");
+    log::info!("{name} [{}] was given the passphrase {cache_id}", user.pid);
+
+    let (class, attributes) = gpg_agent::passphrase_to_attributes(&cache_id, user_id.as_deref());
+    let item = class.resolve(&attributes);
+
+    let details = InnerDetails::new(Backend::GpgAgent, item.title, cf_now(), user).with_class(
+        class.name,
+        item.attributes,
+        item.details,
+    );
+    let _ = shared.events.send(EventData::Accessed(details));
+}
+
+/// Asks the agent what it holds now, and reports whatever came or went since it was last asked.
+/// `named` is a keygrip along with its user ID, if the client said.
+///
+/// Keys imported or deleted by going to the agent directly are only noticed here, so they get
+/// put down to whoever changes keys through the proxy next.
+fn report_key_changes(changer: ProcessInfo, named: Option<(String, String)>, shared: &Shared) {
+    let keys = match list_keys(&shared.upstream) {
+        Ok(keys) => keys,
+        Err(e) => {
+            log::warn!("couldn't list the GnuPG agent's keys: {e}");
+            return;
+        }
+    };
+
+    let user_id = |keygrip: &str| {
+        named
+            .as_ref()
+            .filter(|(named, _)| named == keygrip)
+            .map(|(_, user_id)| user_id.as_str())
+    };
+
+    let old = std::mem::replace(&mut *shared.keys.lock().unwrap(), keys.clone());
+
+    for keygrip in keys.difference(&old) {
+        let _ = shared.events.send(EventData::AddOrUpdate(EventDetails {
+            details: key_details(keygrip, user_id(keygrip), changer.clone()),
+            kind: AddedOrUpdated::Added,
+        }));
+    }
+
+    for keygrip in old.difference(&keys) {
+        let (class, attributes) = gpg_agent::key_to_attributes(keygrip, user_id(keygrip));
+
+        let _ = shared.events.send(EventData::RemovedOrUpdate {
+            backend: Backend::GpgAgent,
+            seen_at: cf_now(),
+            modified_by: changer.clone(),
+            item_name: Some(class.resolve(&attributes).title),
+        });
+    }
+}
+
+fn key_details(keygrip: &str, user_id: Option<&str>, process: ProcessInfo) -> InnerDetails {
+    let (class, attributes) = gpg_agent::key_to_attributes(keygrip, user_id);
+    let mut item = class.resolve(&attributes);
+
+    item.details.push(ItemDetail {
+        name: String::from("keygrip"),
+        value: keygrip.to_owned(),
+    });
+
+    InnerDetails::new(Backend::GpgAgent, item.title, cf_now(), process).with_class(
+        class.name,
+        item.attributes,
+        item.details,
+    )
+}
+
+/// Asks the agent for every key it holds, on a connection of its own.
+fn list_keys(agent: &Path) -> io::Result<Keys> {
+    let agent = UnixStream::connect(agent)?;
+    let mut to_agent = agent.try_clone()?;
+    let mut from_agent = BufReader::new(agent);
+
+    let mut keys = Keys::new();
+    let mut asked = false;
+
+    while let Some(line) = read_line(&mut from_agent)? {
+        let line = String::from_utf8_lossy(&line);
+        let (command, arguments) = split_command(&line);
+
+        match command.as_str() {
+            // The greeting, and then the answer.
+            "OK" if !asked => {
+                to_agent.write_all(b"KEYINFO --list\n")?;
+                asked = true;
+            }
+            "OK" => return Ok(keys),
+            "ERR" => {
+                return Err(io::Error::other(format!(
+                    "the agent couldn't list keys: {arguments}"
+                )))
+            }
+            "S" => {
+                if let Some(keygrip) = arguments.strip_prefix("KEYINFO ") {
+                    keys.extend(keygrip.split(' ').next().map(str::to_owned));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Err(io::ErrorKind::UnexpectedEof.into())
+}
+
+/// Where the agent is, going by `gpgconf`.
+fn agent_socket() -> io::Result<PathBuf> {
+    let output = Command::new("gpgconf")
+        .args(["--list-dirs", "agent-socket"])
+        .output()
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("couldn't run gpgconf, set `upstream`: {e}"),
+            )
+        })?;
+
+    let socket = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if !output.status.success() || socket.is_empty() {
+        return Err(io::Error::other(
+            "gpgconf didn't say where the agent is, set `upstream`",
+        ));
+    }
+
+    Ok(PathBuf::from(socket))
+}
+
+/// Reads one line, newline included, or nothing if the other end is done. Data lines can have
+/// any bytes in them, so they're passed on as they are.
+fn read_line(stream: &mut BufReader<UnixStream>) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    stream
+        .by_ref()
+        .take(MAX_LINE_LEN)
+        .read_until(b'\n', &mut line)?;
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    if line.last() != Some(&b'\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "refused a line that's far too long",
+        ));
+    }
+
+    Ok(Some(line))
+}
+
+/// Splits a line into its command, which isn't case sensitive, and the rest of it.
+fn split_command(line: &str) -> (String, &str) {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let (command, arguments) = line.split_once(' ').unwrap_or((line, ""));
+
+    (command.to_ascii_uppercase(), arguments.trim_start())
+}
+
+/// The last of a command's arguments, which is where keygrips go after any options.
+fn last_argument(arguments: &str) -> Option<String> {
+    arguments
+        .split(' ')
+        .next_back()
+        .filter(|arg| !arg.is_empty() && !arg.starts_with("--"))
+        .map(str::to_owned)
+}
+
+/// Decodes the `%XX` escapes Assuan arguments use, along with `+` standing for a space.
+fn unescape(text: &str) -> String {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes();
+
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = [bytes.next().unwrap_or(b'0'), bytes.next().unwrap_or(b'0')];
+                let value = std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                decoded.push(value.unwrap_or(b'?'));
+            }
+            byte => decoded.push(byte),
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Finds the user ID in a description GnuPG wrote for a key, where it's quoted.
+fn user_id(description: &str) -> Option<String> {
+    let start = description.find('"')? + 1;
+    let len = description[start..].find('"')?;
+
+    Some(description[start..start + len].to_owned()).filter(|user_id| !user_id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Stdio, time::Duration};
+
+    use super::*;
+
+    /// A GnuPG home of its own holding one key with no passphrase, along with its agent. Both are
+    /// stopped and cleaned up when dropped.
+    struct TestHome {
+        dir: PathBuf,
+        keygrip: String,
+    }
+
+    impl TestHome {
+        fn create(name: &str) -> Option<Self> {
+            let dir = std::env::temp_dir().join(format!("keeper-{name}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+
+            let gpg = |args: &[&str]| {
+                Command::new("gpg")
+                    .arg("--homedir")
+                    .arg(&dir)
+                    .args(["--batch", "--pinentry-mode", "loopback", "--passphrase", ""])
+                    .args(args)
+                    .stderr(Stdio::null())
+                    .output()
+            };
+
+            let generated = gpg(&[
+                "--quick-gen-key",
+                "Keeper Test <test@keeper>",
+                "ed25519",
+                "sign",
+                "never",
+            ]);
+            if !generated.is_ok_and(|output| output.status.success()) {
+                eprintln!("skipping, gpg can't make a key");
+                let _ = fs::remove_dir_all(&dir);
+                return None;
+            }
+
+            let listed = gpg(&["--with-keygrip", "--with-colons", "-K"]).unwrap();
+            let keygrip = String::from_utf8_lossy(&listed.stdout)
+                .lines()
+                .find_map(|line| line.strip_prefix("grp:::::::::"))
+                .map(|rest| rest.trim_end_matches(':').to_owned())
+                .expect("the key has no keygrip");
+
+            Some(Self { dir, keygrip })
+        }
+
+        fn agent(&self) -> PathBuf {
+            let output = Command::new("gpgconf")
+                .arg("--homedir")
+                .arg(&self.dir)
+                .args(["--list-dirs", "agent-socket"])
+                .output()
+                .unwrap();
+            PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
+        }
+    }
+
+    impl Drop for TestHome {
+        fn drop(&mut self) {
+            let _ = Command::new("gpgconf")
+                .arg("--homedir")
+                .arg(&self.dir)
+                .args(["--kill", "gpg-agent"])
+                .status();
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Sends one command, returning the data the agent sent back if it worked.
+    fn command(stream: &mut BufReader<UnixStream>, line: &str) -> Option<Vec<u8>> {
+        stream
+            .get_mut()
+            .write_all(format!("{line}\n").as_bytes())
+            .unwrap();
+        finish(stream)
+    }
+
+    fn finish(stream: &mut BufReader<UnixStream>) -> Option<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            let line = read_line(stream).unwrap().expect("the agent hung up");
+            match split_command(&String::from_utf8_lossy(&line)).0.as_str() {
+                "OK" => return Some(data),
+                "ERR" => return None,
+                "D" => data.extend_from_slice(&line[2..]),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn reports_signatures() {
+        let Some(home) = TestHome::create("gpg-reports") else {
+            return;
+        };
+
+        let socket = home.dir.join("proxy");
+        let config = GpgAgentConfig {
+            enabled: true,
+            socket: Some(socket.clone()),
+            upstream: Some(home.agent()),
+        };
+        let (tx, events) = mpsc::channel();
+        start_gpg_agent_proxy(&config, tx).unwrap();
+
+        let mut client = BufReader::new(UnixStream::connect(&socket).unwrap());
+        assert!(finish(&mut client).is_some());
+
+        let keygrip = &home.keygrip;
+        for line in [
+            format!("SIGKEY {keygrip}"),
+            String::from(
+                "SETKEYDESC Please+enter+the+passphrase+for%0A%22Keeper+Test+<test@keeper>%22",
+            ),
+            format!("SETHASH 8 {}", "ab".repeat(32)),
+        ] {
+            assert!(command(&mut client, &line).is_some(), "{line} failed");
+        }
+
+        let signature = command(&mut client, "PKSIGN").expect("PKSIGN failed");
+        assert!(signature.starts_with(b"(7:sig-val"));
+
+        let signed = events
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the proxy didn't report anything")
+            .assume_filtered();
+        assert_eq!(signed.kind_name(), "accessed");
+        assert_eq!(signed.item_title(), Some("Keeper Test <test@keeper>"));
+        assert_eq!(signed.changer().pid, std::process::id() as i32);
+        assert!(signed
+            .details()
+            .iter()
+            .any(|detail| detail.name == "operation" && detail.value == "sign"));
+    }
+
+    #[test]
+    fn splits_commands() {
+        assert_eq!(
+            split_command("pksign --hash=sha256\r\n"),
+            (String::from("PKSIGN"), "--hash=sha256")
+        );
+        assert_eq!(split_command("BYE\n"), (String::from("BYE"), ""));
+        assert_eq!(
+            split_command("SIGKEY   ABCDEF\n"),
+            (String::from("SIGKEY"), "ABCDEF")
+        );
+    }
+
+    #[test]
+    fn finds_the_last_argument() {
+        assert_eq!(last_argument("--force ABCDEF").as_deref(), Some("ABCDEF"));
+        assert_eq!(last_argument("ABCDEF").as_deref(), Some("ABCDEF"));
+        assert_eq!(last_argument("--force"), None);
+        assert_eq!(last_argument(""), None);
+    }
+
+    #[test]
+    fn unescapes_arguments() {
+        assert_eq!(
+            unescape("Keeper+Test%0A%22quoted%22"),
+            "Keeper Test\n\"quoted\""
+        );
+        assert_eq!(unescape("100%25"), "100%");
+        // Broken escapes don't take anything else down with them.
+        assert_eq!(unescape("bad%zz"), "bad?");
+    }
+
+    #[test]
+    fn finds_user_ids_in_descriptions() {
+        assert_eq!(
+            user_id(
+                "Please enter the passphrase for\n\"Keeper Test <test@keeper>\"\n255-bit EDDSA key"
+            )
+            .as_deref(),
+            Some("Keeper Test <test@keeper>")
+        );
+        assert_eq!(user_id("no quotes here"), None);
+        assert_eq!(user_id("empty \"\""), None);
+        assert_eq!(user_id("unterminated \"quote"), None);
+    }
+}
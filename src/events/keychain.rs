@@ -0,0 +1,319 @@
+use core::ffi::c_void;
+use core_foundation::{
+    array::CFArray,
+    base::{CFType, OSStatus, TCFType},
+    boolean::CFBoolean,
+    date::CFDate,
+    dictionary::{CFDictionary, CFMutableDictionary},
+    runloop::{self, CFRunLoop},
+    string::CFString,
+};
+use core_foundation_sys::{
+    base::{CFEqual, CFHash},
+    dictionary::CFDictionaryGetValue,
+};
+use security_framework_sys::{
+    base::errSecItemNotFound,
+    item::{
+        kSecClass, kSecMatchLimit, kSecReturnAttributes, kSecReturnData, kSecReturnRef,
+        kSecValueRef,
+    },
+};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use super::{AddedOrUpdated, Backend, EventData, EventDetails, InnerDetails};
+use crate::{
+    bindings::{self, SecKeychainCallbackInfo, SecKeychainEvent, SecKeychainEventMask},
+    items::{self, ClassSpec, CLASSES},
+    process::ProcessCache,
+};
+
+/// State handed to every invocation of the keychain callback.
+struct MonitorContext {
+    sender: mpsc::Sender<EventData>,
+    processes: ProcessCache,
+    /// Deleted items can't be looked up anymore, so every item's title is kept around to be
+    /// able to say what was removed.
+    titles: HashMap<ItemRef, String>,
+}
+
+/// A keychain item, compared the way the keychain compares them. That goes by the item's
+/// primary key, so the reference a deletion comes with still matches the item it was.
+struct ItemRef(CFType);
+
+impl ItemRef {
+    fn new(item: *const c_void) -> Self {
+        Self(unsafe { CFType::wrap_under_get_rule(item) })
+    }
+}
+
+impl PartialEq for ItemRef {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { CFEqual(self.0.as_CFTypeRef(), other.0.as_CFTypeRef()) != 0 }
+    }
+}
+
+impl Eq for ItemRef {}
+
+impl Hash for ItemRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(unsafe { CFHash(self.0.as_CFTypeRef()) } as usize);
+    }
+}
+
+pub fn start_keychain_monitor(sender: mpsc::Sender<EventData>) {
+    thread::Builder::new()
+        .name(String::from("Keychain Monitor"))
+        .spawn(move || {
+            let titles = CLASSES
+                .iter()
+                .flat_map(all_titles)
+                .collect::<HashMap<_, _>>();
+            log::debug!("watching {} keychain items", titles.len());
+
+            let ctx = Box::into_raw(Box::new(MonitorContext {
+                sender,
+                processes: ProcessCache::default(),
+                titles,
+            }));
+
+            let status = unsafe {
+                bindings::SecKeychainAddCallback(
+                    callback_handler,
+                    SecKeychainEventMask::kSecAddEventMask
+                        | SecKeychainEventMask::kSecDeleteEventMask
+                        | SecKeychainEventMask::kSecUpdateEventMask,
+                    ctx.cast(),
+                )
+            };
+            assert_eq!(status, 0, "failed to register callback");
+
+            // There has got to be a better way than this D:
+            // at some point, look into a custom runloop mode with a different runloop mode?
+            // let loop_mode = CFString::from_static_string("org.blackholefox.KeychainMonitor");
+            loop {
+                CFRunLoop::run_in_mode(
+                    unsafe { runloop::kCFRunLoopDefaultMode },
+                    Duration::from_secs(10),
+                    true,
+                );
+
+                thread::sleep(Duration::from_millis(100));
+
+                // How does the loop end? When the system shuts down the daemon. When is the callback removed?
+                // The heat death of the process.
+            }
+        })
+        .expect("failed to start keychain monitor");
+}
+
+/// Finds the title of every item of a class, along with the item it belongs to.
+fn all_titles(spec: &'static ClassSpec) -> Vec<(ItemRef, String)> {
+    let query = unsafe {
+        CFDictionary::from_CFType_pairs(&[
+            (
+                CFString::wrap_under_get_rule(kSecClass),
+                CFType::wrap_under_get_rule(items::keychain::sec_class(spec.class).cast()),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecReturnAttributes),
+                CFBoolean::true_value().as_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecReturnRef),
+                CFBoolean::true_value().as_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecMatchLimit),
+                CFType::wrap_under_get_rule(bindings::kSecMatchLimitAll.cast()),
+            ),
+        ])
+    };
+
+    let mut result = std::ptr::null();
+
+    match unsafe { bindings::SecItemCopyMatching(query.as_concrete_TypeRef(), &mut result) } {
+        0 => {}
+        errSecItemNotFound => return Vec::new(),
+        code => {
+            log::warn!(
+                "failed to list {} items, their removals won't be named: {code}",
+                spec.name
+            );
+            return Vec::new();
+        }
+    }
+
+    let found: CFArray<*const c_void> = unsafe { CFArray::wrap_under_create_rule(result.cast()) };
+
+    found
+        .iter()
+        .filter_map(|found| {
+            let item = unsafe { CFDictionaryGetValue((*found).cast(), kSecValueRef.cast()) };
+            if item.is_null() {
+                return None;
+            }
+
+            let attributes: CFDictionary<CFString, *const c_void> =
+                unsafe { CFDictionary::wrap_under_get_rule((*found).cast()) };
+            let title = spec
+                .resolve(&items::keychain::to_attributes(&attributes))
+                .title;
+
+            Some((ItemRef::new(item), title))
+        })
+        .collect()
+}
+
+#[allow(non_snake_case)]
+extern "C" fn callback_handler(
+    keychainEvent: SecKeychainEvent,
+    info: *mut SecKeychainCallbackInfo,
+    ctx: *mut c_void,
+) -> OSStatus {
+    // Callbacks are only ever delivered one at a time, on the monitor thread's runloop.
+    let ctx = unsafe { &mut *(ctx as *mut MonitorContext) };
+    let sender = &ctx.sender;
+
+    log::trace!("received callback for {:?} event", keychainEvent);
+
+    let info = unsafe { &*info };
+
+    // Grab details about the changer before doing anything else, since short-lived processes
+    // (like the `security` CLI) may be long gone by the time the event is handled.
+    let changer = ctx.processes.lookup(info.pid);
+
+    if info.item.is_null() {
+        log::warn!("received unusable event with no item");
+        return 0;
+    }
+
+    let item_ref = ItemRef::new(info.item as *const c_void);
+    let item_list = CFArray::from_copyable(&[info.item]);
+
+    let mut query = unsafe {
+        CFMutableDictionary::from_CFType_pairs(&[
+            (bindings::kSecMatchItemList.cast(), item_list.as_CFTypeRef()),
+            (
+                kSecReturnAttributes.cast(),
+                CFBoolean::true_value().as_CFTypeRef(),
+            ),
+            (kSecMatchLimit.cast(), bindings::kSecMatchLimitOne.cast()),
+        ])
+    };
+
+    let mut classes = CLASSES.iter();
+
+    let (spec, attributes) = loop {
+        let spec = match classes.next() {
+            Some(spec) => spec,
+            None => {
+                let now = CFDate::now();
+
+                log::trace!("item was removed or not supported");
+
+                // item wasnt there (or not supported), so consider that it may be deleted.
+                // we only know for sure if there isn't another event right after it.
+                //
+                // branch is taken when `kSecClassInternetPassword` items are updated.
+                send_event(
+                    sender,
+                    EventData::RemovedOrUpdate {
+                        backend: Backend::Keychain,
+                        seen_at: now.abs_time().floor(),
+                        modified_by: changer,
+                        item_name: ctx.titles.remove(&item_ref),
+                    },
+                );
+
+                return 0;
+            }
+        };
+
+        query.set(
+            unsafe { kSecClass.cast() },
+            items::keychain::sec_class(spec.class).cast(),
+        );
+
+        // Only ever true for certificates, which hold nothing secret.
+        if spec.needs_data {
+            query.set(
+                unsafe { kSecReturnData.cast() },
+                CFBoolean::true_value().as_CFTypeRef(),
+            );
+        } else {
+            query.remove(unsafe { kSecReturnData.cast() });
+        }
+
+        let mut attributes = std::ptr::null();
+
+        let status =
+            unsafe { bindings::SecItemCopyMatching(query.as_concrete_TypeRef(), &mut attributes) };
+
+        match status {
+            0 => {
+                let attributes: CFDictionary<CFString, *const c_void> =
+                    unsafe { CFDictionary::wrap_under_create_rule(attributes.cast()) };
+
+                break (spec, items::keychain::to_attributes(&attributes));
+            }
+            errSecItemNotFound => continue,
+            code => {
+                log::warn!("failed to look for the item as a {}: {code}", spec.name);
+                continue;
+            }
+        }
+    };
+
+    let item = spec.resolve(&attributes);
+
+    // Keys don't always keep track of when they were modified.
+    let modified_at = item.modified_at.unwrap_or_else(|| CFDate::now().abs_time());
+
+    let kind = match keychainEvent {
+        _ if keychainEvent.contains(SecKeychainEvent::kSecAddEvent) => AddedOrUpdated::Added,
+        _ if keychainEvent.contains(SecKeychainEvent::kSecDeleteEvent) => {
+            ctx.titles.remove(&item_ref);
+            send_event(
+                sender,
+                EventData::RemovedOrUpdate {
+                    backend: Backend::Keychain,
+                    seen_at: modified_at,
+                    modified_by: changer,
+                    item_name: Some(item.title),
+                },
+            );
+            return 0;
+        }
+        // below is dead code but whatever. This event never fires on modern macOS versions :(
+        _ if keychainEvent.contains(SecKeychainEvent::kSecUpdateEvent) => AddedOrUpdated::Updated,
+        _ => unreachable!("system returned unwanted event type"),
+    };
+
+    log::trace!("item was added or updated");
+
+    ctx.titles.insert(item_ref, item.title.clone());
+
+    send_event(
+        sender,
+        EventData::AddOrUpdate(EventDetails {
+            details: InnerDetails::new(Backend::Keychain, item.title, modified_at, changer)
+                .with_class(spec.name, item.attributes, item.details),
+            kind,
+        }),
+    );
+
+    0
+}
+
+fn send_event(sender: &mpsc::Sender<EventData>, event: EventData) {
+    if sender.send(event).is_err() {
+        log::warn!("event stream receiver has shutdown")
+    }
+}
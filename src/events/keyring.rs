@@ -0,0 +1,500 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Read},
+    os::unix::io::{AsRawFd, FromRawFd},
+    sync::mpsc,
+    thread,
+};
+
+use super::{AddedOrUpdated, Backend, EventData, EventDetails, InnerDetails};
+use crate::{
+    clock::cf_now,
+    items::keyring::{self, Key, KEYCTL_WATCH_KEY, SESSION_KEYRING, USER_KEYRING},
+    process::ProcessInfo,
+};
+
+/// Makes `pipe2` create a watch queue instead of a regular pipe.
+const O_NOTIFICATION_PIPE: libc::c_int = libc::O_EXCL;
+/// `_IO('W', 0x60)`, which sets how many notifications the queue holds.
+const IOC_WATCH_QUEUE_SET_SIZE: libc::c_ulong = 0x5760;
+const QUEUE_SIZE: libc::c_ulong = 256;
+
+const WATCH_TYPE_META: u32 = 0;
+const WATCH_TYPE_KEY_NOTIFY: u32 = 1;
+const WATCH_META_LOSS_NOTIFICATION: u32 = 1;
+
+const NOTIFY_KEY_INSTANTIATED: u32 = 0;
+const NOTIFY_KEY_UPDATED: u32 = 1;
+const NOTIFY_KEY_LINKED: u32 = 2;
+const NOTIFY_KEY_UNLINKED: u32 = 3;
+const NOTIFY_KEY_CLEARED: u32 = 4;
+const NOTIFY_KEY_REVOKED: u32 = 5;
+const NOTIFY_KEY_INVALIDATED: u32 = 6;
+const NOTIFY_KEY_SETATTR: u32 = 7;
+
+pub fn start_keyring_monitor(sender: mpsc::Sender<EventData>) {
+    thread::Builder::new()
+        .name(String::from("Kernel Keyring Monitor"))
+        .spawn(move || {
+            // Watch queues need Linux 5.8 and a kernel built with them, which not every one is.
+            if let Err(e) = monitor(&sender) {
+                log::warn!("can't watch the kernel keyrings: {e}");
+            }
+        })
+        .expect("failed to start kernel keyring monitor");
+}
+
+/// A key that's being watched, and the keyring it was found in.
+struct Watched {
+    key: Key,
+    keyring: i32,
+}
+
+/// One notification out of the queue.
+#[derive(Debug, PartialEq)]
+struct Notification {
+    kind: u32,
+    subtype: u32,
+    key: i32,
+    /// The key that was linked or unlinked, for changes to a keyring.
+    aux: i32,
+}
+
+struct Watcher {
+    queue: WatchQueue,
+    watched: HashMap<i32, Watched>,
+}
+
+fn monitor(sender: &mpsc::Sender<EventData>) -> io::Result<()> {
+    let mut watcher = Watcher {
+        queue: WatchQueue::new()?,
+        watched: HashMap::new(),
+    };
+
+    for (name, keyring) in [
+        ("session", keyring::keyring_id(SESSION_KEYRING)),
+        ("user", keyring::keyring_id(USER_KEYRING)),
+        ("persistent", keyring::persistent_keyring()),
+    ] {
+        match keyring {
+            // Nothing is reported about the root keyrings themselves, so they're their own parent.
+            Ok(keyring) => watcher.watch(keyring, keyring)?,
+            Err(e) => log::debug!("no {name} keyring to watch: {e}"),
+        }
+    }
+
+    log::debug!("watching {} kernel keys", watcher.watched.len());
+
+    loop {
+        for notification in watcher.queue.read()? {
+            let events = match notification.kind {
+                WATCH_TYPE_KEY_NOTIFY => watcher.handle(&notification),
+                WATCH_TYPE_META if notification.subtype == WATCH_META_LOSS_NOTIFICATION => {
+                    log::warn!("the kernel keyring queue overflowed, some changes were missed");
+                    continue;
+                }
+                // The other meta notification says a watched key was destroyed, which its
+                // keyring already reported.
+                _ => continue,
+            };
+
+            for event in events {
+                if sender.send(event).is_err() {
+                    log::warn!("event stream receiver has shutdown");
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Watcher {
+    /// Starts watching a key, along with everything in it if it's a keyring.
+    fn watch(&mut self, key: i32, keyring: i32) -> io::Result<()> {
+        if self.watched.contains_key(&key) {
+            return Ok(());
+        }
+
+        // Keys that can't be looked at can't be watched either.
+        let described = match keyring::describe(key) {
+            Ok(described) => described,
+            Err(e) => {
+                log::debug!("couldn't describe key {key}: {e}");
+                return Ok(());
+            }
+        };
+
+        match keyring::keyctl(
+            KEYCTL_WATCH_KEY,
+            key as libc::c_ulong,
+            self.queue.file.as_raw_fd() as libc::c_ulong,
+            0,
+        ) {
+            Ok(_) => {}
+            // The kernel was built without key notifications.
+            Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => return Err(e),
+            Err(e) => log::debug!("couldn't watch key {key}: {e}"),
+        }
+
+        let is_keyring = described.is_keyring();
+        self.watched.insert(
+            key,
+            Watched {
+                key: described,
+                keyring,
+            },
+        );
+
+        if is_keyring {
+            for linked in keyring::linked_keys(key).unwrap_or_default() {
+                self.watch(linked, key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, notification: &Notification) -> Vec<EventData> {
+        match notification.subtype {
+            NOTIFY_KEY_LINKED => {
+                if let Err(e) = self.watch(notification.aux, notification.key) {
+                    log::warn!("couldn't watch newly linked key: {e}");
+                }
+
+                self.changed(notification.aux, AddedOrUpdated::Added)
+                    .into_iter()
+                    .collect()
+            }
+            NOTIFY_KEY_UNLINKED => self.removed(notification.aux).into_iter().collect(),
+            // Keys that are still waiting to be instantiated are linked already.
+            NOTIFY_KEY_INSTANTIATED | NOTIFY_KEY_UPDATED | NOTIFY_KEY_SETATTR => {
+                if let Ok(described) = keyring::describe(notification.key) {
+                    if let Some(watched) = self.watched.get_mut(&notification.key) {
+                        watched.key = described;
+                    }
+                }
+
+                self.changed(notification.key, AddedOrUpdated::Updated)
+                    .into_iter()
+                    .collect()
+            }
+            // Revoked and invalidated keys can't be used anymore, so they're as good as gone.
+            NOTIFY_KEY_REVOKED | NOTIFY_KEY_INVALIDATED => {
+                self.removed(notification.key).into_iter().collect()
+            }
+            NOTIFY_KEY_CLEARED => {
+                let cleared: Vec<i32> = self
+                    .watched
+                    .iter()
+                    .filter(|(key, watched)| {
+                        watched.keyring == notification.key && **key != watched.keyring
+                    })
+                    .map(|(key, _)| *key)
+                    .collect();
+
+                cleared
+                    .into_iter()
+                    .filter_map(|key| self.removed(key))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn changed(&self, key: i32, kind: AddedOrUpdated) -> Option<EventData> {
+        let watched = self.watched.get(&key)?;
+        let (class, attributes) = keyring::to_attributes(&watched.key, &self.keyring_name(watched));
+        let item = class.resolve(&attributes);
+
+        Some(EventData::AddOrUpdate(EventDetails {
+            details: InnerDetails::new(
+                Backend::KernelKeyring,
+                item.title,
+                cf_now(),
+                ProcessInfo::unknown(0),
+            )
+            .with_class(class.name, item.attributes, item.details),
+            kind,
+        }))
+    }
+
+    fn removed(&mut self, key: i32) -> Option<EventData> {
+        let watched = self.watched.remove(&key)?;
+
+        Some(EventData::RemovedOrUpdate {
+            backend: Backend::KernelKeyring,
+            seen_at: cf_now(),
+            modified_by: ProcessInfo::unknown(0),
+            item_name: Some(watched.key.description),
+        })
+    }
+
+    fn keyring_name(&self, watched: &Watched) -> String {
+        self.watched
+            .get(&watched.keyring)
+            .map(|keyring| keyring.key.description.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// A pipe the kernel writes notifications into.
+struct WatchQueue {
+    file: File,
+    /// Reads only see the end of the queue once nothing can write to it, so this is kept open.
+    _write: File,
+}
+
+impl WatchQueue {
+    fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | O_NOTIFICATION_PIPE) } != 0 {
+            let e = io::Error::last_os_error();
+
+            return Err(if e.raw_os_error() == Some(libc::ENOPKG) {
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "the kernel was built without watch queues",
+                )
+            } else {
+                e
+            });
+        }
+
+        let (file, write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+        if unsafe { libc::ioctl(file.as_raw_fd(), IOC_WATCH_QUEUE_SET_SIZE as _, QUEUE_SIZE) } != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            file,
+            _write: write,
+        })
+    }
+
+    /// Waits for notifications, which the kernel writes a whole one at a time.
+    fn read(&mut self) -> io::Result<Vec<Notification>> {
+        let mut buffer = [0u8; 4096];
+        let read = self.file.read(&mut buffer)?;
+
+        Ok(parse_notifications(&buffer[..read]))
+    }
+}
+
+/// Splits what was read from a watch queue into its notifications, stopping at anything cut short.
+fn parse_notifications(buffer: &[u8]) -> Vec<Notification> {
+    let word = |at: usize| {
+        u32::from_ne_bytes([buffer[at], buffer[at + 1], buffer[at + 2], buffer[at + 3]])
+    };
+
+    let mut notifications = Vec::new();
+    let mut at = 0;
+
+    while at + 8 <= buffer.len() {
+        let header = word(at);
+        // The bottom bits of the info word are how long the notification is.
+        let len = (word(at + 4) & 0x7f) as usize;
+
+        if len < 8 || at + len > buffer.len() {
+            break;
+        }
+
+        notifications.push(Notification {
+            kind: header & 0xff_ffff,
+            subtype: header >> 24,
+            key: if len >= 12 { word(at + 8) as i32 } else { 0 },
+            aux: if len >= 16 { word(at + 12) as i32 } else { 0 },
+        });
+
+        at += len;
+    }
+
+    notifications
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CString, time::Duration};
+
+    use super::*;
+
+    /// Only the thread that makes it can see its thread keyring, so tests can't see each other's.
+    const THREAD_KEYRING: i32 = -1;
+    const KEYCTL_UNLINK: libc::c_ulong = 9;
+
+    /// A notification as the kernel lays it out, with its length in the info word.
+    fn notification(kind: u32, subtype: u32, words: &[u32]) -> Vec<u8> {
+        let len = 8 + 4 * words.len() as u32;
+        [kind | subtype << 24, len]
+            .iter()
+            .chain(words)
+            .flat_map(|word| word.to_ne_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn parses_notifications() {
+        let mut buffer = notification(WATCH_TYPE_KEY_NOTIFY, NOTIFY_KEY_LINKED, &[12, 34]);
+        buffer.extend(notification(
+            WATCH_TYPE_KEY_NOTIFY,
+            NOTIFY_KEY_UPDATED,
+            &[56],
+        ));
+        buffer.extend(notification(
+            WATCH_TYPE_META,
+            WATCH_META_LOSS_NOTIFICATION,
+            &[],
+        ));
+
+        assert_eq!(
+            parse_notifications(&buffer),
+            [
+                Notification {
+                    kind: WATCH_TYPE_KEY_NOTIFY,
+                    subtype: NOTIFY_KEY_LINKED,
+                    key: 12,
+                    aux: 34,
+                },
+                Notification {
+                    kind: WATCH_TYPE_KEY_NOTIFY,
+                    subtype: NOTIFY_KEY_UPDATED,
+                    key: 56,
+                    aux: 0,
+                },
+                Notification {
+                    kind: WATCH_TYPE_META,
+                    subtype: WATCH_META_LOSS_NOTIFICATION,
+                    key: 0,
+                    aux: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_notifications_cut_short() {
+        let mut buffer = notification(WATCH_TYPE_KEY_NOTIFY, NOTIFY_KEY_LINKED, &[12, 34]);
+        let whole = buffer.len();
+        buffer.extend(notification(
+            WATCH_TYPE_KEY_NOTIFY,
+            NOTIFY_KEY_LINKED,
+            &[56, 78],
+        ));
+
+        assert_eq!(parse_notifications(&buffer[..whole + 12]).len(), 1);
+        assert_eq!(parse_notifications(&buffer[..whole + 4]).len(), 1);
+
+        // A length too short to hold the header can't be skipped over.
+        let mut broken = notification(WATCH_TYPE_KEY_NOTIFY, NOTIFY_KEY_LINKED, &[]);
+        broken[4..8].copy_from_slice(&4u32.to_ne_bytes());
+        broken.extend(notification(WATCH_TYPE_KEY_NOTIFY, NOTIFY_KEY_LINKED, &[]));
+        assert!(parse_notifications(&broken).is_empty());
+    }
+
+    fn add_key(key_type: &str, description: &str, payload: &[u8], keyring: i32) -> io::Result<i32> {
+        let key_type = CString::new(key_type).unwrap();
+        let description = CString::new(description).unwrap();
+
+        let id = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                key_type.as_ptr(),
+                description.as_ptr(),
+                payload.as_ptr(),
+                payload.len(),
+                keyring,
+            )
+        };
+
+        if id < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(id as i32)
+    }
+
+    /// Waits for the watcher to turn the next notifications into events.
+    fn next_events(watcher: &mut Watcher) -> Vec<EventData> {
+        let mut poll = libc::pollfd {
+            fd: watcher.queue.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = Duration::from_secs(5).as_millis() as libc::c_int;
+        assert_eq!(
+            unsafe { libc::poll(&mut poll, 1, timeout) },
+            1,
+            "the kernel didn't say anything changed"
+        );
+
+        watcher
+            .queue
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|notification| notification.kind == WATCH_TYPE_KEY_NOTIFY)
+            .flat_map(|notification| watcher.handle(notification))
+            .collect()
+    }
+
+    #[test]
+    fn reports_keys_changing() {
+        let queue = match WatchQueue::new() {
+            Ok(queue) => queue,
+            Err(e) => {
+                eprintln!("skipping, no watch queues: {e}");
+                return;
+            }
+        };
+
+        // A keyring of the test's own, so nothing else's keys are touched.
+        let keyring = match add_key("keyring", "keeper-test", &[], THREAD_KEYRING) {
+            Ok(keyring) => keyring,
+            Err(e) => {
+                eprintln!("skipping, can't make a keyring: {e}");
+                return;
+            }
+        };
+
+        let mut watcher = Watcher {
+            queue,
+            watched: HashMap::new(),
+        };
+        if let Err(e) = watcher.watch(keyring, keyring) {
+            eprintln!("skipping, keys can't be watched: {e}");
+            return;
+        }
+
+        let key = add_key("user", "keeper-test-key", b"secret", keyring).unwrap();
+        let added = next_events(&mut watcher);
+        assert_eq!(added.len(), 1);
+        let added = added.into_iter().next().unwrap().assume_filtered();
+        assert_eq!(added.kind_name(), "added");
+        assert_eq!(added.item_title(), Some("keeper-test-key"));
+        assert_eq!(added.backend(), Backend::KernelKeyring);
+
+        // Adding a key with the same description again updates it.
+        add_key("user", "keeper-test-key", b"changed", keyring).unwrap();
+        let updated = next_events(&mut watcher);
+        assert_eq!(updated.len(), 1);
+        let updated = updated.into_iter().next().unwrap().assume_filtered();
+        assert_eq!(updated.kind_name(), "updated");
+        assert_eq!(updated.item_title(), Some("keeper-test-key"));
+
+        keyring::keyctl(
+            KEYCTL_UNLINK,
+            key as libc::c_ulong,
+            keyring as libc::c_ulong,
+            0,
+        )
+        .unwrap();
+        let removed = next_events(&mut watcher);
+        assert_eq!(removed.len(), 1);
+        let removed = removed.into_iter().next().unwrap().assume_filtered();
+        assert_eq!(removed.kind_name(), "removed");
+        assert_eq!(removed.item_title(), Some("keeper-test-key"));
+    }
+}
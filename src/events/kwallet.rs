@@ -0,0 +1,284 @@
+use std::{collections::HashSet, io, sync::mpsc, thread, time::Duration};
+
+use super::{AddedOrUpdated, Backend, EventData, EventDetails, InnerDetails};
+use crate::{
+    clock::cf_now,
+    dbus::{self, Connection, Value},
+    items::{kwallet, ItemDetail},
+    process::ProcessInfo,
+};
+
+pub fn start_kwallet_monitor(sender: mpsc::Sender<EventData>) {
+    thread::Builder::new()
+        .name(String::from("KWallet Monitor"))
+        .spawn(move || {
+            let watched = dbus::session_address().and_then(|address| watch(&address));
+            if let Err(e) = watched.and_then(|connection| monitor(connection, &sender)) {
+                log::error!("lost connection to KWallet: {e}");
+            }
+        })
+        .expect("failed to start KWallet monitor");
+}
+
+/// How long a folder change waits for the entry change that usually comes right after it.
+const FOLDER_WINDOW: Duration = Duration::from_millis(500);
+
+/// A folder change that hasn't been explained by an entry change yet.
+struct PendingFolder {
+    wallet: String,
+    folder: String,
+}
+
+fn watch(address: &str) -> io::Result<Connection> {
+    let mut connection = Connection::open(address)?;
+
+    // kwalletd doesn't need to be running yet, since signals are matched by interface alone.
+    connection.add_match(&format!("type='signal',interface='{}'", kwallet::INTERFACE))?;
+
+    Ok(connection)
+}
+
+fn monitor(mut connection: Connection, sender: &mpsc::Sender<EventData>) -> io::Result<()> {
+    // Wallets that were seen opening, so they can be reported when all of them close at once.
+    let mut open_wallets: HashSet<String> = HashSet::new();
+    let mut pending: Option<PendingFolder> = None;
+
+    loop {
+        let message = match &pending {
+            Some(_) => connection.receive_timeout(FOLDER_WINDOW)?,
+            None => connection.receive().map(Some)?,
+        };
+
+        let message = match message {
+            Some(message) => message,
+            // Nothing explained the folder change, so it's all there is to report.
+            None => {
+                let folder = pending.take().expect("only waited on a pending folder");
+                if !send(
+                    sender,
+                    changed(&folder.wallet, &folder.folder, None, Vec::new()),
+                ) {
+                    return Ok(());
+                }
+                continue;
+            }
+        };
+
+        if message.interface.as_deref() != Some(kwallet::INTERFACE) {
+            continue;
+        }
+
+        let member = message.member.as_deref().unwrap_or_default();
+        let args: Vec<&str> = message.body.iter().filter_map(Value::as_str).collect();
+
+        // Every entry change comes right after a change to its folder, which says nothing new.
+        if let Some(folder) = pending.take() {
+            let explained = member.starts_with("entry")
+                && args.first() == Some(&folder.wallet.as_str())
+                && args.get(1) == Some(&folder.folder.as_str());
+
+            if !explained
+                && !send(
+                    sender,
+                    changed(&folder.wallet, &folder.folder, None, Vec::new()),
+                )
+            {
+                return Ok(());
+            }
+        }
+
+        let events = match (member, args.as_slice()) {
+            ("folderUpdated", [wallet, folder]) => {
+                pending = Some(PendingFolder {
+                    wallet: (*wallet).to_owned(),
+                    folder: (*folder).to_owned(),
+                });
+                Vec::new()
+            }
+            // KWallet doesn't say if an entry is new or only changed.
+            ("entryUpdated", [wallet, folder, key]) => {
+                vec![changed(wallet, folder, Some(key), Vec::new())]
+            }
+            ("entryRenamed", [wallet, folder, old, new]) => {
+                let renamed = ItemDetail {
+                    name: String::from("renamed from"),
+                    value: (*old).to_owned(),
+                };
+                vec![changed(wallet, folder, Some(new), vec![renamed])]
+            }
+            ("entryDeleted", [_, _, key]) => vec![EventData::RemovedOrUpdate {
+                backend: Backend::KWallet,
+                seen_at: cf_now(),
+                modified_by: ProcessInfo::unknown(0),
+                item_name: Some((*key).to_owned()),
+            }],
+            ("walletOpened", [wallet]) => {
+                open_wallets.insert((*wallet).to_owned());
+                vec![lock_changed(wallet, false)]
+            }
+            // There's also a version with only the handle, which can't be told apart from others.
+            ("walletClosed", [wallet]) => {
+                open_wallets.remove(*wallet);
+                vec![lock_changed(wallet, true)]
+            }
+            ("allWalletsClosed", []) => open_wallets
+                .drain()
+                .map(|wallet| lock_changed(&wallet, true))
+                .collect(),
+            _ => continue,
+        };
+
+        for event in events {
+            if !send(sender, event) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn send(sender: &mpsc::Sender<EventData>, event: EventData) -> bool {
+    if sender.send(event).is_err() {
+        log::warn!("event stream receiver has shutdown");
+        return false;
+    }
+
+    true
+}
+
+fn changed(wallet: &str, folder: &str, key: Option<&str>, extra: Vec<ItemDetail>) -> EventData {
+    let (class, attributes) = kwallet::to_attributes(wallet, folder, key);
+    let item = class.resolve(&attributes);
+
+    let mut details = item.details;
+    details.extend(extra);
+
+    EventData::AddOrUpdate(EventDetails {
+        details: InnerDetails::new(
+            Backend::KWallet,
+            item.title,
+            cf_now(),
+            ProcessInfo::unknown(0),
+        )
+        .with_class(class.name, item.attributes, details),
+        kind: AddedOrUpdated::Updated,
+    })
+}
+
+fn lock_changed(wallet: &str, locked: bool) -> EventData {
+    EventData::LockChanged {
+        backend: Backend::KWallet,
+        seen_at: cf_now(),
+        modified_by: ProcessInfo::unknown(0),
+        keystore: wallet.to_owned(),
+        locked,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dbus::testing, events::FilteredEventData};
+
+    /// Starts a monitor on a private bus, returning what stands in for kwalletd along with the
+    /// events. `None` means there's no bus to test with.
+    fn start() -> Option<(testing::PrivateBus, Connection, mpsc::Receiver<EventData>)> {
+        let bus = testing::PrivateBus::start()?;
+        let kwalletd = bus.serve("org.kde.kwalletd5");
+
+        let connection = watch(bus.address()).unwrap();
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || monitor(connection, &tx));
+
+        Some((bus, kwalletd, events))
+    }
+
+    fn emit(kwalletd: &mut Connection, member: &str, args: &[&str]) {
+        let signal = testing::signal(
+            "/modules/kwalletd5",
+            kwallet::INTERFACE,
+            member,
+            args.iter()
+                .map(|arg| Value::Str((*arg).to_owned()))
+                .collect(),
+        );
+        kwalletd.send(signal).unwrap();
+    }
+
+    fn next_event(events: &mpsc::Receiver<EventData>) -> FilteredEventData {
+        events
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the monitor didn't report anything")
+            .assume_filtered()
+    }
+
+    #[test]
+    fn folder_changes_explained_by_an_entry_are_only_reported_once() {
+        let Some((_bus, mut kwalletd, events)) = start() else {
+            return;
+        };
+
+        emit(&mut kwalletd, "folderUpdated", &["kdewallet", "Passwords"]);
+        emit(
+            &mut kwalletd,
+            "entryUpdated",
+            &["kdewallet", "Passwords", "github.com"],
+        );
+        emit(
+            &mut kwalletd,
+            "entryDeleted",
+            &["kdewallet", "Passwords", "github.com"],
+        );
+
+        let updated = next_event(&events);
+        assert_eq!(updated.kind_name(), "updated");
+        assert_eq!(updated.item_title(), Some("github.com"));
+        assert_eq!(updated.backend(), Backend::KWallet);
+
+        let removed = next_event(&events);
+        assert_eq!(removed.kind_name(), "removed");
+        assert_eq!(removed.item_title(), Some("github.com"));
+
+        assert!(events.recv_timeout(FOLDER_WINDOW * 2).is_err());
+    }
+
+    #[test]
+    fn unexplained_folder_changes_are_reported_after_a_while() {
+        let Some((_bus, mut kwalletd, events)) = start() else {
+            return;
+        };
+
+        emit(&mut kwalletd, "folderUpdated", &["kdewallet", "Form Data"]);
+
+        let updated = next_event(&events);
+        assert_eq!(updated.kind_name(), "updated");
+        assert_eq!(updated.item_title(), Some("Form Data"));
+    }
+
+    #[test]
+    fn reports_wallets_opening_and_closing() {
+        let Some((_bus, mut kwalletd, events)) = start() else {
+            return;
+        };
+
+        emit(&mut kwalletd, "walletOpened", &["kdewallet"]);
+        emit(&mut kwalletd, "walletOpened", &["work"]);
+        emit(&mut kwalletd, "walletClosed", &["work"]);
+        emit(&mut kwalletd, "allWalletsClosed", &[]);
+
+        let kinds: Vec<_> = (0..4)
+            .map(|_| {
+                let event = next_event(&events);
+                format!("{} {}", event.kind_name(), event.item_title().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "unlocked kdewallet",
+                "unlocked work",
+                "locked work",
+                "locked kdewallet"
+            ]
+        );
+    }
+}
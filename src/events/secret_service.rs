@@ -0,0 +1,212 @@
+use std::{collections::HashMap, io, sync::mpsc, thread};
+
+use super::{AddedOrUpdated, Backend, EventData, EventDetails, InnerDetails};
+use crate::{
+    clock::cf_now,
+    dbus::{self, Connection, Message, Value},
+    items::secret_service::{self, COLLECTION_INTERFACE},
+};
+
+mod attribution;
+use attribution::Attributor;
+
+pub fn start_secret_service_monitor(sender: mpsc::Sender<EventData>) {
+    thread::Builder::new()
+        .name(String::from("Secret Service Monitor"))
+        .spawn(move || {
+            if let Err(e) = dbus::session_address().and_then(|address| monitor(&sender, &address)) {
+                log::error!("lost connection to the Secret Service: {e}");
+            }
+        })
+        .expect("failed to start Secret Service monitor");
+}
+
+fn monitor(sender: &mpsc::Sender<EventData>, address: &str) -> io::Result<()> {
+    let mut connection = Connection::open(address)?;
+
+    // Signals are read from the monitor when there is one, so they stay in order with the calls.
+    let mut watcher = match watch_calls(address) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            log::warn!("can't monitor the session bus, changers will be unknown: {e}");
+
+            connection.add_match(&format!(
+                "type='signal',sender='{}',interface='{COLLECTION_INTERFACE}'",
+                secret_service::SERVICE_NAME
+            ))?;
+
+            None
+        }
+    };
+    let mut attributor = Attributor::default();
+
+    // Removal signals only say which object went away, so every item's title is kept around
+    // to be able to say what it was.
+    let mut titles: HashMap<String, String> = HashMap::new();
+    for path in secret_service::all_items(&mut connection)? {
+        if let Ok((class, attributes)) = secret_service::read_item(&mut connection, &path) {
+            titles.insert(path, class.resolve(&attributes).title);
+        }
+    }
+
+    log::debug!("watching {} Secret Service items", titles.len());
+
+    loop {
+        let message = match &mut watcher {
+            Some(watcher) => watcher.receive()?,
+            None => connection.receive()?,
+        };
+
+        if attributor.observe(&message, &mut connection) {
+            continue;
+        }
+
+        let kind = if message.is_signal(COLLECTION_INTERFACE, "ItemCreated") {
+            Some(AddedOrUpdated::Added)
+        } else if message.is_signal(COLLECTION_INTERFACE, "ItemChanged") {
+            Some(AddedOrUpdated::Updated)
+        } else if message.is_signal(COLLECTION_INTERFACE, "ItemDeleted") {
+            None
+        } else {
+            continue;
+        };
+
+        let path = match item_path(&message) {
+            Some(path) => path,
+            None => {
+                log::warn!("received unusable Secret Service signal with no item");
+                continue;
+            }
+        };
+
+        let changer = attributor.changer(matches!(kind, Some(AddedOrUpdated::Added)), &path);
+
+        let event = match kind {
+            Some(kind) => {
+                let (class, attributes) = match secret_service::read_item(&mut connection, &path) {
+                    Ok(item) => item,
+                    Err(e) => {
+                        log::warn!("failed to read changed item {path}: {e}");
+                        continue;
+                    }
+                };
+
+                let item = class.resolve(&attributes);
+                titles.insert(path, item.title.clone());
+
+                EventData::AddOrUpdate(EventDetails {
+                    details: InnerDetails::new(
+                        Backend::SecretService,
+                        item.title,
+                        item.modified_at.unwrap_or_else(cf_now),
+                        changer,
+                    )
+                    .with_class(class.name, item.attributes, item.details),
+                    kind,
+                })
+            }
+            None => EventData::RemovedOrUpdate {
+                backend: Backend::SecretService,
+                seen_at: cf_now(),
+                modified_by: changer,
+                item_name: titles.remove(&path),
+            },
+        };
+
+        if sender.send(event).is_err() {
+            log::warn!("event stream receiver has shutdown");
+            return Ok(());
+        }
+    }
+}
+
+fn watch_calls(address: &str) -> io::Result<Connection> {
+    let mut watcher = Connection::open(address)?;
+    watcher.become_monitor(&Attributor::rules())?;
+    Ok(watcher)
+}
+
+fn item_path(message: &Message) -> Option<String> {
+    match message.body.first()? {
+        Value::ObjectPath(path) => Some(path.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        dbus::testing,
+        items::secret_service::{
+            testing::{mock_service, COLLECTION},
+            ITEM_INTERFACE, SERVICE_NAME,
+        },
+    };
+
+    #[test]
+    fn reports_changes_with_titles_and_changers() {
+        let Some(bus) = testing::PrivateBus::start() else {
+            return;
+        };
+
+        let service = bus.serve(SERVICE_NAME);
+        let existing = vec![(format!("{COLLECTION}/1"), String::from("github.com"))];
+        let (calls_tx, calls) = mpsc::channel();
+        thread::spawn(move || mock_service(service, existing, calls_tx));
+
+        let (tx, events) = mpsc::channel();
+        let address = bus.address().to_owned();
+        thread::spawn(move || monitor(&tx, &address));
+
+        // Changes made before the monitor has read the existing titles couldn't be named.
+        while calls.recv_timeout(Duration::from_secs(5)).unwrap() != "GetAll" {}
+
+        let mut client = bus.connect();
+        let next_event = || {
+            events
+                .recv_timeout(Duration::from_secs(5))
+                .expect("the monitor didn't report anything")
+                .assume_filtered()
+        };
+
+        client
+            .call(
+                SERVICE_NAME,
+                COLLECTION,
+                COLLECTION_INTERFACE,
+                "CreateItem",
+                vec![Value::dict(
+                    "s",
+                    "v",
+                    vec![(
+                        Value::Str(String::from("org.freedesktop.Secret.Item.Label")),
+                        Value::variant(Value::Str(String::from("example.com"))),
+                    )],
+                )],
+            )
+            .unwrap();
+
+        let added = next_event();
+        assert_eq!(added.kind_name(), "added");
+        assert_eq!(added.item_title(), Some("example.com"));
+        assert_eq!(added.backend(), Backend::SecretService);
+        assert_eq!(added.changer().pid, std::process::id() as i32);
+
+        for (item, title) in [
+            (format!("{COLLECTION}/1"), "github.com"),
+            (format!("{COLLECTION}/new1"), "example.com"),
+        ] {
+            client
+                .call(SERVICE_NAME, &item, ITEM_INTERFACE, "Delete", Vec::new())
+                .unwrap();
+
+            let removed = next_event();
+            assert_eq!(removed.kind_name(), "removed");
+            assert_eq!(removed.item_title(), Some(title));
+            assert_eq!(removed.changer().pid, std::process::id() as i32);
+        }
+    }
+}
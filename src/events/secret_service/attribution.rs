@@ -0,0 +1,151 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    dbus::{Connection, Message, MessageType, PROPERTIES_INTERFACE},
+    items::secret_service::{COLLECTION_INTERFACE, ITEM_INTERFACE, SERVICE_NAME},
+    process::{ProcessCache, ProcessInfo},
+};
+
+/// How long after a call its signal can still be blamed on the caller. Calls that need the user
+/// to unlock something first only take effect once they do, so this is fairly generous.
+const ATTRIBUTION_WINDOW: Duration = Duration::from_secs(30);
+
+/// The calls that change items. Labels and attributes are changed by setting properties, which is
+/// what `SetAttributes` in other keystores amounts to here.
+const CHANGING_CALLS: &[(&str, &str)] = &[
+    (COLLECTION_INTERFACE, "CreateItem"),
+    (ITEM_INTERFACE, "Delete"),
+    (ITEM_INTERFACE, "SetSecret"),
+    (PROPERTIES_INTERFACE, "Set"),
+];
+
+/// Works out who changed an item by watching the calls that lead up to each signal.
+///
+/// The signals themselves come from the service, so they say nothing about which client asked
+/// for the change. A monitor sees the calls and the signals in the order the bus routed them, so
+/// every signal can be joined to the last call that was made about the same item.
+#[derive(Default)]
+pub struct Attributor {
+    /// Pids of connections that were already asked about. Unique names are never reused.
+    pids: HashMap<String, Option<i32>>,
+    processes: ProcessCache,
+    /// Who last called something on each item.
+    by_item: HashMap<String, (Instant, ProcessInfo)>,
+    /// Who asked for new items, oldest first. New items don't have a path until they exist,
+    /// and clients usually create them through an alias of the collection anyway.
+    creators: VecDeque<(Instant, ProcessInfo)>,
+}
+
+impl Attributor {
+    /// How many connections' pids are remembered before starting over.
+    const CAPACITY: usize = 256;
+
+    /// The rules a monitor needs to see both the changes and the calls that caused them.
+    pub fn rules() -> Vec<String> {
+        let mut rules: Vec<String> = CHANGING_CALLS
+            .iter()
+            .map(|(interface, member)| {
+                let rule = format!("type='method_call',interface='{interface}',member='{member}'");
+
+                // Every service uses the properties interface, so only item properties count.
+                if *interface == PROPERTIES_INTERFACE {
+                    format!("{rule},arg0='{ITEM_INTERFACE}'")
+                } else {
+                    rule
+                }
+            })
+            .collect();
+
+        rules.push(format!(
+            "type='signal',sender='{SERVICE_NAME}',interface='{COLLECTION_INTERFACE}'"
+        ));
+
+        rules
+    }
+
+    /// Remembers who made `message`, if it's one of the calls that change items.
+    ///
+    /// The caller is looked up right away, since it's likely to be gone by the time the service
+    /// gets around to saying what changed.
+    pub fn observe(&mut self, message: &Message, bus: &mut Connection) -> bool {
+        if message.kind != MessageType::MethodCall {
+            return false;
+        }
+
+        let is_changing = CHANGING_CALLS.iter().any(|(interface, member)| {
+            message.interface.as_deref() == Some(interface)
+                && message.member.as_deref() == Some(member)
+        });
+
+        if !is_changing {
+            return false;
+        }
+
+        let (path, sender) = match (&message.path, &message.sender) {
+            (Some(path), Some(sender)) => (path, sender),
+            _ => return true,
+        };
+
+        let caller = match self.pid_of(sender, bus) {
+            Some(pid) => self.processes.lookup(pid),
+            None => ProcessInfo::unknown(0),
+        };
+
+        let now = Instant::now();
+        self.by_item
+            .retain(|_, (at, _)| now.duration_since(*at) < ATTRIBUTION_WINDOW);
+        while self
+            .creators
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= ATTRIBUTION_WINDOW)
+        {
+            self.creators.pop_front();
+        }
+
+        if message.member.as_deref() == Some("CreateItem") {
+            self.creators.push_back((now, caller));
+        } else {
+            self.by_item.insert(path.clone(), (now, caller));
+        }
+
+        true
+    }
+
+    fn pid_of(&mut self, sender: &str, bus: &mut Connection) -> Option<i32> {
+        if let Some(pid) = self.pids.get(sender) {
+            return *pid;
+        }
+
+        let pid = match bus.connection_pid(sender) {
+            Ok(pid) => i32::try_from(pid).ok(),
+            Err(e) => {
+                log::debug!("couldn't find the process behind {sender}: {e}");
+                None
+            }
+        };
+
+        if self.pids.len() >= Self::CAPACITY {
+            self.pids.clear();
+        }
+
+        self.pids.insert(sender.to_owned(), pid);
+        pid
+    }
+
+    /// Finds who caused a signal about `item`, or an unknown changer if nobody can be blamed.
+    pub fn changer(&mut self, created: bool, item: &str) -> ProcessInfo {
+        let found = if created {
+            self.creators.pop_front()
+        } else {
+            self.by_item.remove(item)
+        };
+
+        match found {
+            Some((at, caller)) if at.elapsed() < ATTRIBUTION_WINDOW => caller,
+            _ => ProcessInfo::unknown(0),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::{collections::HashMap, hash::Hash};
+
+use super::{AddedOrUpdated, Backend, EventData, EventDetails, InnerDetails};
+use crate::{
+    clock::cf_now,
+    items::{ClassSpec, ResolvedItem},
+    process::ProcessInfo,
+};
+
+/// An item out of a keystore that can only be read whole, like a file, so changes to it are
+/// found by comparing two reads.
+pub trait SnapshotItem {
+    /// The item's class, and the item in the common model.
+    fn resolve(&self) -> (&'static ClassSpec, ResolvedItem);
+
+    /// If the item changed since `previous`, which was read with the same key.
+    fn differs_from(&self, previous: &Self) -> bool;
+}
+
+/// What changed between two reads of a keystore, with items keyed by what tells them apart.
+pub fn changes<K: Eq + Hash, T: SnapshotItem>(
+    backend: Backend,
+    old: &HashMap<K, T>,
+    new: &HashMap<K, T>,
+    changer: &ProcessInfo,
+) -> Vec<EventData> {
+    let mut events = Vec::new();
+
+    for (key, item) in new {
+        let kind = match old.get(key) {
+            None => AddedOrUpdated::Added,
+            Some(previous) if item.differs_from(previous) => AddedOrUpdated::Updated,
+            Some(_) => continue,
+        };
+
+        let (class, item) = item.resolve();
+
+        events.push(EventData::AddOrUpdate(EventDetails {
+            details: InnerDetails::new(
+                backend,
+                item.title,
+                item.modified_at.unwrap_or_else(cf_now),
+                changer.clone(),
+            )
+            .with_class(class.name, item.attributes, item.details),
+            kind,
+        }));
+    }
+
+    for (key, item) in old {
+        if !new.contains_key(key) {
+            events.push(EventData::RemovedOrUpdate {
+                backend,
+                seen_at: cf_now(),
+                modified_by: changer.clone(),
+                item_name: Some(item.resolve().1.title),
+            });
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::{attr, spec_named, AttributeValue, Attributes};
+
+    /// A titled item, and a version that goes up when it changes.
+    struct Versioned(&'static str, u32);
+
+    impl SnapshotItem for Versioned {
+        fn resolve(&self) -> (&'static ClassSpec, ResolvedItem) {
+            let class = spec_named("generic_password").unwrap();
+            let attributes = Attributes::from([(
+                attr::LABEL.to_owned(),
+                AttributeValue::Text(self.0.to_owned()),
+            )]);
+
+            (class, class.resolve(&attributes))
+        }
+
+        fn differs_from(&self, previous: &Self) -> bool {
+            self.1 != previous.1
+        }
+    }
+
+    fn read(items: &[(&'static str, u32)]) -> HashMap<&'static str, Versioned> {
+        items
+            .iter()
+            .map(|(title, version)| (*title, Versioned(title, *version)))
+            .collect()
+    }
+
+    #[test]
+    fn reports_added_updated_and_removed_items() {
+        let old = read(&[("a", 1), ("b", 1), ("c", 1)]);
+        let new = read(&[("b", 2), ("c", 1), ("d", 1)]);
+
+        let mut events: Vec<(&str, Option<String>)> =
+            changes(Backend::TrustStore, &old, &new, &ProcessInfo::unknown(0))
+                .into_iter()
+                .map(|event| {
+                    let event = event.assume_filtered();
+                    assert_eq!(event.backend(), Backend::TrustStore);
+                    (event.kind_name(), event.item_title().map(str::to_owned))
+                })
+                .collect();
+        events.sort();
+
+        assert_eq!(
+            events,
+            [
+                ("added", Some(String::from("d"))),
+                ("removed", Some(String::from("a"))),
+                ("updated", Some(String::from("b"))),
+            ]
+        );
+        assert!(changes(Backend::TrustStore, &new, &new, &ProcessInfo::unknown(0)).is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use crate::{baseline::wildcard_match, process::ProcessInfo};
+
+#[cfg(target_os = "linux")]
+mod proxy;
+#[cfg(target_os = "linux")]
+pub use proxy::start_ssh_agent_proxy;
+
+/// Sitting between SSH clients and the agent, to see every key that's used, added or removed.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct SshAgentConfig {
+    pub(crate) enabled: bool,
+    /// Where clients connect instead of the agent. Defaults to `keeper_of_keys/ssh-agent` in the
+    /// runtime directory.
+    pub(crate) socket: Option<PathBuf>,
+    /// The agent everything is passed on to. Defaults to `SSH_AUTH_SOCK`.
+    pub(crate) upstream: Option<PathBuf>,
+    /// What happens when an executable that isn't approved asks for a signature.
+    pub(crate) unapproved: SigningPolicy,
+    /// Paths of executables that may use keys, where `*` matches any run of characters.
+    pub(crate) approved: Vec<String>,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SigningPolicy {
+    /// The signature goes ahead, and is only reported.
+    #[default]
+    Allow,
+    /// Someone has to say yes to the signature through `ssh-askpass` first, like with keys that
+    /// were added with `ssh-add -c`.
+    Confirm,
+    /// The signature is refused.
+    Refuse,
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+impl SshAgentConfig {
+    /// Decides what happens with a signature `signer` asked for. Signers that couldn't be looked
+    /// at are never approved.
+    pub fn policy_for(&self, signer: &ProcessInfo) -> SigningPolicy {
+        let approved = signer
+            .path
+            .as_deref()
+            .and_then(|path| path.to_str())
+            .is_some_and(|path| {
+                self.approved
+                    .iter()
+                    .any(|pattern| wildcard_match(pattern, path))
+            });
+
+        if approved {
+            SigningPolicy::Allow
+        } else {
+            self.unapproved
+        }
+    }
+}
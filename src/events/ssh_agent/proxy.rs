@@ -0,0 +1,512 @@
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{mpsc, Arc, Mutex},
+};
+
+use super::{SigningPolicy, SshAgentConfig};
+use crate::{
+    access,
+    clock::cf_now,
+    events::{AddedOrUpdated, Backend, EventData, EventDetails, InnerDetails},
+    items::{ssh_agent, ItemDetail},
+    process::{ProcessCache, ProcessInfo},
+};
+
+// Message numbers from the agent protocol.
+const FAILURE: u8 = 5;
+const SUCCESS: u8 = 6;
+const REQUEST_IDENTITIES: u8 = 11;
+const IDENTITIES_ANSWER: u8 = 12;
+const SIGN_REQUEST: u8 = 13;
+const SIGN_RESPONSE: u8 = 14;
+const ADD_IDENTITY: u8 = 17;
+const REMOVE_IDENTITY: u8 = 18;
+const REMOVE_ALL_IDENTITIES: u8 = 19;
+const ADD_SMARTCARD_KEY: u8 = 20;
+const REMOVE_SMARTCARD_KEY: u8 = 21;
+const LOCK: u8 = 22;
+const UNLOCK: u8 = 23;
+const ADD_ID_CONSTRAINED: u8 = 25;
+const ADD_SMARTCARD_KEY_CONSTRAINED: u8 = 26;
+
+/// The longest message `ssh-agent` accepts.
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+/// What the agent is called in lock events.
+const KEYSTORE: &str = "SSH agent";
+
+/// Public keys, and their comments.
+type Keys = HashMap<Vec<u8>, String>;
+
+/// What every client connection shares.
+struct Shared {
+    config: SshAgentConfig,
+    upstream: PathBuf,
+    processes: Mutex<ProcessCache>,
+    /// What the agent held when it was last asked.
+    keys: Mutex<Keys>,
+    events: mpsc::Sender<EventData>,
+}
+
+/// Starts passing SSH agent requests through a socket of its own, reporting every signature as
+/// an `Accessed` event and keys coming and going as they're added or removed.
+///
+/// Clients only go through the proxy if they're pointed at it, so it's most useful with
+/// `SSH_AUTH_SOCK` set for the whole session.
+pub fn start_ssh_agent_proxy(
+    config: &SshAgentConfig,
+    events: mpsc::Sender<EventData>,
+) -> io::Result<()> {
+    let socket = access::proxy_socket(config.socket.as_deref(), "ssh-agent")?;
+
+    let upstream = config
+        .upstream
+        .clone()
+        .or_else(|| env::var_os("SSH_AUTH_SOCK").map(PathBuf::from))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "there's no agent to pass requests on to, set `upstream` to its socket",
+            )
+        })?;
+
+    if upstream == socket {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the proxy would pass everything on to itself, set `upstream` to the real agent",
+        ));
+    }
+
+    let keys = list_keys(&upstream)?;
+
+    let keys_held = keys.len();
+    let shared = Arc::new(Shared {
+        config: config.clone(),
+        upstream,
+        processes: Mutex::new(ProcessCache::default()),
+        keys: Mutex::new(keys),
+        events,
+    });
+
+    access::serve_proxy(&socket, "SSH Agent", move |client, peer| {
+        serve(client, peer.pid, &shared)
+    })?;
+
+    log::info!(
+        "proxying the SSH agent holding {keys_held} keys at {}",
+        socket.display()
+    );
+
+    Ok(())
+}
+
+/// Passes a client's requests on one at a time, since the agent answers each before the next.
+fn serve(mut client: UnixStream, pid: i32, shared: &Shared) -> io::Result<()> {
+    let mut agent = UnixStream::connect(&shared.upstream)?;
+
+    // Most clients only list keys, so they're only looked up once they do anything else.
+    let mut looked_up = None;
+
+    while let Some(request) = read_message(&mut client)? {
+        let kind = request.first().copied().unwrap_or_default();
+        let mut caller = || {
+            looked_up
+                .get_or_insert_with(|| shared.processes.lock().unwrap().lookup(pid))
+                .clone()
+        };
+
+        let reply = if kind == SIGN_REQUEST && !may_sign(&request, &caller(), shared) {
+            vec![FAILURE]
+        } else {
+            write_message(&mut agent, &request)?;
+            read_message(&mut agent)?.ok_or(io::ErrorKind::UnexpectedEof)?
+        };
+
+        let succeeded = reply.first() == Some(&SUCCESS);
+
+        match kind {
+            SIGN_REQUEST if reply.first() == Some(&SIGN_RESPONSE) => {
+                report_signature(&request, caller(), shared);
+            }
+            ADD_IDENTITY
+            | ADD_ID_CONSTRAINED
+            | REMOVE_IDENTITY
+            | REMOVE_ALL_IDENTITIES
+            | ADD_SMARTCARD_KEY
+            | ADD_SMARTCARD_KEY_CONSTRAINED
+            | REMOVE_SMARTCARD_KEY
+                if succeeded =>
+            {
+                report_key_changes(caller(), shared);
+            }
+            LOCK | UNLOCK if succeeded => {
+                let _ = shared.events.send(EventData::LockChanged {
+                    backend: Backend::SshAgent,
+                    seen_at: cf_now(),
+                    modified_by: caller(),
+                    keystore: KEYSTORE.to_owned(),
+                    locked: kind == LOCK,
+                });
+            }
+            _ => {}
+        }
+
+        write_message(&mut client, &reply)?;
+    }
+
+    Ok(())
+}
+
+/// Checks if a signature can go ahead, asking for confirmation if the policy says to.
+fn may_sign(request: &[u8], signer: &ProcessInfo, shared: &Shared) -> bool {
+    let name = signer.display_name().unwrap_or("Unknown");
+
+    let allowed = match shared.config.policy_for(signer) {
+        SigningPolicy::Allow => return true,
+        SigningPolicy::Confirm => {
+            let key = key_title(request, shared);
+            confirm(&format!(
+                "Allow {name} [{}] to sign with {key}?",
+                signer.pid
+            ))
+        }
+        SigningPolicy::Refuse => false,
+    };
+
+    if !allowed {
+        log::warn!("refused {name} [{}] asking for a signature", signer.pid);
+    }
+
+    allowed
+}
+
+/// Asks whoever is at the screen to say yes, the same way `ssh-agent` does for keys that need
+/// confirmation. Nobody being there to ask counts as a no.
+fn confirm(prompt: &str) -> bool {
+    let askpass = env::var_os("SSH_ASKPASS").unwrap_or_else(|| "ssh-askpass".into());
+
+    match Command::new(&askpass)
+        .arg(prompt)
+        .env("SSH_ASKPASS_PROMPT", "confirm")
+        .status()
+    {
+        Ok(status) => status.success(),
+        Err(e) => {
+            log::warn!("couldn't ask for confirmation with {askpass:?}: {e}");
+            false
+        }
+    }
+}
+
+fn report_signature(request: &[u8], signer: ProcessInfo, shared: &Shared) {
+    let public_key = match signed_key(request) {
+        Some(public_key) => public_key,
+        None => return,
+    };
+
+    let comment = shared
+        .keys
+        .lock()
+        .unwrap()
+        .get(public_key)
+        .cloned()
+        .unwrap_or_default();
+
+    let name = signer.display_name().unwrap_or("Unknown");
+    let fingerprint = ssh_agent::fingerprint(public_key);
+    log::info!("{name} [{}] signed with {fingerprint}", signer.pid);
+
+    let details = key_details(public_key, &comment, signer);
+    let _ = shared.events.send(EventData::Accessed(details));
+}
+
+/// Asks the agent what it holds now, and reports whatever came or went since it was last asked.
+///
+/// Keys added or removed by going to the agent directly are only noticed here, so they get put
+/// down to whoever made the next change through the proxy.
+fn report_key_changes(changer: ProcessInfo, shared: &Shared) {
+    let keys = match list_keys(&shared.upstream) {
+        Ok(keys) => keys,
+        Err(e) => {
+            log::warn!("couldn't list the SSH agent's keys: {e}");
+            return;
+        }
+    };
+
+    let old = std::mem::replace(&mut *shared.keys.lock().unwrap(), keys.clone());
+
+    for (public_key, comment) in &keys {
+        if !old.contains_key(public_key) {
+            let _ = shared.events.send(EventData::AddOrUpdate(EventDetails {
+                details: key_details(public_key, comment, changer.clone()),
+                kind: AddedOrUpdated::Added,
+            }));
+        }
+    }
+
+    for (public_key, comment) in &old {
+        if !keys.contains_key(public_key) {
+            let (class, attributes) = ssh_agent::to_attributes(public_key, comment);
+
+            let _ = shared.events.send(EventData::RemovedOrUpdate {
+                backend: Backend::SshAgent,
+                seen_at: cf_now(),
+                modified_by: changer.clone(),
+                item_name: Some(class.resolve(&attributes).title),
+            });
+        }
+    }
+}
+
+fn key_details(public_key: &[u8], comment: &str, process: ProcessInfo) -> InnerDetails {
+    let (class, attributes) = ssh_agent::to_attributes(public_key, comment);
+    let mut item = class.resolve(&attributes);
+
+    item.details.push(ItemDetail {
+        name: String::from("fingerprint"),
+        value: ssh_agent::fingerprint(public_key),
+    });
+
+    InnerDetails::new(Backend::SshAgent, item.title, cf_now(), process).with_class(
+        class.name,
+        item.attributes,
+        item.details,
+    )
+}
+
+/// What a signature request's key is called, for asking about it.
+fn key_title(request: &[u8], shared: &Shared) -> String {
+    let public_key = signed_key(request).unwrap_or_default();
+
+    match shared.keys.lock().unwrap().get(public_key) {
+        Some(comment) if !comment.is_empty() => comment.clone(),
+        _ => ssh_agent::fingerprint(public_key),
+    }
+}
+
+/// The public key of the key a signature was asked for with.
+fn signed_key(request: &[u8]) -> Option<&[u8]> {
+    Some(ssh_agent::read_string(request.get(1..)?)?.0)
+}
+
+/// Asks the agent for every key it holds, on a connection of its own.
+fn list_keys(agent: &Path) -> io::Result<Keys> {
+    let mut agent = UnixStream::connect(agent)?;
+    write_message(&mut agent, &[REQUEST_IDENTITIES])?;
+
+    let reply = read_message(&mut agent)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "the agent listed keys wrongly");
+
+    let mut rest = match reply.split_first() {
+        Some((&IDENTITIES_ANSWER, rest)) => rest.get(4..).ok_or_else(invalid)?,
+        _ => return Err(invalid()),
+    };
+
+    let mut keys = Keys::new();
+    while !rest.is_empty() {
+        let (public_key, after) = ssh_agent::read_string(rest).ok_or_else(invalid)?;
+        let (comment, after) = ssh_agent::read_string(after).ok_or_else(invalid)?;
+
+        keys.insert(
+            public_key.to_vec(),
+            String::from_utf8_lossy(comment).into_owned(),
+        );
+        rest = after;
+    }
+
+    Ok(keys)
+}
+
+/// Reads one message, or nothing if the other end is done.
+fn read_message(stream: &mut UnixStream) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("refused a {len} byte message"),
+        ));
+    }
+
+    let mut message = vec![0u8; len];
+    stream.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+fn write_message(stream: &mut UnixStream, message: &[u8]) -> io::Result<()> {
+    let mut framed = Vec::with_capacity(4 + message.len());
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(message);
+
+    stream.write_all(&framed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        process::{Child, Stdio},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    /// An `ssh-agent` of its own with a key ready to be added to it, which is stopped and cleaned
+    /// up when dropped.
+    struct TestAgent {
+        agent: Child,
+        dir: PathBuf,
+    }
+
+    impl TestAgent {
+        fn start(name: &str) -> Option<Self> {
+            let dir = std::env::temp_dir().join(format!("keeper-{name}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+
+            let agent = Command::new("ssh-agent")
+                .arg("-D")
+                .arg("-a")
+                .arg(dir.join("agent"))
+                .stdout(Stdio::null())
+                .spawn();
+
+            let agent = match agent {
+                Ok(agent) => TestAgent { agent, dir },
+                Err(e) => {
+                    eprintln!("skipping, ssh-agent can't be started: {e}");
+                    let _ = fs::remove_dir_all(&dir);
+                    return None;
+                }
+            };
+
+            let generated = Command::new("ssh-keygen")
+                .args(["-q", "-t", "ed25519", "-N", "", "-C", "test@keeper", "-f"])
+                .arg(agent.dir.join("key"))
+                .status();
+            if !generated.is_ok_and(|status| status.success()) {
+                eprintln!("skipping, ssh-keygen can't make a key");
+                return None;
+            }
+
+            for _ in 0..100 {
+                if agent.dir.join("agent").exists() {
+                    return Some(agent);
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+
+            panic!("ssh-agent never started listening");
+        }
+
+        fn proxy(&self, unapproved: SigningPolicy) -> (PathBuf, mpsc::Receiver<EventData>) {
+            let socket = self.dir.join(format!("proxy-{unapproved:?}"));
+            let config = SshAgentConfig {
+                enabled: true,
+                socket: Some(socket.clone()),
+                upstream: Some(self.dir.join("agent")),
+                unapproved,
+                approved: Vec::new(),
+            };
+
+            let (tx, events) = mpsc::channel();
+            start_ssh_agent_proxy(&config, tx).unwrap();
+            (socket, events)
+        }
+
+        /// Signs some text with the test key, through `socket`.
+        fn sign(&self, socket: &Path) -> bool {
+            let mut signer = Command::new("ssh-keygen")
+                .args(["-Y", "sign", "-n", "file", "-f"])
+                .arg(self.dir.join("key.pub"))
+                .env("SSH_AUTH_SOCK", socket)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+
+            signer.stdin.take().unwrap().write_all(b"sign me").unwrap();
+            signer.wait().unwrap().success()
+        }
+    }
+
+    impl Drop for TestAgent {
+        fn drop(&mut self) {
+            let _ = self.agent.kill();
+            let _ = self.agent.wait();
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn next_event(events: &mpsc::Receiver<EventData>) -> crate::events::FilteredEventData {
+        events
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the proxy didn't report anything")
+            .assume_filtered()
+    }
+
+    #[test]
+    fn reports_keys_and_signatures() {
+        let Some(agent) = TestAgent::start("ssh-reports") else {
+            return;
+        };
+        let (socket, events) = agent.proxy(SigningPolicy::Allow);
+
+        let added = Command::new("ssh-add")
+            .arg("-q")
+            .arg(agent.dir.join("key"))
+            .env("SSH_AUTH_SOCK", &socket)
+            .status()
+            .unwrap();
+        assert!(added.success());
+
+        let added = next_event(&events);
+        assert_eq!(added.kind_name(), "added");
+        assert_eq!(added.item_title(), Some("test@keeper"));
+        assert_eq!(added.backend(), Backend::SshAgent);
+
+        assert!(agent.sign(&socket));
+
+        let signed = next_event(&events);
+        assert_eq!(signed.kind_name(), "accessed");
+        assert_eq!(signed.item_title(), Some("test@keeper"));
+        assert!(signed
+            .changer()
+            .path
+            .as_deref()
+            .is_some_and(|path| path.ends_with("ssh-keygen")));
+    }
+
+    #[test]
+    fn refuses_unapproved_signatures() {
+        let Some(agent) = TestAgent::start("ssh-refuses") else {
+            return;
+        };
+
+        let added = Command::new("ssh-add")
+            .arg("-q")
+            .arg(agent.dir.join("key"))
+            .env("SSH_AUTH_SOCK", agent.dir.join("agent"))
+            .status()
+            .unwrap();
+        assert!(added.success());
+
+        let (socket, events) = agent.proxy(SigningPolicy::Refuse);
+
+        assert!(!agent.sign(&socket));
+        assert!(agent.sign(&agent.dir.join("agent")));
+        assert!(events.try_recv().is_err());
+    }
+}
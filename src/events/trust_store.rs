@@ -0,0 +1,177 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+#[cfg(target_os = "linux")]
+use std::{io, thread, time::Duration};
+
+#[cfg(target_os = "linux")]
+use super::credential_files::Watcher;
+use super::{
+    snapshot::{self, SnapshotItem},
+    Backend, EventData,
+};
+use crate::items::{
+    attr, spec_named, x509, AttributeValue, Attributes, ClassSpec, ItemDetail, ResolvedItem,
+    CLASSES,
+};
+#[cfg(target_os = "linux")]
+use crate::process::ProcessInfo;
+
+#[cfg(target_os = "linux")]
+mod certificate_directories;
+#[cfg(target_os = "linux")]
+mod nss;
+#[cfg(target_os = "macos")]
+mod trust_settings;
+
+/// How long to wait after a change for the rest of it to land, since tools like
+/// `update-ca-certificates` rewrite every file.
+#[cfg(target_os = "linux")]
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// Watching the certificates the system trusts, since a new root certificate lets whoever
+/// holds its key impersonate any site.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub(crate) struct TrustStoreConfig {
+    pub(crate) enabled: bool,
+    /// More directories of trusted certificates to watch on Linux, besides the ones
+    /// distributions keep them in.
+    pub(crate) directories: Vec<PathBuf>,
+    /// More NSS databases to watch on Linux, relative to the home directory unless they're
+    /// absolute. The shared one and Firefox's are always watched.
+    pub(crate) nss_databases: Vec<PathBuf>,
+}
+
+/// Trusted certificates, by the store they're in and their SHA-256.
+type Anchors = HashMap<(String, [u8; 32]), Anchor>;
+
+/// A trusted certificate, as it was when the store was last looked at.
+struct Anchor {
+    attributes: Attributes,
+    /// What the store trusts the certificate for, if it says. Stores that only hold trusted
+    /// certificates don't.
+    trust: Option<String>,
+}
+
+impl Anchor {
+    /// Reads a DER encoded certificate into the anchor it is in `store`. Certificates without a
+    /// common name go by `fallback_name`.
+    fn read(
+        store: &str,
+        der: &[u8],
+        fallback_name: &str,
+        trust: Option<String>,
+    ) -> Option<((String, [u8; 32]), Self)> {
+        let mut attributes = x509::to_attributes(der)?;
+
+        attributes
+            .entry(attr::LABEL.to_owned())
+            .or_insert_with(|| AttributeValue::Text(fallback_name.to_owned()));
+        attributes.insert(
+            attr::ACCESS_GROUP.to_owned(),
+            AttributeValue::Text(store.to_owned()),
+        );
+
+        let fingerprint = Sha256::digest(der).into();
+        Some(((store.to_owned(), fingerprint), Self { attributes, trust }))
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn start_trust_store_monitor(
+    _config: &TrustStoreConfig,
+    _home: &Path,
+    sender: mpsc::Sender<EventData>,
+) {
+    trust_settings::start_trust_settings_monitor(sender);
+}
+
+#[cfg(target_os = "linux")]
+pub fn start_trust_store_monitor(
+    config: &TrustStoreConfig,
+    home: &Path,
+    sender: mpsc::Sender<EventData>,
+) {
+    let directories = certificate_directories::resolve(config);
+    let databases = nss::resolve(config, home);
+
+    thread::Builder::new()
+        .name(String::from("Trust Store Watcher"))
+        .spawn(move || {
+            if let Err(e) = watch(&directories, &databases, &sender) {
+                log::error!("stopped watching the trust store: {e}");
+            }
+        })
+        .expect("failed to start trust store watcher");
+}
+
+#[cfg(target_os = "linux")]
+fn watch(
+    directories: &[PathBuf],
+    databases: &[PathBuf],
+    sender: &mpsc::Sender<EventData>,
+) -> io::Result<()> {
+    let scan = || {
+        let mut anchors = certificate_directories::anchors(directories);
+        anchors.extend(nss::anchors(databases));
+        anchors
+    };
+
+    let mut watcher = Watcher::new(
+        directories
+            .iter()
+            .cloned()
+            .chain(nss::files(databases))
+            .collect(),
+    )?;
+    let mut anchors = scan();
+
+    log::debug!("watching {} trusted certificates", anchors.len());
+
+    loop {
+        watcher.wait(SETTLE_TIME)?;
+
+        let scanned = scan();
+        let changes = snapshot::changes(
+            Backend::TrustStore,
+            &anchors,
+            &scanned,
+            &ProcessInfo::unknown(0),
+        );
+        for event in changes {
+            if sender.send(event).is_err() {
+                log::warn!("event stream receiver has shutdown");
+                return Ok(());
+            }
+        }
+
+        anchors = scanned;
+    }
+}
+
+/// Certificates whose trust changed are updated.
+impl SnapshotItem for Anchor {
+    fn resolve(&self) -> (&'static ClassSpec, ResolvedItem) {
+        let class = spec_named("certificate").unwrap_or(&CLASSES[0]);
+        let mut item = class.resolve(&self.attributes);
+
+        if let Some(trust) = &self.trust {
+            item.details.push(ItemDetail {
+                name: String::from("trust"),
+                value: trust.clone(),
+            });
+        }
+
+        (class, item)
+    }
+
+    fn differs_from(&self, previous: &Self) -> bool {
+        self.trust != previous.trust
+    }
+}
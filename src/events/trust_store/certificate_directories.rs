@@ -0,0 +1,113 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{Anchor, Anchors, TrustStoreConfig};
+use crate::items::{attr, x509, AttributeValue};
+
+/// Where distributions keep the certificates everything on the system trusts, along with
+/// where admins add their own.
+const DEFAULT_DIRECTORIES: &[&str] = &[
+    "/etc/ssl/certs",
+    "/usr/local/share/ca-certificates",
+    "/etc/pki/ca-trust/source/anchors",
+    "/etc/ca-certificates/trust-source/anchors",
+];
+
+/// How deep into a directory certificates are looked for.
+const MAX_DEPTH: usize = 2;
+
+/// The store these certificates are all reported as being in, since the same ones show up in
+/// several directories once they're installed.
+const STORE: &str = "system";
+
+/// Every directory to watch. Subdirectories are included as they are now, since ones made
+/// later can't be watched without them.
+pub fn resolve(config: &TrustStoreConfig) -> Vec<PathBuf> {
+    let mut directories = Vec::new();
+
+    for directory in DEFAULT_DIRECTORIES
+        .iter()
+        .map(PathBuf::from)
+        .chain(config.directories.iter().cloned())
+    {
+        collect_directories(directory, 0, &mut directories);
+    }
+
+    directories
+}
+
+fn collect_directories(directory: PathBuf, depth: usize, found: &mut Vec<PathBuf>) {
+    if depth < MAX_DEPTH {
+        for entry in fs::read_dir(&directory).into_iter().flatten().flatten() {
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                collect_directories(entry.path(), depth + 1, found);
+            }
+        }
+    }
+
+    found.push(directory);
+}
+
+/// Reads every certificate in the directories, whether it's PEM or DER encoded.
+pub fn anchors(directories: &[PathBuf]) -> Anchors {
+    // Certificates are usually both in a file of their own and a bundle of all of them, and the
+    // file of their own says more about where they came from.
+    let mut files: Vec<(PathBuf, Vec<Vec<u8>>)> = directories
+        .iter()
+        .flat_map(|directory| fs::read_dir(directory).into_iter().flatten().flatten())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .map(|path| {
+            let certificates = certificates_in(&path);
+            (path, certificates)
+        })
+        .collect();
+
+    files.sort_by(|(a_path, a), (b_path, b)| a.len().cmp(&b.len()).then(a_path.cmp(b_path)));
+
+    let mut anchors = Anchors::new();
+
+    for (path, certificates) in files {
+        // Links are followed to the file that's really there, which is the same one no matter
+        // how many links point at it.
+        let path = fs::canonicalize(&path).unwrap_or(path);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        for der in certificates {
+            let (key, mut anchor) = match Anchor::read(STORE, &der, &name, None) {
+                Some(anchor) => anchor,
+                None => continue,
+            };
+
+            anchors.entry(key).or_insert_with(|| {
+                anchor.attributes.insert(
+                    attr::PATH.to_owned(),
+                    AttributeValue::Text(path.to_string_lossy().into_owned()),
+                );
+                anchor
+            });
+        }
+    }
+
+    anchors
+}
+
+/// Every certificate in a file. Files that aren't certificates have none.
+fn certificates_in(path: &Path) -> Vec<Vec<u8>> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            log::debug!("couldn't read {}: {e}", path.display());
+            return Vec::new();
+        }
+    };
+
+    match std::str::from_utf8(&contents) {
+        Ok(text) => x509::pem_certificates(text),
+        // Anything that doesn't start like a certificate is skipped once it fails to parse.
+        Err(_) if contents.first() == Some(&0x30) => vec![contents],
+        Err(_) => Vec::new(),
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use super::{Anchor, Anchors, TrustStoreConfig};
+
+/// The NSS database Chrome and other apps share, relative to the home directory.
+const SHARED_DATABASE: &str = ".pki/nssdb";
+/// The system wide NSS database some distributions have.
+const SYSTEM_DATABASE: &str = "/etc/pki/nssdb";
+/// Where Firefox keeps its profiles, each with a database of its own.
+const FIREFOX_PROFILES: &[&str] = &[".mozilla/firefox", "snap/firefox/common/.mozilla/firefox"];
+/// The files in a database that hold its certificates and their trust, and the modules it loads.
+const DATABASE_FILES: &[&str] = &["cert9.db", "pkcs11.txt"];
+
+/// Every NSS database directory to look at, including Firefox profiles that exist now.
+pub fn resolve(config: &TrustStoreConfig, home: &Path) -> Vec<PathBuf> {
+    let mut databases = vec![home.join(SHARED_DATABASE), PathBuf::from(SYSTEM_DATABASE)];
+
+    for profiles in FIREFOX_PROFILES {
+        for entry in fs::read_dir(home.join(profiles))
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            if entry.path().join("cert9.db").is_file() {
+                databases.push(entry.path());
+            }
+        }
+    }
+
+    databases.extend(config.nss_databases.iter().map(|path| home.join(path)));
+    databases
+}
+
+/// The files to watch in each database. Firefox keeps the rest of a profile next to them, which
+/// changes all the time.
+pub fn files(databases: &[PathBuf]) -> Vec<PathBuf> {
+    databases
+        .iter()
+        .flat_map(|database| DATABASE_FILES.iter().map(|file| database.join(file)))
+        .collect()
+}
+
+/// Reads the certificates each database trusts to issue others, using `certutil`.
+///
+/// Firefox's built in roots aren't in its database, only the ones added to it and ones whose
+/// trust was changed.
+pub fn anchors(databases: &[PathBuf]) -> Anchors {
+    let mut anchors = Anchors::new();
+
+    for database in databases {
+        if !database.join("cert9.db").is_file() {
+            continue;
+        }
+
+        let store = database.to_string_lossy();
+
+        let listed = match list(database) {
+            Ok(listed) => listed,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::debug!("certutil isn't installed, NSS databases won't be looked at");
+                break;
+            }
+            Err(e) => {
+                log::warn!("couldn't list the certificates in {store}: {e}");
+                continue;
+            }
+        };
+
+        for (nickname, trust) in listed {
+            if !trust.contains(['C', 'T']) {
+                continue;
+            }
+
+            let der = match certutil(database, &["-n", &nickname, "-r"]) {
+                Ok(der) => der,
+                Err(e) => {
+                    log::debug!("couldn't read {nickname} out of {store}: {e}");
+                    continue;
+                }
+            };
+
+            anchors.extend(Anchor::read(&store, &der, &nickname, Some(trust)));
+        }
+    }
+
+    anchors
+}
+
+/// Lists a database's certificates by nickname, along with their trust flags.
+///
+/// The flags are for SSL, email and code signing, like `CT,C,`, where `C` and `T` mean the
+/// certificate is trusted to issue server and client certificates.
+fn list(database: &Path) -> io::Result<Vec<(String, String)>> {
+    let output = certutil(database, &[])?;
+
+    Ok(String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| {
+            // Nicknames can have spaces in them, but the flags never do.
+            let (nickname, trust) = line.trim_end().rsplit_once(char::is_whitespace)?;
+            let is_trust = trust.matches(',').count() == 2
+                && trust.chars().all(|c| c == ',' || c.is_ascii_alphabetic());
+
+            is_trust.then(|| (nickname.trim_end().to_owned(), trust.to_owned()))
+        })
+        .collect())
+}
+
+fn certutil(database: &Path, args: &[&str]) -> io::Result<Vec<u8>> {
+    let output = Command::new("certutil")
+        .arg("-L")
+        .arg("-d")
+        .arg(format!("sql:{}", database.display()))
+        .args(args)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use super::{super::Watcher, *};
+
+    /// A home directory with a Firefox profile in it, which is removed when dropped.
+    struct TestHome(PathBuf);
+
+    impl TestHome {
+        fn new(name: &str) -> Self {
+            let home = std::env::temp_dir().join(format!("keeper-{name}-{}", std::process::id()));
+            fs::create_dir_all(home.join(".mozilla/firefox/test.default")).unwrap();
+            fs::create_dir_all(home.join(".mozilla/firefox/Crash Reports")).unwrap();
+            fs::write(home.join(".mozilla/firefox/test.default/cert9.db"), "").unwrap();
+            Self(home)
+        }
+
+        fn profile(&self) -> PathBuf {
+            self.0.join(".mozilla/firefox/test.default")
+        }
+    }
+
+    impl Drop for TestHome {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn finds_firefox_profiles_with_databases() {
+        let home = TestHome::new("nss-resolve");
+        let databases = resolve(&TrustStoreConfig::default(), &home.0);
+
+        assert!(databases.contains(&home.profile()));
+        assert!(!databases.contains(&home.0.join(".mozilla/firefox/Crash Reports")));
+        assert_eq!(
+            files(&[home.profile()]),
+            [
+                home.profile().join("cert9.db"),
+                home.profile().join("pkcs11.txt")
+            ]
+        );
+    }
+
+    #[test]
+    fn only_database_files_are_watched_in_profiles() {
+        let home = TestHome::new("nss-watch");
+        let mut watcher = Watcher::new(files(&[home.profile()])).unwrap();
+
+        let (woke_tx, woke) = mpsc::channel();
+        thread::spawn(move || {
+            while watcher.wait(Duration::ZERO).is_ok() {
+                if woke_tx.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Give the watcher a moment to add its watches.
+        thread::sleep(Duration::from_millis(100));
+
+        fs::write(home.profile().join("prefs.js"), "user_pref();").unwrap();
+        assert!(woke.recv_timeout(Duration::from_millis(500)).is_err());
+
+        fs::write(home.profile().join("pkcs11.txt"), "library=").unwrap();
+        woke.recv_timeout(Duration::from_secs(5))
+            .expect("the watcher didn't see the database change");
+    }
+}
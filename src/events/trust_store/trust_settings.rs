@@ -0,0 +1,127 @@
+use core::ffi::c_void;
+use core_foundation::{
+    base::OSStatus,
+    runloop::{self, CFRunLoop},
+};
+use security_framework::trust_settings::{Domain, TrustSettings, TrustSettingsForCertificate};
+use std::{sync::mpsc, thread, time::Duration};
+
+use super::{Anchor, Anchors};
+use crate::{
+    bindings::{self, SecKeychainCallbackInfo, SecKeychainEvent, SecKeychainEventMask},
+    events::{snapshot, Backend, EventData},
+    process::ProcessCache,
+};
+
+/// State handed to every invocation of the trust settings callback.
+struct MonitorContext {
+    sender: mpsc::Sender<EventData>,
+    processes: ProcessCache,
+    anchors: Anchors,
+}
+
+pub fn start_trust_settings_monitor(sender: mpsc::Sender<EventData>) {
+    thread::Builder::new()
+        .name(String::from("Trust Settings Monitor"))
+        .spawn(move || {
+            let anchors = anchors();
+            log::debug!(
+                "watching {} certificates with trust settings",
+                anchors.len()
+            );
+
+            let ctx = Box::into_raw(Box::new(MonitorContext {
+                sender,
+                processes: ProcessCache::default(),
+                anchors,
+            }));
+
+            let status = unsafe {
+                bindings::SecKeychainAddCallback(
+                    callback_handler,
+                    SecKeychainEventMask::kSecTrustSettingsChangedEventMask,
+                    ctx.cast(),
+                )
+            };
+            assert_eq!(status, 0, "failed to register callback");
+
+            // Callbacks are delivered on the runloop of the thread that added them, the same as
+            // the keychain monitor's.
+            loop {
+                CFRunLoop::run_in_mode(
+                    unsafe { runloop::kCFRunLoopDefaultMode },
+                    Duration::from_secs(10),
+                    true,
+                );
+
+                thread::sleep(Duration::from_millis(100));
+            }
+        })
+        .expect("failed to start trust settings monitor");
+}
+
+#[allow(non_snake_case)]
+extern "C" fn callback_handler(
+    keychainEvent: SecKeychainEvent,
+    info: *mut SecKeychainCallbackInfo,
+    ctx: *mut c_void,
+) -> OSStatus {
+    let ctx = unsafe { &mut *(ctx as *mut MonitorContext) };
+
+    log::trace!("received callback for {:?} event", keychainEvent);
+
+    // The event only says that something changed, so everything is looked at again.
+    let changer = ctx.processes.lookup(unsafe { &*info }.pid);
+    let anchors = anchors();
+
+    for event in snapshot::changes(Backend::TrustStore, &ctx.anchors, &anchors, &changer) {
+        if ctx.sender.send(event).is_err() {
+            log::warn!("event stream receiver has shutdown");
+        }
+    }
+
+    ctx.anchors = anchors;
+
+    0
+}
+
+/// Every certificate with trust settings for the user or the whole machine. The system's own
+/// settings only change with macOS itself, so they aren't included.
+fn anchors() -> Anchors {
+    let mut anchors = Anchors::new();
+
+    for (domain, store) in [(Domain::User, "user"), (Domain::Admin, "admin")] {
+        let settings = TrustSettings::new(domain);
+
+        // Domains without any trust settings at all are an error.
+        let certificates = match settings.iter() {
+            Ok(certificates) => certificates,
+            Err(_) => continue,
+        };
+
+        for certificate in certificates {
+            let trust = match settings.tls_trust_settings_for_certificate(&certificate) {
+                // Settings that don't say otherwise make the certificate a trusted root.
+                Ok(None | Some(TrustSettingsForCertificate::TrustRoot)) => "trusted",
+                // Certificates that aren't roots themselves, but are trusted like one.
+                Ok(Some(TrustSettingsForCertificate::TrustAsRoot)) => "trusted as root",
+                Ok(Some(TrustSettingsForCertificate::Deny)) => "never trusted",
+                Ok(Some(_)) => "unspecified",
+                Err(e) => {
+                    log::debug!("couldn't read a certificate's trust settings: {e}");
+                    continue;
+                }
+            };
+
+            let der = certificate.to_der();
+            anchors.extend(Anchor::read(
+                store,
+                &der,
+                &certificate.subject_summary(),
+                Some(trust.to_owned()),
+            ));
+        }
+    }
+
+    anchors
+}
@@ -0,0 +1,352 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    fs::File,
+    io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{process::ProcessInfo, state};
+
+/// Hashes of executables, remembered until the file on disk changes.
+#[derive(Default)]
+pub struct HashCache {
+    entries: HashMap<PathBuf, CachedHash>,
+}
+
+struct CachedHash {
+    inode: u64,
+    modified: SystemTime,
+    fingerprint: Fingerprint,
+}
+
+/// What's known about the contents of an executable.
+#[derive(Clone, Debug)]
+pub struct Fingerprint {
+    /// Hex encoded SHA-256 of the file.
+    pub sha256: String,
+    /// If the file has a valid code signature, on platforms that have them.
+    pub signed: Option<bool>,
+}
+
+impl HashCache {
+    /// How many executables are remembered before starting over.
+    const CAPACITY: usize = 512;
+
+    /// Returns the fingerprint of the file at `path`.
+    pub fn fingerprint(&mut self, path: &Path) -> Option<Fingerprint> {
+        let metadata = match path.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::debug!("can't fingerprint {}: {e}", path.display());
+                return None;
+            }
+        };
+
+        let modified = metadata.modified().ok()?;
+
+        if let Some(cached) = self.entries.get(path) {
+            if cached.inode == metadata.ino() && cached.modified == modified {
+                return Some(cached.fingerprint.clone());
+            }
+        }
+
+        let sha256 = match hash_file(path) {
+            Ok(sha256) => sha256,
+            Err(e) => {
+                log::debug!("can't fingerprint {}: {e}", path.display());
+                return None;
+            }
+        };
+
+        let fingerprint = Fingerprint {
+            sha256,
+            signed: crate::process::code_signed(path),
+        };
+
+        if self.entries.len() >= Self::CAPACITY {
+            self.entries.clear();
+        }
+
+        self.entries.insert(
+            path.to_path_buf(),
+            CachedHash {
+                inode: metadata.ino(),
+                modified,
+                fingerprint: fingerprint.clone(),
+            },
+        );
+
+        Some(fingerprint)
+    }
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    let mut hex = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        let _ = write!(hex, "{byte:02x}");
+    }
+
+    Ok(hex)
+}
+
+/// Something unusual about the executable that changed an item.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// The executable at this path has different contents than the first time it touched the item.
+    HashChanged { first_seen: String },
+    /// The item has been changed before, but never by anything with this executable's contents.
+    NewExecutable,
+}
+
+impl Finding {
+    pub fn describe(&self) -> String {
+        match self {
+            Finding::HashChanged { first_seen } => format!(
+                "the changer's executable was modified since it first touched this item (was {})",
+                &first_seen[..first_seen.len().min(12)]
+            ),
+            Finding::NewExecutable => {
+                String::from("the changer's executable has never been seen before")
+            }
+        }
+    }
+}
+
+/// Remembers which executables have touched each item, to notice when that changes.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FingerprintStore {
+    /// The first hash seen for every executable path, per item.
+    items: HashMap<String, HashMap<PathBuf, String>>,
+    /// Every executable hash that has changed an item.
+    known_hashes: HashSet<String>,
+    /// Where changes get written to, if they should outlive the process.
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl FingerprintStore {
+    const FILE_NAME: &'static str = "fingerprints.json";
+
+    pub fn load(data_dir: &Path) -> Self {
+        let path = state::path_for(data_dir, Self::FILE_NAME);
+
+        let mut store: Self = state::load(&path);
+        store.path = Some(path);
+        store
+    }
+
+    /// A store that starts empty and forgets everything when dropped.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// If `changer`'s executable path has touched `item` before.
+    pub fn has_touched(&self, item: &str, changer: &ProcessInfo) -> bool {
+        match (&changer.path, self.items.get(item)) {
+            (Some(path), Some(seen_paths)) => seen_paths.contains_key(path),
+            _ => false,
+        }
+    }
+
+    /// Records that `changer` touched `item`, returning anything suspicious about it.
+    pub fn observe(&mut self, item: &str, changer: &ProcessInfo) -> Option<Finding> {
+        let (path, sha256) = match (&changer.path, &changer.sha256) {
+            (Some(path), Some(sha256)) => (path, sha256),
+            _ => return None,
+        };
+
+        let newly_seen_hash = self.known_hashes.insert(sha256.clone());
+
+        let item_known = self.items.contains_key(item);
+        let seen_paths = self.items.entry(item.to_owned()).or_default();
+
+        let mut changed = newly_seen_hash;
+
+        let finding = match seen_paths.get(path) {
+            Some(first_seen) if first_seen != sha256 => Some(Finding::HashChanged {
+                first_seen: first_seen.clone(),
+            }),
+            Some(_) => None,
+            None => {
+                seen_paths.insert(path.clone(), sha256.clone());
+                changed = true;
+
+                if item_known && newly_seen_hash {
+                    Some(Finding::NewExecutable)
+                } else {
+                    None
+                }
+            }
+        };
+
+        if changed {
+            self.persist();
+        }
+
+        finding
+    }
+
+    fn persist(&self) {
+        if let Some(path) = &self.path {
+            if let Err(e) = state::save(path, self) {
+                log::warn!("failed to save executable fingerprints: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use super::*;
+
+    /// A directory of executables, removed when dropped.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn create(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("keeper-{name}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        /// Writes `contents` to `name`, modified at `modified` seconds after the epoch.
+        fn write(&self, name: &str, contents: &str, modified: u64) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, contents).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified))
+                .unwrap();
+            path
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn sha256(contents: &str) -> String {
+        Sha256::digest(contents)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn changer(path: &str, contents: &str) -> ProcessInfo {
+        ProcessInfo {
+            path: Some(path.into()),
+            sha256: Some(sha256(contents)),
+            ..ProcessInfo::unknown(100)
+        }
+    }
+
+    #[test]
+    fn fingerprints_files() {
+        let dir = TestDir::create("fingerprints");
+        let mut cache = HashCache::default();
+
+        let path = dir.write("tool", "one", 1_000);
+        assert_eq!(cache.fingerprint(&path).unwrap().sha256, sha256("one"));
+        assert!(cache.fingerprint(&dir.0.join("missing")).is_none());
+    }
+
+    #[test]
+    fn rehashes_files_modified_in_place() {
+        let dir = TestDir::create("fingerprints-modified");
+        let mut cache = HashCache::default();
+
+        let path = dir.write("tool", "one", 1_000);
+        assert_eq!(cache.fingerprint(&path).unwrap().sha256, sha256("one"));
+
+        // The file is only read again once its modification time says it changed.
+        dir.write("tool", "two", 1_000);
+        assert_eq!(cache.fingerprint(&path).unwrap().sha256, sha256("one"));
+
+        dir.write("tool", "two", 2_000);
+        assert_eq!(cache.fingerprint(&path).unwrap().sha256, sha256("two"));
+    }
+
+    #[test]
+    fn rehashes_replaced_files() {
+        let dir = TestDir::create("fingerprints-replaced");
+        let mut cache = HashCache::default();
+
+        let path = dir.write("tool", "one", 1_000);
+        assert_eq!(cache.fingerprint(&path).unwrap().sha256, sha256("one"));
+
+        // Installers usually write a new file and move it over the old one, which can leave the
+        // modification time as it was.
+        let replacement = dir.write("tool.new", "two", 1_000);
+        fs::rename(replacement, &path).unwrap();
+        assert_eq!(cache.fingerprint(&path).unwrap().sha256, sha256("two"));
+    }
+
+    #[test]
+    fn finds_modified_executables() {
+        let mut store = FingerprintStore::in_memory();
+        let before = changer("/usr/bin/tool", "one");
+
+        assert!(!store.has_touched("github.com", &before));
+        assert_eq!(store.observe("github.com", &before), None);
+        assert!(store.has_touched("github.com", &before));
+        assert_eq!(store.observe("github.com", &before), None);
+
+        assert_eq!(
+            store.observe("github.com", &changer("/usr/bin/tool", "two")),
+            Some(Finding::HashChanged {
+                first_seen: sha256("one")
+            })
+        );
+        // Other items don't remember the old executable.
+        assert_eq!(
+            store.observe("gitlab.com", &changer("/usr/bin/tool", "two")),
+            None
+        );
+    }
+
+    #[test]
+    fn finds_new_executables() {
+        let mut store = FingerprintStore::in_memory();
+
+        assert_eq!(
+            store.observe("github.com", &changer("/usr/bin/tool", "one")),
+            None
+        );
+        assert_eq!(
+            store.observe("github.com", &changer("/usr/bin/other", "two")),
+            Some(Finding::NewExecutable)
+        );
+        // Executables that were seen changing something else before aren't new.
+        assert_eq!(
+            store.observe("gitlab.com", &changer("/usr/bin/third", "three")),
+            None
+        );
+        assert_eq!(
+            store.observe("github.com", &changer("/usr/local/bin/third", "three")),
+            None
+        );
+
+        // Nothing is known about changers without an executable or its hash.
+        assert_eq!(
+            store.observe("github.com", &ProcessInfo::unknown(100)),
+            None
+        );
+    }
+}
@@ -0,0 +1,505 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{cf_now, unix_now},
+    events::{Backend, FilteredEventData, InnerDetails},
+    items::ItemAttributes,
+    process::ProcessInfo,
+    state,
+};
+
+mod export;
+#[cfg(target_os = "macos")]
+mod keychain;
+#[cfg(target_os = "linux")]
+mod secret_service;
+
+use export::{Filters, Format};
+
+/// Settings for taking stock of every item, to catch changes made while the monitor wasn't running.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct InventoryConfig {
+    pub(crate) enabled: bool,
+    /// How often to take stock again while running, in minutes.
+    pub(crate) interval_mins: u64,
+}
+
+impl Default for InventoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_mins: 60,
+        }
+    }
+}
+
+/// What's known about an item without ever reading its secret.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct InventoryItem {
+    /// The kind of item, like `generic_password` or `internet_password`.
+    pub class: String,
+    pub label: Option<String>,
+    /// The service of generic passwords, or the server of internet passwords.
+    pub service: Option<String>,
+    pub account: Option<String>,
+    /// When the item was last modified, as a Core Foundation absolute time.
+    pub modified_at: Option<f64>,
+}
+
+impl InventoryItem {
+    /// The name events about this item are reported with.
+    pub fn title(&self) -> &str {
+        self.label
+            .as_deref()
+            .or(self.service.as_deref())
+            .unwrap_or("Unknown")
+    }
+
+    /// Everything that tells this item apart from others, besides when it was modified.
+    fn key(&self) -> (&str, Option<&str>, Option<&str>, Option<&str>) {
+        (
+            &self.class,
+            self.label.as_deref(),
+            self.service.as_deref(),
+            self.account.as_deref(),
+        )
+    }
+}
+
+/// Somewhere items can be listed from.
+pub trait InventorySource {
+    /// Lists the metadata of every item. This must never read any secrets.
+    fn scan(&mut self) -> Result<Vec<InventoryItem>, String>;
+
+    /// Which keystore the items are in.
+    fn backend(&self) -> Backend;
+}
+
+/// The inventory source for this platform's keystore, if there is one.
+#[cfg(target_os = "macos")]
+pub fn platform_source() -> Option<Box<dyn InventorySource>> {
+    Some(Box::new(keychain::KeychainInventory))
+}
+
+#[cfg(target_os = "linux")]
+pub fn platform_source() -> Option<Box<dyn InventorySource>> {
+    Some(Box::new(secret_service::SecretServiceInventory))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn platform_source() -> Option<Box<dyn InventorySource>> {
+    None
+}
+
+/// How one item differs between two inventories.
+#[derive(Debug)]
+pub enum Change {
+    Added(InventoryItem),
+    Modified(InventoryItem),
+    Removed(InventoryItem),
+}
+
+/// Finds every item that was added, modified or removed going from `old` to `new`.
+pub fn diff(old: &[InventoryItem], new: &[InventoryItem]) -> Vec<Change> {
+    let old_items: HashMap<_, _> = old.iter().map(|item| (item.key(), item)).collect();
+    let new_keys: HashSet<_> = new.iter().map(InventoryItem::key).collect();
+
+    let mut changes = Vec::new();
+
+    for item in new {
+        match old_items.get(&item.key()) {
+            None => changes.push(Change::Added(item.clone())),
+            Some(old) if old.modified_at != item.modified_at => {
+                changes.push(Change::Modified(item.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+
+    for item in old {
+        if !new_keys.contains(&item.key()) {
+            changes.push(Change::Removed(item.clone()));
+        }
+    }
+
+    changes
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InventoryState {
+    /// Seconds since the Unix epoch that the last scan finished at.
+    pub scanned_at: Option<u64>,
+    pub items: Vec<InventoryItem>,
+}
+
+/// Takes stock of every item on a schedule, turning anything that changed since the last
+/// time into events.
+pub struct Scanner {
+    source: Box<dyn InventorySource>,
+    interval: Duration,
+    state: InventoryState,
+    path: PathBuf,
+    last_scan: Option<Instant>,
+    /// Items the live monitor already reported since the last scan, which don't need reporting twice.
+    seen_live: HashSet<String>,
+    /// Removals the live monitor already reported since the last scan. It never knows what was
+    /// removed, so these are matched up with removals the scan finds by count.
+    live_removals: usize,
+}
+
+impl Scanner {
+    pub const FILE_NAME: &'static str = "inventory.json";
+
+    pub fn load(
+        source: Box<dyn InventorySource>,
+        config: &InventoryConfig,
+        data_dir: &Path,
+    ) -> Self {
+        let path = state::path_for(data_dir, Self::FILE_NAME);
+
+        Self {
+            source,
+            interval: Duration::from_secs(config.interval_mins.max(1) * 60),
+            state: state::load(&path),
+            path,
+            last_scan: None,
+            seen_live: HashSet::new(),
+            live_removals: 0,
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_scan
+            .is_none_or(|last| last.elapsed() >= self.interval)
+    }
+
+    /// Remembers an event the live monitor reported, so the next scan doesn't repeat it.
+    pub fn note_live(&mut self, event: &FilteredEventData) {
+        // Reads and locking don't change anything a scan could notice.
+        if let FilteredEventData::Accessed(_) | FilteredEventData::LockChanged { .. } = event {
+            return;
+        }
+
+        match event.item_title() {
+            Some(item_title) => {
+                self.seen_live.insert(item_title.to_owned());
+            }
+            None => self.live_removals += 1,
+        }
+    }
+
+    /// Takes stock of every item, returning events for whatever changed since the last scan.
+    ///
+    /// The very first scan has nothing to compare against, so it only records what's there.
+    pub fn scan(&mut self) -> Vec<FilteredEventData> {
+        self.last_scan = Some(Instant::now());
+
+        let items = match self.source.scan() {
+            Ok(items) => items,
+            Err(e) => {
+                log::warn!("failed to take inventory: {e}");
+                return Vec::new();
+            }
+        };
+
+        let changes = match self.state.scanned_at {
+            Some(_) => diff(&self.state.items, &items),
+            None => {
+                log::info!("took first inventory of {} items", items.len());
+                Vec::new()
+            }
+        };
+
+        self.state = InventoryState {
+            scanned_at: Some(unix_now()),
+            items,
+        };
+        if let Err(e) = state::save(&self.path, &self.state) {
+            log::warn!("failed to save inventory: {e}");
+        }
+
+        let seen_live = std::mem::take(&mut self.seen_live);
+        let mut live_removals = std::mem::take(&mut self.live_removals);
+
+        let backend = self.source.backend();
+        let events: Vec<_> = changes
+            .into_iter()
+            .filter(|change| match change {
+                Change::Added(item) | Change::Modified(item) => !seen_live.contains(item.title()),
+                Change::Removed(_) if live_removals > 0 => {
+                    live_removals -= 1;
+                    false
+                }
+                Change::Removed(_) => true,
+            })
+            .map(|change| into_event(backend, change))
+            .collect();
+
+        log::debug!("inventory scan found {} unreported changes", events.len());
+        events
+    }
+}
+
+/// The attributes an inventory knows about, which are enough to tell most items apart.
+fn scanned_attributes(item: &InventoryItem) -> ItemAttributes {
+    let mut attributes = ItemAttributes {
+        account: item.account.clone(),
+        ..ItemAttributes::default()
+    };
+
+    // Inventories keep the server of internet passwords in the same place as other services.
+    if item.class == "internet_password" {
+        attributes.server = item.service.clone();
+    } else {
+        attributes.service = item.service.clone();
+    }
+
+    attributes
+}
+
+fn into_event(backend: Backend, change: Change) -> FilteredEventData {
+    // Whatever made the change is long gone by the time a scan notices it.
+    let changer = ProcessInfo::unknown(0);
+
+    match change {
+        Change::Added(item) => FilteredEventData::Added(
+            InnerDetails::new(
+                backend,
+                item.title().to_owned(),
+                item.modified_at.unwrap_or_else(cf_now),
+                changer,
+            )
+            .with_class(&item.class, scanned_attributes(&item), Vec::new()),
+        ),
+        Change::Modified(item) => FilteredEventData::Updated(
+            InnerDetails::new(
+                backend,
+                item.title().to_owned(),
+                item.modified_at.unwrap_or_else(cf_now),
+                changer,
+            )
+            .with_class(&item.class, scanned_attributes(&item), Vec::new()),
+        ),
+        Change::Removed(item) => FilteredEventData::Removed {
+            backend,
+            seen_at: cf_now(),
+            modified_by: changer,
+            item_name: Some(item.title().to_owned()),
+        },
+    }
+}
+
+/// Something an inventory can be read back from, either a saved state file or an export.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Snapshot {
+    State(InventoryState),
+    Items(Vec<InventoryItem>),
+}
+
+fn read_snapshot(path: &str) -> Result<Vec<InventoryItem>, ()> {
+    let contents = fs::read(path).map_err(|e| log::error!("failed to read {path}: {e}"))?;
+
+    match serde_json::from_slice(&contents) {
+        Ok(Snapshot::State(state)) => Ok(state.items),
+        Ok(Snapshot::Items(items)) => Ok(items),
+        Err(e) => {
+            log::error!("{path} isn't an inventory snapshot: {e}");
+            Err(())
+        }
+    }
+}
+
+/// Handles `inventory [list|diff <a> <b>] [--scan] [--format table|json|csv] [--redact]
+/// [--class <c>] [--label <l>] [--service <s>] [--account <a>]`.
+pub fn run_command(mut args: impl Iterator<Item = String>, data_dir: &Path) -> Result<(), ()> {
+    let mut positional = Vec::new();
+    let mut filters = Filters::default();
+    let mut format = Format::Table;
+    let mut redact = false;
+    let mut scan = false;
+
+    while let Some(arg) = args.next() {
+        let filter = match arg.as_str() {
+            "--redact" => {
+                redact = true;
+                continue;
+            }
+            "--scan" => {
+                scan = true;
+                continue;
+            }
+            "--format" => {
+                format = match args.next().as_deref().and_then(Format::parse) {
+                    Some(format) => format,
+                    None => {
+                        log::error!("--format needs one of table, json or csv");
+                        return Err(());
+                    }
+                };
+                continue;
+            }
+            "--class" => &mut filters.class,
+            "--label" => &mut filters.label,
+            "--service" => &mut filters.service,
+            "--account" => &mut filters.account,
+            _ if !arg.starts_with("--") => {
+                positional.push(arg);
+                continue;
+            }
+            _ => {
+                log::error!("unexpected inventory argument: {arg}");
+                return Err(());
+            }
+        };
+
+        match args.next() {
+            Some(value) => *filter = Some(value),
+            None => {
+                log::error!("{arg} needs a value");
+                return Err(());
+            }
+        }
+    }
+
+    let key = match redact.then(|| export::RedactionKey::load(data_dir)) {
+        Some(Ok(key)) => Some(key),
+        Some(Err(e)) => {
+            log::error!("failed to load the key accounts are redacted with: {e}");
+            return Err(());
+        }
+        None => None,
+    };
+
+    let prepare = |items: Vec<InventoryItem>| -> Vec<InventoryItem> {
+        items
+            .into_iter()
+            .filter(|item| filters.matches(item))
+            .map(|mut item| {
+                if let Some(key) = &key {
+                    export::redact(&mut item, key);
+                }
+                item
+            })
+            .collect()
+    };
+
+    let mut positional = positional.into_iter();
+
+    match positional.next().as_deref() {
+        None | Some("list") => {
+            let items = if scan {
+                let mut source = match platform_source() {
+                    Some(source) => source,
+                    None => {
+                        log::error!("there's nothing to take inventory of on this platform");
+                        return Err(());
+                    }
+                };
+
+                source
+                    .scan()
+                    .map_err(|e| log::error!("failed to take inventory: {e}"))?
+            } else {
+                let state: InventoryState =
+                    state::load(&state::path_for(data_dir, Scanner::FILE_NAME));
+
+                if state.scanned_at.is_none() {
+                    log::warn!("no inventory has been taken yet, try --scan");
+                }
+
+                state.items
+            };
+
+            export::print_items(&prepare(items), format);
+            Ok(())
+        }
+        Some("diff") => {
+            let (old, new) = match (positional.next(), positional.next()) {
+                (Some(old), Some(new)) => (old, new),
+                _ => {
+                    log::error!("diffing needs two snapshots");
+                    return Err(());
+                }
+            };
+
+            // Redacting before diffing is fine, since the same account always redacts the same way
+            // on this machine.
+            let old = prepare(read_snapshot(&old)?);
+            let new = prepare(read_snapshot(&new)?);
+
+            export::print_changes(&diff(&old, &new), format);
+            Ok(())
+        }
+        Some(other) => {
+            log::error!("unknown inventory command {other:?}, expected one of list or diff");
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(label: &str, account: &str, modified_at: f64) -> InventoryItem {
+        InventoryItem {
+            class: String::from("generic_password"),
+            label: Some(label.to_owned()),
+            service: Some(label.to_owned()),
+            account: Some(account.to_owned()),
+            modified_at: Some(modified_at),
+        }
+    }
+
+    fn summary(changes: &[Change]) -> Vec<(&'static str, &str, Option<&str>)> {
+        changes
+            .iter()
+            .map(|change| {
+                let (kind, item) = match change {
+                    Change::Added(item) => ("added", item),
+                    Change::Modified(item) => ("modified", item),
+                    Change::Removed(item) => ("removed", item),
+                };
+                (kind, item.title(), item.account.as_deref())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diffs_inventories() {
+        let old = [
+            item("github.com", "fox", 1.0),
+            item("gitlab.com", "fox", 1.0),
+            item("aws", "fox", 1.0),
+        ];
+        let new = [
+            item("github.com", "fox", 1.0),
+            item("gitlab.com", "fox", 2.0),
+            // Another account is another item, even with the same label.
+            item("aws", "hound", 1.0),
+        ];
+
+        assert_eq!(
+            summary(&diff(&old, &new)),
+            [
+                ("modified", "gitlab.com", Some("fox")),
+                ("added", "aws", Some("hound")),
+                ("removed", "aws", Some("fox")),
+            ]
+        );
+        assert!(diff(&new, &new).is_empty());
+        assert_eq!(
+            summary(&diff(&[], &old[..1])),
+            [("added", "github.com", Some("fox"))]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, Read, Write as _},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+};
+
+use super::{Change, InventoryItem};
+use crate::{clock::cf_to_unix, state};
+
+/// How inventories and diffs get printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "table" => Some(Self::Table),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Narrows an inventory down to matching items. Every filter is a case-insensitive substring.
+#[derive(Debug, Default)]
+pub struct Filters {
+    pub class: Option<String>,
+    pub label: Option<String>,
+    pub service: Option<String>,
+    pub account: Option<String>,
+}
+
+impl Filters {
+    pub fn matches(&self, item: &InventoryItem) -> bool {
+        fn contains(value: Option<&str>, filter: &Option<String>) -> bool {
+            match filter {
+                Some(filter) => {
+                    value.is_some_and(|value| value.to_lowercase().contains(&filter.to_lowercase()))
+                }
+                None => true,
+            }
+        }
+
+        contains(Some(&item.class), &self.class)
+            && contains(item.label.as_deref(), &self.label)
+            && contains(item.service.as_deref(), &self.service)
+            && contains(item.account.as_deref(), &self.account)
+    }
+}
+
+/// A secret that account names are hashed with when they're redacted, so they can't be found
+/// again by hashing likely ones. It never leaves this machine.
+pub struct RedactionKey([u8; 32]);
+
+impl RedactionKey {
+    const FILE_NAME: &'static str = "redaction.key";
+
+    /// Reads the key, making a new one the first time.
+    pub fn load(data_dir: &Path) -> io::Result<Self> {
+        let path = state::path_for(data_dir, Self::FILE_NAME);
+
+        let mut key = [0; 32];
+
+        match File::open(&path) {
+            Ok(mut file) => file.read_exact(&mut key)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                File::open("/dev/urandom")?.read_exact(&mut key)?;
+
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+
+                // Nobody else needs to be able to read it.
+                File::options()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&path)?
+                    .write_all(&key)?;
+            }
+            Err(e) => return Err(e),
+        }
+
+        Ok(Self(key))
+    }
+}
+
+/// Replaces the account name with a short keyed hash of it.
+///
+/// The same account always turns into the same hash on the same machine, so redacted snapshots
+/// can still be diffed.
+pub fn redact(item: &mut InventoryItem, key: &RedactionKey) {
+    if let Some(account) = &mut item.account {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&key.0).expect("HMAC takes keys of any length");
+        mac.update(account.as_bytes());
+        let digest = mac.finalize().into_bytes();
+
+        let mut redacted = String::from("redacted-");
+        for byte in &digest[..8] {
+            let _ = write!(redacted, "{byte:02x}");
+        }
+
+        *account = redacted;
+    }
+}
+
+const COLUMNS: [&str; 5] = ["class", "label", "service", "account", "modified_at"];
+
+/// The item's fields as printed in tables and CSV, with the modification date in seconds
+/// since the Unix epoch.
+fn row(item: &InventoryItem) -> [String; 5] {
+    [
+        item.class.clone(),
+        item.label.clone().unwrap_or_default(),
+        item.service.clone().unwrap_or_default(),
+        item.account.clone().unwrap_or_default(),
+        item.modified_at
+            .map(|at| cf_to_unix(at).to_string())
+            .unwrap_or_default(),
+    ]
+}
+
+fn change_kind(change: &Change) -> &'static str {
+    match change {
+        Change::Added(_) => "added",
+        Change::Modified(_) => "modified",
+        Change::Removed(_) => "removed",
+    }
+}
+
+fn change_item(change: &Change) -> &InventoryItem {
+    match change {
+        Change::Added(item) | Change::Modified(item) | Change::Removed(item) => item,
+    }
+}
+
+pub fn print_items(items: &[InventoryItem], format: Format) {
+    match format {
+        Format::Json => print_json(&items),
+        Format::Table => print_table(&COLUMNS, items.iter().map(|item| row(item).to_vec())),
+        Format::Csv => print_csv(&COLUMNS, items.iter().map(|item| row(item).to_vec())),
+    }
+}
+
+pub fn print_changes(changes: &[Change], format: Format) {
+    #[derive(Serialize)]
+    struct JsonChange<'a> {
+        change: &'static str,
+        item: &'a InventoryItem,
+    }
+
+    let mut columns = vec!["change"];
+    columns.extend(COLUMNS);
+
+    let rows = changes.iter().map(|change| {
+        let mut row = vec![change_kind(change).to_owned()];
+        row.extend(self::row(change_item(change)));
+        row
+    });
+
+    match format {
+        Format::Json => print_json(
+            &changes
+                .iter()
+                .map(|change| JsonChange {
+                    change: change_kind(change),
+                    item: change_item(change),
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Format::Table => print_table(&columns, rows),
+        Format::Csv => print_csv(&columns, rows),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    let json = serde_json::to_string_pretty(value).expect("inventories are always serializable");
+    println!("{json}");
+}
+
+fn print_table(columns: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    let rows: Vec<_> = rows.collect();
+
+    let mut widths: Vec<usize> = columns.iter().map(|column| column.len()).collect();
+    for row in &rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+
+    let print_row = |fields: &mut dyn Iterator<Item = &str>| {
+        let mut line = String::new();
+        for (field, width) in fields.zip(&widths) {
+            let _ = write!(line, "{field:<width$}  ");
+        }
+        println!("{}", line.trim_end());
+    };
+
+    print_row(&mut columns.iter().copied());
+    for row in &rows {
+        print_row(&mut row.iter().map(String::as_str));
+    }
+}
+
+fn print_csv(columns: &[&str], rows: impl Iterator<Item = Vec<String>>) {
+    println!("{}", columns.join(","));
+
+    for row in rows {
+        let fields: Vec<_> = row.iter().map(|field| csv_field(field)).collect();
+        println!("{}", fields.join(","));
+    }
+}
+
+/// Quotes a field if it has anything in it that CSV readers would trip over.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(account: &str) -> InventoryItem {
+        InventoryItem {
+            class: String::from("internet_password"),
+            label: Some(String::from("github.com")),
+            service: Some(String::from("github.com")),
+            account: Some(account.to_owned()),
+            modified_at: None,
+        }
+    }
+
+    #[test]
+    fn redacts_with_hmac_sha256() {
+        // RFC 4231 test case 2, with the key padded out with zeros like a shorter key would be.
+        let mut key = [0; 32];
+        key[..4].copy_from_slice(b"Jefe");
+
+        let mut item = item("what do ya want for nothing?");
+        redact(&mut item, &RedactionKey(key));
+
+        assert_eq!(item.account.as_deref(), Some("redacted-5bdcc146bf60754e"));
+    }
+
+    #[test]
+    fn redaction_depends_on_the_key() {
+        let redacted = |account, key: &RedactionKey| {
+            let mut item = item(account);
+            redact(&mut item, key);
+            item.account.unwrap()
+        };
+
+        let ours = RedactionKey([1; 32]);
+        let theirs = RedactionKey([2; 32]);
+
+        let alice = redacted("alice@example.com", &ours);
+        assert!(alice.starts_with("redacted-"));
+        assert!(!alice.contains("alice"));
+        assert_eq!(alice, redacted("alice@example.com", &ours));
+        assert_ne!(alice, redacted("bob@example.com", &ours));
+        assert_ne!(alice, redacted("alice@example.com", &theirs));
+    }
+
+    #[test]
+    fn filters_by_substring_regardless_of_case() {
+        let filters = |class: &str, label: &str, service: &str, account: &str| {
+            let filter = |value: &str| (!value.is_empty()).then(|| value.to_owned());
+            Filters {
+                class: filter(class),
+                label: filter(label),
+                service: filter(service),
+                account: filter(account),
+            }
+        };
+        let item = item("Alice@Example.com");
+
+        assert!(Filters::default().matches(&item));
+        assert!(filters("internet", "", "", "").matches(&item));
+        assert!(filters("", "GitHub", "", "").matches(&item));
+        assert!(filters("", "", "hub.c", "alice@").matches(&item));
+        assert!(!filters("generic", "", "", "").matches(&item));
+        // Every filter has to match.
+        assert!(!filters("", "github", "", "bob").matches(&item));
+
+        let unlabelled = InventoryItem {
+            label: None,
+            ..item.clone()
+        };
+        assert!(filters("", "", "github", "").matches(&unlabelled));
+        assert!(!filters("", "github", "", "").matches(&unlabelled));
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("github.com"), "github.com");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("fox, hound"), "\"fox, hound\"");
+        assert_eq!(csv_field("the \"prod\" key"), "\"the \"\"prod\"\" key\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+}
@@ -0,0 +1,90 @@
+use core::ffi::c_void;
+use core_foundation::{
+    array::CFArray,
+    base::{CFType, TCFType},
+    boolean::CFBoolean,
+    dictionary::CFDictionary,
+    string::CFString,
+};
+use security_framework_sys::{
+    base::errSecItemNotFound,
+    item::{kSecClass, kSecMatchLimit, kSecReturnAttributes},
+};
+
+use super::{InventoryItem, InventorySource};
+use crate::{
+    bindings,
+    events::Backend,
+    items::{self, Attributes, ClassSpec, CLASSES},
+};
+
+/// Lists every item in the user's keychains.
+pub struct KeychainInventory;
+
+impl InventorySource for KeychainInventory {
+    fn scan(&mut self) -> Result<Vec<InventoryItem>, String> {
+        let mut inventory = Vec::new();
+
+        for spec in CLASSES {
+            for attributes in copy_all_attributes(spec)? {
+                inventory.push(InventoryItem {
+                    class: spec.name.to_owned(),
+                    label: items::text(&attributes, items::attr::LABEL),
+                    service: spec.service(&attributes),
+                    account: spec.account(&attributes),
+                    modified_at: spec.resolve(&attributes).modified_at,
+                });
+            }
+        }
+
+        Ok(inventory)
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::Keychain
+    }
+}
+
+/// Fetches the attributes of every item of a class. Data is never asked for, even when the
+/// class isn't secret, since the attributes are enough to notice changes.
+fn copy_all_attributes(spec: &ClassSpec) -> Result<Vec<Attributes>, String> {
+    let query = unsafe {
+        CFDictionary::from_CFType_pairs(&[
+            (
+                CFString::wrap_under_get_rule(kSecClass),
+                CFType::wrap_under_get_rule(items::keychain::sec_class(spec.class).cast()),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecReturnAttributes),
+                CFBoolean::true_value().as_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecMatchLimit),
+                CFType::wrap_under_get_rule(bindings::kSecMatchLimitAll.cast()),
+            ),
+        ])
+    };
+
+    let mut result = std::ptr::null();
+
+    let status = unsafe { bindings::SecItemCopyMatching(query.as_concrete_TypeRef(), &mut result) };
+
+    match status {
+        0 => {
+            let found: CFArray<*const c_void> =
+                unsafe { CFArray::wrap_under_create_rule(result.cast()) };
+
+            Ok(found
+                .iter()
+                .map(|attributes| {
+                    let attributes: CFDictionary<CFString, *const c_void> =
+                        unsafe { CFDictionary::wrap_under_get_rule((*attributes).cast()) };
+
+                    items::keychain::to_attributes(&attributes)
+                })
+                .collect())
+        }
+        errSecItemNotFound => Ok(Vec::new()),
+        code => Err(format!("keychain error {code}")),
+    }
+}
@@ -0,0 +1,41 @@
+use super::{InventoryItem, InventorySource};
+use crate::{
+    dbus::Connection,
+    events::Backend,
+    items::{self, secret_service},
+};
+
+/// Lists every item in every Secret Service collection.
+pub struct SecretServiceInventory;
+
+impl InventorySource for SecretServiceInventory {
+    fn scan(&mut self) -> Result<Vec<InventoryItem>, String> {
+        let mut connection = Connection::session().map_err(|e| e.to_string())?;
+        let mut inventory = Vec::new();
+
+        for path in secret_service::all_items(&mut connection).map_err(|e| e.to_string())? {
+            // Items can disappear between being listed and being read.
+            let (spec, attributes) = match secret_service::read_item(&mut connection, &path) {
+                Ok(item) => item,
+                Err(e) => {
+                    log::debug!("skipping item {path}: {e}");
+                    continue;
+                }
+            };
+
+            inventory.push(InventoryItem {
+                class: spec.name.to_owned(),
+                label: items::text(&attributes, items::attr::LABEL),
+                service: spec.service(&attributes),
+                account: spec.account(&attributes),
+                modified_at: spec.resolve(&attributes).modified_at,
+            });
+        }
+
+        Ok(inventory)
+    }
+
+    fn backend(&self) -> Backend {
+        Backend::SecretService
+    }
+}
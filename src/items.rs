@@ -0,0 +1,791 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{baseline::wildcard_match, clock, events::FilteredEventData};
+
+mod base64;
+pub mod credential_files;
+#[cfg(target_os = "linux")]
+pub mod gpg_agent;
+#[cfg(target_os = "macos")]
+pub mod keychain;
+#[cfg(target_os = "linux")]
+pub mod keyring;
+#[cfg(target_os = "linux")]
+pub mod keystore_files;
+#[cfg(target_os = "linux")]
+pub mod kwallet;
+#[cfg(target_os = "linux")]
+pub mod secret_service;
+#[cfg(target_os = "linux")]
+pub mod ssh_agent;
+pub mod x509;
+
+/// Names of the attributes items are described with.
+///
+/// These match the raw keys the macOS keychain uses, so its attribute dictionaries can be
+/// used as-is. Other backends translate their own attributes into these.
+pub mod attr {
+    pub const LABEL: &str = "labl";
+    pub const SERVICE: &str = "svce";
+    pub const ACCOUNT: &str = "acct";
+    pub const SERVER: &str = "srvr";
+    pub const MODIFIED_AT: &str = "mdat";
+    pub const CREATED_AT: &str = "cdat";
+    pub const PORT: &str = "port";
+    pub const PROTOCOL: &str = "ptcl";
+    pub const PATH: &str = "path";
+    pub const ACCESS_GROUP: &str = "agrp";
+    pub const CREATOR: &str = "crtr";
+    pub const KEY_TYPE: &str = "type";
+    pub const KEY_SIZE: &str = "bsiz";
+    pub const KEY_CLASS: &str = "kcls";
+    pub const SUBJECT: &str = "subj";
+    pub const ISSUER: &str = "issr";
+    pub const SERIAL_NUMBER: &str = "slnr";
+    /// When a certificate starts and stops being valid. Keystores don't store these, so they
+    /// only come from parsing the certificate itself.
+    pub const VALID_FROM: &str = "x_vfrm";
+    pub const VALID_UNTIL: &str = "x_vtil";
+    /// The item's data. Only ever fetched for certificates, which aren't secret.
+    pub const DATA: &str = "v_Data";
+}
+
+/// One attribute of an item, independent of how the backend stored it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeValue {
+    Text(String),
+    Number(i64),
+    Data(Vec<u8>),
+    /// A Core Foundation absolute time.
+    Date(f64),
+}
+
+pub type Attributes = HashMap<String, AttributeValue>;
+
+/// The kinds of items a keystore can hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemClass {
+    GenericPassword,
+    InternetPassword,
+    Certificate,
+    Key,
+    Identity,
+}
+
+/// How an attribute gets turned into something readable.
+#[derive(Clone, Copy, Debug)]
+enum Render {
+    Number,
+    Hex,
+    KeyType,
+    KeyClass,
+    /// The common name out of a DER encoded X.500 name.
+    CommonName,
+    /// The SHA-256 of the attribute's data.
+    Fingerprint,
+    /// The day a Core Foundation absolute time falls on.
+    Date,
+}
+
+/// Everything that's needed to make sense of one item class.
+pub struct ClassSpec {
+    // Only the keychain is searched by class.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub class: ItemClass,
+    /// What the class is called in inventories and journals.
+    pub name: &'static str,
+    /// What the class is called in notifications.
+    pub noun: &'static str,
+    /// Attributes to try, in order, for the name the item is reported with.
+    title_attrs: &'static [&'static str],
+    pub service_attr: Option<&'static str>,
+    pub account_attr: Option<&'static str>,
+    /// If the item's data has to be fetched to describe it. This must never be true for
+    /// classes where the data is secret.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub needs_data: bool,
+    /// Class-specific attributes shown with events, along with what they're called.
+    details: &'static [(&'static str, &'static str, Render)],
+}
+
+/// Every supported item class, in the order items are matched against them.
+pub const CLASSES: &[ClassSpec] = &[
+    ClassSpec {
+        class: ItemClass::GenericPassword,
+        name: "generic_password",
+        noun: "item",
+        title_attrs: &[attr::LABEL, attr::SERVICE],
+        service_attr: Some(attr::SERVICE),
+        account_attr: Some(attr::ACCOUNT),
+        needs_data: false,
+        details: &[],
+    },
+    ClassSpec {
+        class: ItemClass::InternetPassword,
+        name: "internet_password",
+        noun: "item",
+        title_attrs: &[attr::LABEL, attr::SERVER],
+        service_attr: Some(attr::SERVER),
+        account_attr: Some(attr::ACCOUNT),
+        needs_data: false,
+        details: &[],
+    },
+    ClassSpec {
+        class: ItemClass::Certificate,
+        name: "certificate",
+        noun: "certificate",
+        title_attrs: &[attr::LABEL],
+        service_attr: None,
+        account_attr: None,
+        needs_data: true,
+        details: &[
+            (attr::SUBJECT, "subject", Render::CommonName),
+            (attr::ISSUER, "issuer", Render::CommonName),
+            (attr::SERIAL_NUMBER, "serial", Render::Hex),
+            (attr::DATA, "sha256", Render::Fingerprint),
+            (attr::VALID_FROM, "valid from", Render::Date),
+            (attr::VALID_UNTIL, "valid until", Render::Date),
+        ],
+    },
+    ClassSpec {
+        class: ItemClass::Key,
+        name: "key",
+        noun: "key",
+        title_attrs: &[attr::LABEL],
+        service_attr: None,
+        account_attr: None,
+        needs_data: false,
+        details: &[
+            (attr::KEY_CLASS, "key class", Render::KeyClass),
+            (attr::KEY_TYPE, "key type", Render::KeyType),
+            (attr::KEY_SIZE, "key size", Render::Number),
+        ],
+    },
+    ClassSpec {
+        class: ItemClass::Identity,
+        name: "identity",
+        noun: "identity",
+        title_attrs: &[attr::LABEL],
+        service_attr: None,
+        account_attr: None,
+        needs_data: false,
+        details: &[
+            (attr::SUBJECT, "subject", Render::CommonName),
+            (attr::ISSUER, "issuer", Render::CommonName),
+            (attr::KEY_TYPE, "key type", Render::KeyType),
+            (attr::KEY_SIZE, "key size", Render::Number),
+        ],
+    },
+];
+
+/// Looks up a class by the name it's stored under.
+pub fn spec_named(name: &str) -> Option<&'static ClassSpec> {
+    CLASSES.iter().find(|spec| spec.name == name)
+}
+
+/// A class-specific attribute, rendered for people.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ItemDetail {
+    pub name: String,
+    pub value: String,
+}
+
+/// Attributes that tell apart items with the same title, like several `github.com` logins.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ItemAttributes {
+    pub service: Option<String>,
+    pub account: Option<String>,
+    pub server: Option<String>,
+    pub port: Option<u16>,
+    pub protocol: Option<String>,
+    pub path: Option<String>,
+    pub access_group: Option<String>,
+    /// When the item was created, as a Core Foundation absolute time.
+    pub created_at: Option<f64>,
+    /// The four character code of the app that created the item.
+    pub creator: Option<String>,
+}
+
+impl ItemAttributes {
+    fn from_attributes(attributes: &Attributes) -> Self {
+        Self {
+            service: text(attributes, attr::SERVICE),
+            account: text(attributes, attr::ACCOUNT),
+            server: text(attributes, attr::SERVER),
+            port: match attributes.get(attr::PORT) {
+                // Zero means no port was given.
+                Some(AttributeValue::Number(port)) if *port != 0 => u16::try_from(*port).ok(),
+                _ => None,
+            },
+            protocol: match attributes.get(attr::PROTOCOL) {
+                Some(AttributeValue::Text(protocol)) => Some(protocol_name(protocol)),
+                Some(AttributeValue::Number(code)) => {
+                    four_char_code(*code).map(|p| protocol_name(&p))
+                }
+                _ => None,
+            },
+            path: text(attributes, attr::PATH),
+            access_group: text(attributes, attr::ACCESS_GROUP),
+            created_at: match attributes.get(attr::CREATED_AT) {
+                Some(AttributeValue::Date(at)) => Some(*at),
+                _ => None,
+            },
+            creator: match attributes.get(attr::CREATOR) {
+                Some(AttributeValue::Number(code)) => four_char_code(*code),
+                Some(AttributeValue::Text(code)) if !code.is_empty() => Some(code.clone()),
+                _ => None,
+            },
+        }
+    }
+
+    /// Every attribute that's known, like `account fox`, for showing to people.
+    pub fn describe(&self) -> Vec<String> {
+        let mut described = Vec::new();
+
+        let text_fields = [
+            ("account", &self.account),
+            ("service", &self.service),
+            ("server", &self.server),
+            ("protocol", &self.protocol),
+            ("path", &self.path),
+            ("access group", &self.access_group),
+            ("creator", &self.creator),
+        ];
+
+        for (name, value) in text_fields {
+            if let Some(value) = value {
+                described.push(format!("{name} {value}"));
+            }
+        }
+
+        if let Some(port) = self.port {
+            described.push(format!("port {port}"));
+        }
+
+        described
+    }
+}
+
+/// What an item's attributes say about it.
+#[derive(Debug)]
+pub struct ResolvedItem {
+    pub title: String,
+    /// When the item was last modified, as a Core Foundation absolute time.
+    pub modified_at: Option<f64>,
+    pub attributes: ItemAttributes,
+    pub details: Vec<ItemDetail>,
+}
+
+impl ClassSpec {
+    /// Turns a backend's attributes for an item of this class into the common model.
+    pub fn resolve(&self, attributes: &Attributes) -> ResolvedItem {
+        let title = self
+            .title_attrs
+            .iter()
+            .find_map(|attr| text(attributes, attr))
+            .unwrap_or_else(|| String::from("Unknown"));
+
+        let details = self
+            .details
+            .iter()
+            .filter_map(|(attr, name, render)| {
+                let value = render.apply(attributes.get(*attr)?)?;
+                Some(ItemDetail {
+                    name: (*name).to_owned(),
+                    value,
+                })
+            })
+            .collect();
+
+        ResolvedItem {
+            title,
+            modified_at: match attributes.get(attr::MODIFIED_AT) {
+                Some(AttributeValue::Date(at)) => Some(*at),
+                _ => None,
+            },
+            attributes: ItemAttributes::from_attributes(attributes),
+            details,
+        }
+    }
+
+    pub fn service(&self, attributes: &Attributes) -> Option<String> {
+        text(attributes, self.service_attr?)
+    }
+
+    pub fn account(&self, attributes: &Attributes) -> Option<String> {
+        text(attributes, self.account_attr?)
+    }
+}
+
+/// An attribute as text, skipping anything that isn't.
+pub fn text(attributes: &Attributes, attr: &str) -> Option<String> {
+    match attributes.get(attr)? {
+        AttributeValue::Text(text) if !text.is_empty() => Some(text.clone()),
+        // Some apps store text attributes as raw bytes.
+        AttributeValue::Data(data) => String::from_utf8(data.clone())
+            .ok()
+            .filter(|text| !text.is_empty()),
+        _ => None,
+    }
+}
+
+impl Render {
+    fn apply(self, value: &AttributeValue) -> Option<String> {
+        match (self, value) {
+            (Render::Number, AttributeValue::Number(number)) => Some(number.to_string()),
+            (Render::Number, AttributeValue::Text(text)) => Some(text.clone()),
+            (Render::Hex, AttributeValue::Data(data)) => Some(hex(data)),
+            (Render::KeyType, AttributeValue::Text(key_type)) => {
+                Some(key_type_name(key_type).to_owned())
+            }
+            (Render::KeyType, AttributeValue::Number(key_type)) => {
+                Some(key_type_name(&key_type.to_string()).to_owned())
+            }
+            (Render::KeyClass, AttributeValue::Text(key_class)) => {
+                Some(key_class_name(key_class).to_owned())
+            }
+            (Render::KeyClass, AttributeValue::Number(key_class)) => {
+                Some(key_class_name(&key_class.to_string()).to_owned())
+            }
+            (Render::CommonName, AttributeValue::Data(name)) => common_name(name),
+            (Render::Fingerprint, AttributeValue::Data(data)) => Some(hex(&Sha256::digest(data))),
+            (Render::Date, AttributeValue::Date(at)) => Some(clock::format_date(*at)),
+            _ => None,
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+/// Names the key algorithm, going by the values of the `kSecAttrKeyType*` constants.
+fn key_type_name(key_type: &str) -> &str {
+    match key_type {
+        "42" => "RSA",
+        "43" => "DSA",
+        "73" => "EC",
+        "2147483649" => "AES",
+        other => other,
+    }
+}
+
+/// Names the kind of key, going by the values of the `kSecAttrKeyClass*` constants.
+fn key_class_name(key_class: &str) -> &str {
+    match key_class {
+        "0" => "public",
+        "1" => "private",
+        "2" => "symmetric",
+        other => other,
+    }
+}
+
+/// Decodes a four character code, like the `htps` protocol or an app's creator code.
+fn four_char_code(code: i64) -> Option<String> {
+    let bytes = u32::try_from(code).ok()?.to_be_bytes();
+
+    bytes
+        .iter()
+        .all(|byte| byte.is_ascii_graphic() || *byte == b' ')
+        .then(|| String::from_utf8_lossy(&bytes).trim_end().to_owned())
+}
+
+/// Names the protocol, going by the values of the `kSecAttrProtocol*` constants.
+fn protocol_name(protocol: &str) -> String {
+    let name = match protocol.trim_end() {
+        "htps" => "https",
+        "ftps" => "ftps",
+        "imps" => "imaps",
+        "pops" => "pop3s",
+        "ldps" => "ldaps",
+        "smb" => "smb",
+        "afp" => "afp",
+        other => other,
+    };
+
+    name.to_owned()
+}
+
+/// Finds the common name in a DER encoded X.500 name, without fully parsing it.
+fn common_name(name: &[u8]) -> Option<String> {
+    // The object identifier of the common name attribute, 2.5.4.3.
+    const CN_OID: [u8; 5] = [0x06, 0x03, 0x55, 0x04, 0x03];
+
+    let start = name.windows(CN_OID.len()).position(|w| w == CN_OID)? + CN_OID.len();
+
+    // The value comes right after as a string type tag, with a length that's short for any sane name.
+    let (_tag, len) = (name.get(start)?, *name.get(start + 1)? as usize);
+    if len >= 0x80 {
+        return None;
+    }
+
+    let value = name.get(start + 2..start + 2 + len)?;
+    String::from_utf8(value.to_vec()).ok()
+}
+
+/// A rule that picks out items by their attributes.
+///
+/// Every field that's set has to match, and can use `*` to stand for any run of characters.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ItemMatch {
+    /// The item's title.
+    pub(crate) item: Option<String>,
+    /// The item's class, like `internet_password` or `certificate`.
+    pub(crate) class: Option<String>,
+    pub(crate) service: Option<String>,
+    pub(crate) account: Option<String>,
+    pub(crate) server: Option<String>,
+    pub(crate) port: Option<u16>,
+    pub(crate) protocol: Option<String>,
+    pub(crate) path: Option<String>,
+    pub(crate) access_group: Option<String>,
+    pub(crate) creator: Option<String>,
+}
+
+impl ItemMatch {
+    pub(crate) fn matches(&self, event: &FilteredEventData) -> bool {
+        fn field(pattern: &Option<String>, value: Option<&str>) -> bool {
+            match (pattern, value) {
+                (None, _) => true,
+                (Some(pattern), Some(value)) => wildcard_match(pattern, value),
+                (Some(_), None) => false,
+            }
+        }
+
+        let attributes = event.attributes();
+        let attribute = |get: fn(&ItemAttributes) -> &Option<String>| {
+            attributes.and_then(|attributes| get(attributes).as_deref())
+        };
+
+        field(&self.item, event.item_title())
+            && field(&self.class, event.item_class())
+            && field(&self.service, attribute(|a| &a.service))
+            && field(&self.account, attribute(|a| &a.account))
+            && field(&self.server, attribute(|a| &a.server))
+            && field(&self.protocol, attribute(|a| &a.protocol))
+            && field(&self.path, attribute(|a| &a.path))
+            && field(&self.access_group, attribute(|a| &a.access_group))
+            && field(&self.creator, attribute(|a| &a.creator))
+            && self
+                .port
+                .is_none_or(|port| attributes.and_then(|a| a.port) == Some(port))
+    }
+}
+
+/// An entry in `ignored_items`, either an item title or a rule matching on its attributes.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum IgnoredItem {
+    Title(String),
+    Match(ItemMatch),
+}
+
+impl IgnoredItem {
+    pub(crate) fn matches(&self, event: &FilteredEventData) -> bool {
+        match self {
+            IgnoredItem::Title(title) => event.item_title().unwrap_or("Unknown") == title,
+            IgnoredItem::Match(rule) => rule.matches(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{Backend, InnerDetails},
+        process::ProcessInfo,
+    };
+
+    /// A self-signed certificate made with `openssl req -x509` for these tests.
+    const TEST_ROOT: &[u8] = include_bytes!("../tests/fixtures/test-root.der");
+
+    fn attributes(pairs: &[(&str, AttributeValue)]) -> Attributes {
+        pairs
+            .iter()
+            .map(|(attr, value)| ((*attr).to_owned(), value.clone()))
+            .collect()
+    }
+
+    fn string(value: &str) -> AttributeValue {
+        AttributeValue::Text(value.to_owned())
+    }
+
+    fn details(resolved: &ResolvedItem) -> Vec<(&str, &str)> {
+        resolved
+            .details
+            .iter()
+            .map(|detail| (detail.name.as_str(), detail.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn resolves_internet_passwords() {
+        // As `SecItemCopyMatching` returns a Safari login, with its four character codes as numbers.
+        let recorded = attributes(&[
+            (attr::LABEL, string("github.com (fox)")),
+            (attr::SERVER, string("github.com")),
+            (attr::ACCOUNT, string("fox")),
+            (attr::PORT, AttributeValue::Number(0)),
+            (attr::PROTOCOL, AttributeValue::Number(0x6874_7073)),
+            (attr::PATH, string("")),
+            (attr::CREATOR, AttributeValue::Number(0x6170_706c)),
+            (attr::MODIFIED_AT, AttributeValue::Date(719_000_000.0)),
+            (attr::CREATED_AT, AttributeValue::Date(718_000_000.0)),
+        ]);
+
+        let spec = spec_named("internet_password").unwrap();
+        let resolved = spec.resolve(&recorded);
+
+        assert_eq!(resolved.title, "github.com (fox)");
+        assert_eq!(resolved.modified_at, Some(719_000_000.0));
+        assert_eq!(
+            resolved.attributes,
+            ItemAttributes {
+                server: Some(String::from("github.com")),
+                account: Some(String::from("fox")),
+                protocol: Some(String::from("https")),
+                created_at: Some(718_000_000.0),
+                creator: Some(String::from("appl")),
+                ..ItemAttributes::default()
+            }
+        );
+        assert!(resolved.details.is_empty());
+        assert_eq!(spec.service(&recorded).as_deref(), Some("github.com"));
+    }
+
+    #[test]
+    fn resolves_generic_passwords_without_a_label() {
+        let recorded = attributes(&[
+            (
+                attr::SERVICE,
+                AttributeValue::Data(b"com.apple.ids".to_vec()),
+            ),
+            (attr::ACCOUNT, string("IDSAccountKey")),
+            (attr::ACCESS_GROUP, string("com.apple.ids")),
+        ]);
+
+        let resolved = spec_named("generic_password").unwrap().resolve(&recorded);
+
+        assert_eq!(resolved.title, "com.apple.ids");
+        assert_eq!(resolved.modified_at, None);
+        assert_eq!(
+            resolved.attributes.access_group.as_deref(),
+            Some("com.apple.ids")
+        );
+    }
+
+    #[test]
+    fn resolves_keys() {
+        // `kSecAttrKeyTypeAES` and `kSecAttrKeyClassSymmetric`, as the strings the keychain has them as.
+        let aes = attributes(&[
+            (attr::LABEL, string("FileVault Recovery")),
+            (attr::KEY_TYPE, string("2147483649")),
+            (attr::KEY_CLASS, string("2")),
+            (attr::KEY_SIZE, AttributeValue::Number(256)),
+        ]);
+        // `kSecAttrKeyTypeRSA` and `kSecAttrKeyClassPrivate`, as numbers.
+        let rsa = attributes(&[
+            (attr::LABEL, string("Apple Development")),
+            (attr::KEY_TYPE, AttributeValue::Number(42)),
+            (attr::KEY_CLASS, AttributeValue::Number(1)),
+            (attr::KEY_SIZE, AttributeValue::Number(2048)),
+        ]);
+
+        let spec = spec_named("key").unwrap();
+
+        assert_eq!(
+            details(&spec.resolve(&aes)),
+            [
+                ("key class", "symmetric"),
+                ("key type", "AES"),
+                ("key size", "256")
+            ]
+        );
+        assert_eq!(
+            details(&spec.resolve(&rsa)),
+            [
+                ("key class", "private"),
+                ("key type", "RSA"),
+                ("key size", "2048")
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_certificates() {
+        let mut recorded = x509::to_attributes(TEST_ROOT).expect("fixture is a certificate");
+        // The keychain has its own label for certificates, which wins over the common name.
+        recorded.insert(attr::LABEL.to_owned(), string("Keeper Test Root"));
+
+        let resolved = spec_named("certificate").unwrap().resolve(&recorded);
+
+        assert_eq!(resolved.title, "Keeper Test Root");
+        assert_eq!(
+            details(&resolved),
+            [
+                ("subject", "Keeper Test Root"),
+                ("issuer", "Keeper Test Root"),
+                ("serial", "1f2e3d"),
+                (
+                    "sha256",
+                    "227b243c9c63864b919595f3e38ec48dce16ee753b625b8c9f341ed7081a4db1"
+                ),
+                ("valid from", "2024-01-01"),
+                ("valid until", "2034-01-01"),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_identities() {
+        let certificate = x509::to_attributes(TEST_ROOT).expect("fixture is a certificate");
+
+        // Identities have the certificate's names along with the key's attributes.
+        let mut recorded = attributes(&[
+            (attr::LABEL, string("Keeper Test Root")),
+            (attr::KEY_TYPE, string("73")),
+            (attr::KEY_SIZE, AttributeValue::Number(256)),
+        ]);
+        recorded.insert(attr::SUBJECT.to_owned(), certificate[attr::SUBJECT].clone());
+        recorded.insert(attr::ISSUER.to_owned(), certificate[attr::ISSUER].clone());
+
+        let resolved = spec_named("identity").unwrap().resolve(&recorded);
+
+        assert_eq!(
+            details(&resolved),
+            [
+                ("subject", "Keeper Test Root"),
+                ("issuer", "Keeper Test Root"),
+                ("key type", "EC"),
+                ("key size", "256"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_attributes_of_the_wrong_type() {
+        let recorded = attributes(&[
+            (attr::LABEL, AttributeValue::Number(7)),
+            (attr::KEY_SIZE, AttributeValue::Data(vec![1, 0])),
+            (attr::PORT, AttributeValue::Number(100_000)),
+        ]);
+
+        let resolved = spec_named("key").unwrap().resolve(&recorded);
+
+        assert_eq!(resolved.title, "Unknown");
+        assert!(resolved.details.is_empty());
+        assert_eq!(resolved.attributes.port, None);
+    }
+
+    /// The `ignored_items` of a config.
+    fn ignored_items(config: &str) -> Result<Vec<IgnoredItem>, toml::de::Error> {
+        #[derive(Deserialize)]
+        struct Config {
+            ignored_items: Vec<IgnoredItem>,
+        }
+
+        toml::from_str::<Config>(config).map(|config| config.ignored_items)
+    }
+
+    fn ignored(rules: &[IgnoredItem], event: &FilteredEventData) -> bool {
+        rules.iter().any(|rule| rule.matches(event))
+    }
+
+    /// A login for `account` on `server`, added over `protocol`.
+    fn login(server: &str, account: &str, protocol: &str, port: u16) -> FilteredEventData {
+        FilteredEventData::Added(
+            InnerDetails::new(
+                Backend::Keychain,
+                server.to_owned(),
+                0.0,
+                ProcessInfo::unknown(100),
+            )
+            .with_class(
+                "internet_password",
+                ItemAttributes {
+                    server: Some(server.to_owned()),
+                    account: Some(account.to_owned()),
+                    protocol: Some(protocol.to_owned()),
+                    port: Some(port),
+                    ..ItemAttributes::default()
+                },
+                Vec::new(),
+            ),
+        )
+    }
+
+    #[test]
+    fn ignores_items_by_title() {
+        let rules = ignored_items(r#"ignored_items = ["Wi-Fi", "Unknown"]"#).unwrap();
+
+        let wifi = FilteredEventData::Updated(InnerDetails::new(
+            Backend::Keychain,
+            String::from("Wi-Fi"),
+            0.0,
+            ProcessInfo::unknown(100),
+        ));
+        let removal = FilteredEventData::Removed {
+            backend: Backend::Keychain,
+            seen_at: 0.0,
+            modified_by: ProcessInfo::unknown(100),
+            item_name: None,
+        };
+
+        assert!(ignored(&rules, &wifi));
+        // Removals that don't say what was removed go by `Unknown`.
+        assert!(ignored(&rules, &removal));
+        assert!(!ignored(&rules, &login("wi-fi", "fox", "https", 443)));
+    }
+
+    #[test]
+    fn ignores_items_by_their_attributes() {
+        let rules = ignored_items(
+            r#"ignored_items = [
+                { server = "*.corp.example.com", account = "svc-*" },
+                { class = "internet_password", protocol = "smb", port = 445 },
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(ignored(
+            &rules,
+            &login("build.corp.example.com", "svc-ci", "https", 443)
+        ));
+        // Every field that's set has to match.
+        assert!(!ignored(
+            &rules,
+            &login("build.corp.example.com", "fox", "https", 443)
+        ));
+        assert!(!ignored(
+            &rules,
+            &login("corp.example.com.evil", "svc-ci", "https", 443)
+        ));
+
+        assert!(ignored(&rules, &login("nas.local", "fox", "smb", 445)));
+        assert!(!ignored(&rules, &login("nas.local", "fox", "smb", 139)));
+
+        // Removals have no attributes to match.
+        let removal = FilteredEventData::Removed {
+            backend: Backend::Keychain,
+            seen_at: 0.0,
+            modified_by: ProcessInfo::unknown(100),
+            item_name: Some(String::from("nas.local")),
+        };
+        assert!(!ignored(&rules, &removal));
+    }
+
+    #[test]
+    fn refuses_misspelled_rules() {
+        assert!(ignored_items(r#"ignored_items = [{ sever = "example.com" }]"#).is_err());
+        assert!(ignored_items(r#"ignored_items = [{ port = "https" }]"#).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes the way fingerprints show them, without any padding.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - i * 8)
+        });
+
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3f] as char);
+        }
+    }
+
+    encoded
+}
+
+/// Decodes base64, with or without padding. Anything else in the text, whitespace included,
+/// makes it undecodable.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut bits, mut bit_count) = (0u32, 0);
+
+    for c in text.bytes().take_while(|c| *c != b'=') {
+        let value = ALPHABET.iter().position(|a| *a == c)?;
+
+        bits = bits << 6 | value as u32;
+        bit_count += 6;
+
+        if bit_count >= 8 {
+            bit_count -= 8;
+            decoded.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_without_padding() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"Ke"), "S2U");
+        assert_eq!(encode(b"Keep"), "S2VlcA");
+        assert_eq!(encode(b"Keeper"), "S2VlcGVy");
+        assert_eq!(encode(&[0xfb, 0xff]), "+/8");
+    }
+
+    #[test]
+    fn decodes_with_or_without_padding() {
+        assert_eq!(decode("S2VlcGVy").as_deref(), Some(&b"Keeper"[..]));
+        assert_eq!(decode("S2VlcA==").as_deref(), Some(&b"Keep"[..]));
+        assert_eq!(decode("S2VlcA").as_deref(), Some(&b"Keep"[..]));
+        assert_eq!(decode("S2U=").as_deref(), Some(&b"Ke"[..]));
+        assert_eq!(decode("+/8").as_deref(), Some(&[0xfb, 0xff][..]));
+        assert_eq!(decode("S2Vl cA=="), None);
+        assert_eq!(decode("S2Vl-A=="), None);
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let bytes: Vec<u8> = (0..=255).collect();
+
+        for len in 0..8 {
+            assert_eq!(decode(&encode(&bytes[len..])), Some(bytes[len..].to_vec()));
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use super::{attr, spec_named, AttributeValue, Attributes, ClassSpec, CLASSES};
+
+/// What everything the agent holds is reported as being in.
+const ACCESS_GROUP: &str = "gpg-agent";
+
+/// Translates a private key the agent holds into the common attribute model. Keys go by the
+/// user ID they belong to when the client said, since the agent only knows them by keygrip.
+pub fn key_to_attributes(keygrip: &str, user_id: Option<&str>) -> (&'static ClassSpec, Attributes) {
+    let mut attributes = Attributes::new();
+    let mut insert = |attr: &str, value: &str| {
+        attributes.insert(attr.to_owned(), AttributeValue::Text(value.to_owned()));
+    };
+
+    insert(attr::LABEL, user_id.unwrap_or(keygrip));
+    insert(attr::ACCESS_GROUP, ACCESS_GROUP);
+    // The agent only ever holds private keys. This is `kSecAttrKeyClassPrivate`.
+    attributes.insert(attr::KEY_CLASS.to_owned(), AttributeValue::Number(1));
+
+    (spec_named("key").unwrap_or(&CLASSES[0]), attributes)
+}
+
+/// Translates a passphrase the agent caches, like one for symmetric encryption, into the common
+/// attribute model, by the ID it's cached under.
+pub fn passphrase_to_attributes(
+    cache_id: &str,
+    user_id: Option<&str>,
+) -> (&'static ClassSpec, Attributes) {
+    let mut attributes = Attributes::new();
+    let mut insert = |attr: &str, value: &str| {
+        attributes.insert(attr.to_owned(), AttributeValue::Text(value.to_owned()));
+    };
+
+    insert(attr::LABEL, user_id.unwrap_or(cache_id));
+    insert(attr::SERVICE, ACCESS_GROUP);
+    insert(attr::ACCOUNT, cache_id);
+    insert(attr::ACCESS_GROUP, ACCESS_GROUP);
+
+    (
+        spec_named("generic_password").unwrap_or(&CLASSES[0]),
+        attributes,
+    )
+}
@@ -0,0 +1,60 @@
+use core::ffi::c_void;
+use core_foundation::{
+    base::{CFType, TCFType},
+    boolean::CFBoolean,
+    data::CFData,
+    date::CFDate,
+    dictionary::CFDictionary,
+    number::CFNumber,
+    string::{CFString, CFStringRef},
+};
+use security_framework_sys::item::{
+    kSecClassCertificate, kSecClassGenericPassword, kSecClassIdentity, kSecClassInternetPassword,
+    kSecClassKey,
+};
+
+use super::{AttributeValue, Attributes, ItemClass};
+
+/// The `kSecClass` value for `class`.
+pub fn sec_class(class: ItemClass) -> CFStringRef {
+    unsafe {
+        match class {
+            ItemClass::GenericPassword => kSecClassGenericPassword,
+            ItemClass::InternetPassword => kSecClassInternetPassword,
+            ItemClass::Certificate => kSecClassCertificate,
+            ItemClass::Key => kSecClassKey,
+            ItemClass::Identity => kSecClassIdentity,
+        }
+    }
+}
+
+/// Translates an attribute dictionary from `SecItemCopyMatching` into the common model.
+///
+/// Anything that isn't text, a number, data or a date is left out.
+pub fn to_attributes(dict: &CFDictionary<CFString, *const c_void>) -> Attributes {
+    let (keys, values) = dict.get_keys_and_values();
+
+    keys.into_iter()
+        .zip(values)
+        .filter_map(|(key, value)| {
+            let key = unsafe { CFString::wrap_under_get_rule(key.cast()) }.to_string();
+            let value = unsafe { CFType::wrap_under_get_rule(value) };
+
+            let value = if let Some(text) = value.downcast::<CFString>() {
+                AttributeValue::Text(text.to_string())
+            } else if let Some(number) = value.downcast::<CFNumber>() {
+                AttributeValue::Number(number.to_i64()?)
+            } else if let Some(boolean) = value.downcast::<CFBoolean>() {
+                AttributeValue::Number(i64::from(bool::from(boolean)))
+            } else if let Some(data) = value.downcast::<CFData>() {
+                AttributeValue::Data(data.bytes().to_vec())
+            } else if let Some(date) = value.downcast::<CFDate>() {
+                AttributeValue::Date(date.abs_time())
+            } else {
+                return None;
+            };
+
+            Some((key, value))
+        })
+        .collect()
+}
@@ -0,0 +1,128 @@
+use std::io;
+
+use super::{attr, spec_named, AttributeValue, Attributes, ClassSpec, CLASSES};
+
+/// The special ids for the calling process' keyrings, from `linux/keyctl.h`.
+pub const SESSION_KEYRING: i32 = -3;
+pub const USER_KEYRING: i32 = -4;
+const PROCESS_KEYRING: i32 = -2;
+
+const KEYCTL_GET_KEYRING_ID: libc::c_ulong = 0;
+const KEYCTL_DESCRIBE: libc::c_ulong = 6;
+const KEYCTL_READ: libc::c_ulong = 11;
+const KEYCTL_GET_PERSISTENT: libc::c_ulong = 22;
+pub const KEYCTL_WATCH_KEY: libc::c_ulong = 32;
+
+/// A key, or keyring, as the kernel describes it.
+#[derive(Clone, Debug)]
+pub struct Key {
+    pub key_type: String,
+    pub description: String,
+}
+
+impl Key {
+    pub fn is_keyring(&self) -> bool {
+        self.key_type == "keyring"
+    }
+}
+
+pub fn keyctl(
+    operation: libc::c_ulong,
+    arg2: libc::c_ulong,
+    arg3: libc::c_ulong,
+    arg4: libc::c_ulong,
+) -> io::Result<libc::c_long> {
+    let result = unsafe { libc::syscall(libc::SYS_keyctl, operation, arg2, arg3, arg4, 0) };
+
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(result)
+}
+
+/// Finds the real id behind one of the special keyring ids, without creating the keyring.
+pub fn keyring_id(special: i32) -> io::Result<i32> {
+    keyctl(KEYCTL_GET_KEYRING_ID, special as libc::c_ulong, 0, 0).map(|id| id as i32)
+}
+
+/// Finds the user's persistent keyring, which outlives their sessions.
+///
+/// The kernel only hands it out by linking it somewhere, so it's linked into this process' own
+/// keyring, which goes away with it.
+pub fn persistent_keyring() -> io::Result<i32> {
+    keyctl(
+        KEYCTL_GET_PERSISTENT,
+        // Minus one is the calling user.
+        -1i32 as libc::c_ulong,
+        PROCESS_KEYRING as libc::c_ulong,
+        0,
+    )
+    .map(|id| id as i32)
+}
+
+pub fn describe(key: i32) -> io::Result<Key> {
+    let buffer = read_buffer(KEYCTL_DESCRIBE, key)?;
+    let described = String::from_utf8_lossy(buffer.strip_suffix(&[0]).unwrap_or(&buffer));
+
+    // This is `type;uid;gid;perm;description`, where only the description can have semicolons.
+    let mut fields = described.splitn(5, ';');
+    match (fields.next(), fields.nth(3)) {
+        (Some(key_type), Some(description)) => Ok(Key {
+            key_type: key_type.to_owned(),
+            description: description.to_owned(),
+        }),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected description of key {key}: {described}"),
+        )),
+    }
+}
+
+/// Lists the ids of everything linked into a keyring.
+pub fn linked_keys(keyring: i32) -> io::Result<Vec<i32>> {
+    let buffer = read_buffer(KEYCTL_READ, keyring)?;
+
+    Ok(buffer
+        .chunks_exact(4)
+        .map(|id| i32::from_ne_bytes([id[0], id[1], id[2], id[3]]))
+        .collect())
+}
+
+/// Runs an operation that fills a buffer, which says how big it needs to be when it's too small.
+fn read_buffer(operation: libc::c_ulong, key: i32) -> io::Result<Vec<u8>> {
+    let mut buffer: Vec<u8> = Vec::new();
+
+    loop {
+        let needed = keyctl(
+            operation,
+            key as libc::c_ulong,
+            buffer.as_mut_ptr() as libc::c_ulong,
+            buffer.len() as libc::c_ulong,
+        )? as usize;
+
+        // Keyrings can grow between the calls.
+        if needed <= buffer.len() {
+            buffer.truncate(needed);
+            return Ok(buffer);
+        }
+
+        buffer.resize(needed, 0);
+    }
+}
+
+/// Translates a key into the common model. The keyring it's linked into is reported as its
+/// access group, since that's what decides which processes can find it.
+pub fn to_attributes(key: &Key, keyring: &str) -> (&'static ClassSpec, Attributes) {
+    let mut attributes = Attributes::new();
+
+    for (name, value) in [
+        (attr::LABEL, key.description.as_str()),
+        (attr::KEY_TYPE, key.key_type.as_str()),
+        (attr::ACCESS_GROUP, keyring),
+    ] {
+        attributes.insert(name.to_owned(), AttributeValue::Text(value.to_owned()));
+    }
+
+    (spec_named("key").unwrap_or(&CLASSES[0]), attributes)
+}
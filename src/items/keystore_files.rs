@@ -0,0 +1,32 @@
+use std::path::Path;
+
+use super::{attr, spec_named, AttributeValue, Attributes, ClassSpec, CLASSES};
+
+/// Describes a keystore's file in the common model. What's in it stays unknown, since only the
+/// file being opened is ever seen.
+pub fn to_attributes(keystore: Option<&str>, path: &Path) -> (&'static ClassSpec, Attributes) {
+    let mut attributes = Attributes::new();
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    attributes.insert(attr::LABEL.to_owned(), AttributeValue::Text(name));
+    attributes.insert(
+        attr::PATH.to_owned(),
+        AttributeValue::Text(path.to_string_lossy().into_owned()),
+    );
+
+    if let Some(keystore) = keystore {
+        attributes.insert(
+            attr::SERVICE.to_owned(),
+            AttributeValue::Text(keystore.to_owned()),
+        );
+    }
+
+    (
+        spec_named("generic_password").unwrap_or(&CLASSES[0]),
+        attributes,
+    )
+}
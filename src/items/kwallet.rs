@@ -0,0 +1,30 @@
+use super::{attr, spec_named, AttributeValue, Attributes, ClassSpec, CLASSES};
+
+/// The interface both kwalletd5 and kwalletd6 use.
+pub const INTERFACE: &str = "org.kde.KWallet";
+
+/// Describes a wallet entry, or a whole folder when there's no key, in the common model.
+///
+/// Entries are only known by where they are, since anything more would mean opening the wallet,
+/// which can ask the user for its password.
+pub fn to_attributes(
+    wallet: &str,
+    folder: &str,
+    key: Option<&str>,
+) -> (&'static ClassSpec, Attributes) {
+    let mut attributes = Attributes::new();
+
+    for (name, value) in [
+        (attr::LABEL, key.unwrap_or(folder)),
+        (attr::SERVICE, folder),
+        // Wallets decide who can unlock what, like collections in the Secret Service.
+        (attr::ACCESS_GROUP, wallet),
+    ] {
+        attributes.insert(name.to_owned(), AttributeValue::Text(value.to_owned()));
+    }
+
+    // Entries can also be maps or binary blobs, but only passwords are common.
+    let class = spec_named("generic_password").unwrap_or(&CLASSES[0]);
+
+    (class, attributes)
+}
@@ -0,0 +1,245 @@
+use std::io;
+
+use super::{attr, spec_named, AttributeValue, Attributes, ClassSpec, CLASSES};
+use crate::{
+    clock::unix_to_cf,
+    dbus::{Connection, Value},
+};
+
+pub const SERVICE_NAME: &str = "org.freedesktop.secrets";
+pub const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+pub const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+pub const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+pub const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+
+/// The schema libsecret uses for what the keychain calls internet passwords.
+const NETWORK_SCHEMA: &str = "org.gnome.keyring.NetworkPassword";
+
+/// Which common attribute each of the well known Secret Service lookup attributes fills in.
+const ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("service", attr::SERVICE),
+    ("username", attr::ACCOUNT),
+    ("user", attr::ACCOUNT),
+    ("account", attr::ACCOUNT),
+    ("server", attr::SERVER),
+    ("host", attr::SERVER),
+    ("protocol", attr::PROTOCOL),
+    ("object", attr::PATH),
+    ("path", attr::PATH),
+];
+
+/// Lists every item in every collection.
+pub fn all_items(connection: &mut Connection) -> io::Result<Vec<String>> {
+    let collections =
+        connection.get_property(SERVICE_NAME, SERVICE_PATH, SERVICE_INTERFACE, "Collections")?;
+
+    let mut items = Vec::new();
+
+    for collection in collections.as_array().unwrap_or_default() {
+        let collection = match collection.as_str() {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let found =
+            connection.get_property(SERVICE_NAME, collection, COLLECTION_INTERFACE, "Items")?;
+
+        items.extend(
+            found
+                .as_array()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_owned),
+        );
+    }
+
+    Ok(items)
+}
+
+/// Reads an item's properties. Its secret is never asked for.
+pub fn read_item(
+    connection: &mut Connection,
+    path: &str,
+) -> io::Result<(&'static ClassSpec, Attributes)> {
+    let properties = connection.get_all_properties(SERVICE_NAME, path, ITEM_INTERFACE)?;
+    Ok(to_attributes(path, &properties))
+}
+
+/// Translates an item's properties into the common model.
+pub fn to_attributes(path: &str, properties: &Value) -> (&'static ClassSpec, Attributes) {
+    let mut attributes = Attributes::new();
+
+    if let Some(label) = properties.dict_get("Label").and_then(Value::as_str) {
+        attributes.insert(
+            attr::LABEL.to_owned(),
+            AttributeValue::Text(label.to_owned()),
+        );
+    }
+
+    for (property, name) in [
+        ("Modified", attr::MODIFIED_AT),
+        ("Created", attr::CREATED_AT),
+    ] {
+        // Zero means the service doesn't keep track.
+        if let Some(at) = properties
+            .dict_get(property)
+            .and_then(Value::as_u64)
+            .filter(|at| *at != 0)
+        {
+            attributes.insert(name.to_owned(), AttributeValue::Date(unix_to_cf(at)));
+        }
+    }
+
+    // Collections are the closest thing to an access group, since they decide who can unlock what.
+    if let Some(collection) = path
+        .strip_prefix("/org/freedesktop/secrets/collection/")
+        .and_then(|rest| rest.split('/').next())
+    {
+        attributes.insert(
+            attr::ACCESS_GROUP.to_owned(),
+            AttributeValue::Text(collection.to_owned()),
+        );
+    }
+
+    let mut schema = None;
+
+    if let Some(lookup) = properties.dict_get("Attributes") {
+        for (key, value) in lookup.dict_entries() {
+            let (key, value) = match (key.as_str(), value.as_str()) {
+                (Some(key), Some(value)) => (key, value),
+                _ => continue,
+            };
+
+            if key == "xdg:schema" {
+                schema = Some(value.to_owned());
+            } else if key == "port" {
+                if let Ok(port) = value.parse() {
+                    attributes.insert(attr::PORT.to_owned(), AttributeValue::Number(port));
+                }
+            } else if let Some((_, name)) = ATTRIBUTE_NAMES.iter().find(|(known, _)| *known == key)
+            {
+                // The first of several names for the same thing wins.
+                attributes
+                    .entry((*name).to_owned())
+                    .or_insert_with(|| AttributeValue::Text(value.to_owned()));
+            }
+        }
+    }
+
+    let class = match schema.as_deref() {
+        Some(NETWORK_SCHEMA) => spec_named("internet_password"),
+        _ => spec_named("generic_password"),
+    };
+
+    (class.unwrap_or(&CLASSES[0]), attributes)
+}
+
+#[cfg(test)]
+pub mod testing {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::dbus::{testing, Message, PROPERTIES_INTERFACE};
+
+    pub const COLLECTION: &str = "/org/freedesktop/secrets/collection/login";
+
+    /// Just enough of a Secret Service to list, create, delete and read the items of one
+    /// collection. The secrets it hands out are always empty.
+    /// Every call it answers is passed along to `calls`.
+    pub fn mock_service(
+        mut connection: Connection,
+        mut items: Vec<(String, String)>,
+        calls: mpsc::Sender<String>,
+    ) {
+        let mut created = 0;
+
+        while let Ok(call) = testing::next_call(&mut connection) {
+            let path = call.path.clone().unwrap_or_default();
+            let member = call.member.clone().unwrap_or_default();
+            let mut signal = None;
+
+            let body = match (call.interface.as_deref(), member.as_str()) {
+                (Some(PROPERTIES_INTERFACE), "Get") if path == SERVICE_PATH => {
+                    vec![Value::variant(Value::Array(
+                        String::from("o"),
+                        vec![Value::ObjectPath(COLLECTION.to_owned())],
+                    ))]
+                }
+                (Some(PROPERTIES_INTERFACE), "Get") => vec![Value::variant(Value::Array(
+                    String::from("o"),
+                    items
+                        .iter()
+                        .map(|(item, _)| Value::ObjectPath(item.clone()))
+                        .collect(),
+                ))],
+                (Some(PROPERTIES_INTERFACE), "GetAll") => {
+                    match items.iter().find(|(item, _)| *item == path) {
+                        Some((_, label)) => vec![Value::dict(
+                            "s",
+                            "v",
+                            vec![(
+                                Value::Str(String::from("Label")),
+                                Value::variant(Value::Str(label.clone())),
+                            )],
+                        )],
+                        None => Vec::new(),
+                    }
+                }
+                (Some(SERVICE_INTERFACE), "GetSecrets") => {
+                    vec![Value::dict("o", "(oayays)", Vec::new())]
+                }
+                (Some(COLLECTION_INTERFACE), "CreateItem") => {
+                    let label = call
+                        .body
+                        .first()
+                        .and_then(|properties| {
+                            properties.dict_get("org.freedesktop.Secret.Item.Label")
+                        })
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_owned();
+
+                    created += 1;
+                    let item = format!("{COLLECTION}/new{created}");
+                    items.push((item.clone(), label));
+
+                    signal = Some(("ItemCreated", item.clone()));
+                    vec![
+                        Value::ObjectPath(item),
+                        Value::ObjectPath(String::from("/")),
+                    ]
+                }
+                (Some(ITEM_INTERFACE), "Delete") => {
+                    items.retain(|(item, _)| *item != path);
+                    signal = Some(("ItemDeleted", path.clone()));
+                    vec![Value::ObjectPath(String::from("/"))]
+                }
+                _ => Vec::new(),
+            };
+
+            let answer = match body.is_empty() {
+                true => Message::error(&call, "org.freedesktop.DBus.Error.UnknownMethod", &member),
+                false => testing::reply(&call, body),
+            };
+
+            if connection.send(answer).is_err() {
+                return;
+            }
+
+            if let Some((member, item)) = signal {
+                let signal = testing::signal(
+                    COLLECTION,
+                    COLLECTION_INTERFACE,
+                    member,
+                    vec![Value::ObjectPath(item)],
+                );
+                if connection.send(signal).is_err() {
+                    return;
+                }
+            }
+
+            let _ = calls.send(member);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use sha2::{Digest, Sha256};
+
+use super::{attr, base64, spec_named, AttributeValue, Attributes, ClassSpec, CLASSES};
+
+/// What keys held by an agent are reported as being in.
+const ACCESS_GROUP: &str = "ssh-agent";
+
+/// Reads a string with its length in front, which the agent protocol and public keys are made
+/// of, along with what comes after it.
+pub fn read_string(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let len = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    let end = len.checked_add(4)?;
+
+    Some((data.get(4..end)?, &data[end..]))
+}
+
+/// The fingerprint `ssh-keygen -l` shows for a public key, like `SHA256:uNiVzt...`.
+pub fn fingerprint(public_key: &[u8]) -> String {
+    format!("SHA256:{}", base64::encode(&Sha256::digest(public_key)))
+}
+
+/// Translates a key an agent holds into the common attribute model. Keys go by their comment,
+/// which is usually where they came from, or their fingerprint if they don't have one.
+pub fn to_attributes(public_key: &[u8], comment: &str) -> (&'static ClassSpec, Attributes) {
+    let mut attributes = Attributes::new();
+    let mut insert = |attr: &str, value| {
+        attributes.insert(attr.to_owned(), value);
+    };
+
+    let label = match comment {
+        "" => fingerprint(public_key),
+        comment => comment.to_owned(),
+    };
+
+    insert(attr::LABEL, AttributeValue::Text(label));
+    insert(
+        attr::ACCESS_GROUP,
+        AttributeValue::Text(ACCESS_GROUP.to_owned()),
+    );
+    // Agents only ever hold private keys. This is `kSecAttrKeyClassPrivate`.
+    insert(attr::KEY_CLASS, AttributeValue::Number(1));
+
+    if let Some((key_type, rest)) = read_string(public_key) {
+        let key_type = String::from_utf8_lossy(key_type);
+
+        if let Some(size) = key_size(&key_type, rest) {
+            insert(attr::KEY_SIZE, AttributeValue::Number(size));
+        }
+        insert(attr::KEY_TYPE, AttributeValue::Text(key_type.into_owned()));
+    }
+
+    (spec_named("key").unwrap_or(&CLASSES[0]), attributes)
+}
+
+/// How many bits a key has, going by what's after the type in its public key.
+fn key_size(key_type: &str, fields: &[u8]) -> Option<i64> {
+    match key_type {
+        "ssh-ed25519" | "sk-ssh-ed25519@openssh.com" => Some(256),
+        // The exponent comes before the modulus.
+        "ssh-rsa" => integer_bits(read_string(read_string(fields)?.1)?.0),
+        // The prime comes first.
+        "ssh-dss" => integer_bits(read_string(fields)?.0),
+        _ => key_type
+            .strip_prefix("ecdsa-sha2-nistp")
+            .or_else(|| key_type.strip_prefix("sk-ecdsa-sha2-nistp"))
+            .and_then(|curve| curve.split('@').next()?.parse().ok()),
+    }
+}
+
+/// How many bits an `mpint` takes up, leaving off the zeros in front.
+fn integer_bits(integer: &[u8]) -> Option<i64> {
+    let start = integer.iter().position(|byte| *byte != 0)?;
+    let bits = (integer.len() - start) * 8 - integer[start].leading_zeros() as usize;
+
+    i64::try_from(bits).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_ED25519: &str = include_str!("../../tests/fixtures/test-ed25519.pub");
+    const TEST_ECDSA: &str = include_str!("../../tests/fixtures/test-ecdsa.pub");
+    const TEST_RSA: &str = include_str!("../../tests/fixtures/test-rsa.pub");
+
+    /// The key out of a line of `authorized_keys`, as an agent would hand it over.
+    fn public_key(line: &str) -> Vec<u8> {
+        let encoded = line.split_whitespace().nth(1).expect("line has a key");
+        base64::decode(encoded).expect("key is base64")
+    }
+
+    #[test]
+    fn fingerprints_like_ssh_keygen() {
+        // What `ssh-keygen -lf` shows for each.
+        for (line, expected) in [
+            (
+                TEST_ED25519,
+                "SHA256:kYlqVcAYb6wCjKkWha4Lol8If1VSrufeBed/MSOUlZs",
+            ),
+            (
+                TEST_ECDSA,
+                "SHA256:84v9UZ9upEaiRMnZ4uG/9wGKFD+Ro8Q+uN2obRdDJMs",
+            ),
+            (
+                TEST_RSA,
+                "SHA256:BA5Q7gfxTxt3A/Rsn8CW4RRYJnRKtAwmkjEuhSAXjeo",
+            ),
+        ] {
+            assert_eq!(fingerprint(&public_key(line)), expected);
+        }
+    }
+
+    #[test]
+    fn reads_key_sizes() {
+        for (line, key_type, size) in [
+            (TEST_ED25519, "ssh-ed25519", 256),
+            (TEST_ECDSA, "ecdsa-sha2-nistp384", 384),
+            (TEST_RSA, "ssh-rsa", 3072),
+        ] {
+            let (_, attributes) = to_attributes(&public_key(line), "");
+
+            assert_eq!(
+                attributes.get(attr::KEY_TYPE),
+                Some(&AttributeValue::Text(key_type.to_owned()))
+            );
+            assert_eq!(
+                attributes.get(attr::KEY_SIZE),
+                Some(&AttributeValue::Number(size))
+            );
+        }
+
+        assert_eq!(
+            key_size("sk-ecdsa-sha2-nistp256@openssh.com", &[]),
+            Some(256)
+        );
+        assert_eq!(key_size("ssh-rsa", &[0, 0, 0, 1, 3]), None);
+        assert_eq!(key_size("ssh-unknown", &[]), None);
+    }
+
+    #[test]
+    fn names_keys_without_a_comment_by_fingerprint() {
+        let key = public_key(TEST_ED25519);
+
+        let (_, commented) = to_attributes(&key, "keeper-test-ed25519");
+        let (_, uncommented) = to_attributes(&key, "");
+
+        assert_eq!(
+            commented.get(attr::LABEL),
+            Some(&AttributeValue::Text(String::from("keeper-test-ed25519")))
+        );
+        assert_eq!(
+            uncommented.get(attr::LABEL),
+            Some(&AttributeValue::Text(fingerprint(&key)))
+        );
+    }
+}
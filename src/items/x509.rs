@@ -0,0 +1,316 @@
+use super::{attr, base64, common_name, AttributeValue, Attributes};
+use crate::clock::utc_to_cf;
+
+const INTEGER: u8 = 0x02;
+const SEQUENCE: u8 = 0x30;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+/// The explicitly tagged version at the start of a certificate, which v1 ones leave out.
+const VERSION: u8 = 0xa0;
+
+/// One DER element.
+struct Element<'a> {
+    tag: u8,
+    contents: &'a [u8],
+    /// The whole element, tag and length included.
+    encoded: &'a [u8],
+}
+
+/// Reads the element at the start of `data`, along with what comes after it.
+fn read(data: &[u8]) -> Option<(Element<'_>, &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        // Long lengths say how many bytes they take up first. Nothing in a certificate needs
+        // more than four.
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return None;
+        }
+
+        let len = data
+            .get(2..2 + count)?
+            .iter()
+            .fold(0, |len, byte| len << 8 | *byte as usize);
+        (len, 2 + count)
+    };
+
+    let end = header.checked_add(len)?;
+    let encoded = data.get(..end)?;
+
+    Some((
+        Element {
+            tag,
+            contents: &encoded[header..],
+            encoded,
+        },
+        &data[end..],
+    ))
+}
+
+fn read_tagged(data: &[u8], tag: u8) -> Option<(Element<'_>, &[u8])> {
+    read(data).filter(|(element, _)| element.tag == tag)
+}
+
+/// Reads the attributes a keystore would have for a DER encoded certificate, along with the
+/// certificate itself and when it's valid. The label is the subject's common name, if it has one.
+pub fn to_attributes(der: &[u8]) -> Option<Attributes> {
+    let (certificate, _) = read_tagged(der, SEQUENCE)?;
+    let (tbs, _) = read_tagged(certificate.contents, SEQUENCE)?;
+
+    let mut rest = tbs.contents;
+    if rest.first() == Some(&VERSION) {
+        rest = read(rest)?.1;
+    }
+
+    let (serial, rest) = read_tagged(rest, INTEGER)?;
+    let (_signature, rest) = read_tagged(rest, SEQUENCE)?;
+    let (issuer, rest) = read_tagged(rest, SEQUENCE)?;
+    let (validity, rest) = read_tagged(rest, SEQUENCE)?;
+    let (subject, _) = read_tagged(rest, SEQUENCE)?;
+
+    let (not_before, validity) = read(validity.contents)?;
+    let (not_after, _) = read(validity)?;
+
+    let mut attributes = Attributes::new();
+    let mut insert = |attr: &str, value| {
+        attributes.insert(attr.to_owned(), value);
+    };
+
+    insert(
+        attr::SUBJECT,
+        AttributeValue::Data(subject.encoded.to_vec()),
+    );
+    insert(attr::ISSUER, AttributeValue::Data(issuer.encoded.to_vec()));
+    insert(
+        attr::SERIAL_NUMBER,
+        AttributeValue::Data(serial.contents.to_vec()),
+    );
+    insert(
+        attr::DATA,
+        AttributeValue::Data(certificate.encoded.to_vec()),
+    );
+
+    if let Some(at) = time(&not_before) {
+        insert(attr::VALID_FROM, AttributeValue::Date(at));
+    }
+    if let Some(at) = time(&not_after) {
+        insert(attr::VALID_UNTIL, AttributeValue::Date(at));
+    }
+    if let Some(name) = common_name(subject.encoded) {
+        insert(attr::LABEL, AttributeValue::Text(name));
+    }
+
+    Some(attributes)
+}
+
+/// Reads a `UTCTime` or `GeneralizedTime`, as certificates write them.
+fn time(element: &Element<'_>) -> Option<f64> {
+    let text = std::str::from_utf8(element.contents)
+        .ok()?
+        .strip_suffix('Z')?;
+
+    let (year, rest) = match element.tag {
+        UTC_TIME => {
+            // Two digit years are from 1950 to 2049.
+            let year: i32 = text.get(..2)?.parse().ok()?;
+            (if year >= 50 { 1900 } else { 2000 } + year, &text[2..])
+        }
+        GENERALIZED_TIME => (text.get(..4)?.parse().ok()?, &text[4..]),
+        _ => return None,
+    };
+
+    let field = |at: usize| rest.get(at..at + 2)?.parse::<u8>().ok();
+
+    Some(utc_to_cf(
+        year,
+        field(0)?,
+        field(2)?,
+        field(4)?,
+        field(6)?,
+        field(8)?,
+    ))
+}
+
+/// Decodes every certificate in PEM text. Anything else in it is skipped.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn pem_certificates(text: &str) -> Vec<Vec<u8>> {
+    let mut certificates = Vec::new();
+    let mut current: Option<String> = None;
+
+    for line in text.lines().map(str::trim) {
+        match line {
+            // OpenSSL's trusted certificates have their trust settings after the certificate,
+            // which reading the certificate's element leaves off.
+            "-----BEGIN CERTIFICATE-----" | "-----BEGIN TRUSTED CERTIFICATE-----" => {
+                current = Some(String::new());
+            }
+            _ if line.starts_with("-----END ") => {
+                certificates.extend(current.take().and_then(|encoded| base64::decode(&encoded)));
+            }
+            _ => {
+                if let Some(encoded) = &mut current {
+                    encoded.push_str(line);
+                }
+            }
+        }
+    }
+
+    certificates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A leaf certificate, with a serial whose top bit is set, valid from a `UTCTime` to a
+    /// `GeneralizedTime`.
+    const TEST_LEAF: &[u8] = include_bytes!("../../tests/fixtures/test-leaf.der");
+    const TEST_ROOT: &[u8] = include_bytes!("../../tests/fixtures/test-root.der");
+    /// The leaf, then the root as an OpenSSL trusted certificate, with text between them.
+    const TEST_BUNDLE: &str = include_str!("../../tests/fixtures/test-bundle.pem");
+
+    fn data<'a>(attributes: &'a Attributes, attr: &str) -> &'a [u8] {
+        match attributes.get(attr) {
+            Some(AttributeValue::Data(data)) => data,
+            other => panic!("{attr} is {other:?}"),
+        }
+    }
+
+    fn date(attributes: &Attributes, attr: &str) -> f64 {
+        match attributes.get(attr) {
+            Some(AttributeValue::Date(at)) => *at,
+            other => panic!("{attr} is {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reads_certificates() {
+        let leaf = to_attributes(TEST_LEAF).expect("fixture is a certificate");
+
+        assert_eq!(
+            leaf.get(attr::LABEL),
+            Some(&AttributeValue::Text(String::from("Keeper Test Leaf")))
+        );
+        assert_eq!(
+            common_name(data(&leaf, attr::SUBJECT)).as_deref(),
+            Some("Keeper Test Leaf")
+        );
+        assert_eq!(
+            common_name(data(&leaf, attr::ISSUER)).as_deref(),
+            Some("Keeper Test CA")
+        );
+        // The serial keeps the zero that stops it reading as negative.
+        assert_eq!(data(&leaf, attr::SERIAL_NUMBER), [0x00, 0xc0, 0xff, 0xee]);
+        assert_eq!(data(&leaf, attr::DATA), TEST_LEAF);
+        assert_eq!(
+            date(&leaf, attr::VALID_FROM),
+            utc_to_cf(2024, 1, 1, 0, 0, 0)
+        );
+        assert_eq!(
+            date(&leaf, attr::VALID_UNTIL),
+            utc_to_cf(2060, 1, 1, 0, 0, 0)
+        );
+
+        let root = to_attributes(TEST_ROOT).expect("fixture is a certificate");
+
+        assert_eq!(data(&root, attr::SUBJECT), data(&root, attr::ISSUER));
+        assert_eq!(data(&root, attr::SERIAL_NUMBER), [0x1f, 0x2e, 0x3d]);
+        assert_eq!(
+            date(&root, attr::VALID_FROM),
+            utc_to_cf(2024, 1, 1, 0, 0, 0)
+        );
+        assert_eq!(
+            date(&root, attr::VALID_UNTIL),
+            utc_to_cf(2034, 1, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn refuses_malformed_certificates() {
+        for len in [0, 1, 2, 4, 100, TEST_LEAF.len() - 1] {
+            assert!(to_attributes(&TEST_LEAF[..len]).is_none(), "cut to {len}");
+        }
+
+        let mut not_a_sequence = TEST_LEAF.to_vec();
+        not_a_sequence[0] = 0x31;
+        assert!(to_attributes(&not_a_sequence).is_none());
+
+        // The outer length says there's more than there is.
+        let mut too_long = TEST_LEAF.to_vec();
+        too_long[3] += 1;
+        assert!(to_attributes(&too_long).is_none());
+
+        assert!(to_attributes(b"-----BEGIN CERTIFICATE-----").is_none());
+    }
+
+    #[test]
+    fn reads_lengths() {
+        let (element, rest) = read(&[0x04, 0x02, 0xaa, 0xbb, 0xcc]).unwrap();
+        assert_eq!(
+            (element.tag, element.contents, rest),
+            (0x04, &[0xaa, 0xbb][..], &[0xcc][..])
+        );
+        assert_eq!(element.encoded, [0x04, 0x02, 0xaa, 0xbb]);
+
+        let mut long = vec![0x04, 0x81, 0x80];
+        long.extend([0; 0x80]);
+        let (element, rest) = read(&long).unwrap();
+        assert_eq!((element.contents.len(), rest.len()), (0x80, 0));
+
+        // Long lengths with no bytes, or more than four.
+        assert!(read(&[0x04, 0x80]).is_none());
+        assert!(read(&[0x04, 0x85, 0, 0, 0, 0, 1, 0]).is_none());
+        // Lengths cut short, and contents shorter than their length.
+        assert!(read(&[0x04]).is_none());
+        assert!(read(&[0x04, 0x82, 0x01]).is_none());
+        assert!(read(&[0x04, 0x03, 0xaa]).is_none());
+        assert!(read_tagged(&[0x04, 0x00], INTEGER).is_none());
+    }
+
+    #[test]
+    fn reads_times() {
+        let at = |tag, text: &str| {
+            let encoded = [&[tag, text.len() as u8][..], text.as_bytes()].concat();
+            time(&read(&encoded).unwrap().0)
+        };
+
+        assert_eq!(
+            at(UTC_TIME, "491231235959Z"),
+            Some(utc_to_cf(2049, 12, 31, 23, 59, 59))
+        );
+        assert_eq!(
+            at(UTC_TIME, "500101000000Z"),
+            Some(utc_to_cf(1950, 1, 1, 0, 0, 0))
+        );
+        assert_eq!(
+            at(GENERALIZED_TIME, "20600101120000Z"),
+            Some(utc_to_cf(2060, 1, 1, 12, 0, 0))
+        );
+
+        // Times that aren't in UTC, or are cut short.
+        assert_eq!(at(UTC_TIME, "240101000000+0100"), None);
+        assert_eq!(at(UTC_TIME, "2401010000Z"), None);
+        assert_eq!(at(GENERALIZED_TIME, "240101000000Z"), None);
+        assert_eq!(at(INTEGER, "240101000000Z"), None);
+    }
+
+    #[test]
+    fn reads_pem_bundles() {
+        let certificates = pem_certificates(TEST_BUNDLE);
+
+        assert_eq!(certificates.len(), 2);
+        assert_eq!(certificates[0], TEST_LEAF);
+        // Trusted certificates come with their trust settings, which reading leaves off.
+        assert!(certificates[1].starts_with(TEST_ROOT));
+        assert_eq!(
+            data(&to_attributes(&certificates[1]).unwrap(), attr::DATA),
+            TEST_ROOT
+        );
+
+        assert!(pem_certificates("Keeper Test Root").is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::process::ProcessInfo;
+
+/// A set of executables, as written in the config.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct ListConfig {
+    /// Hex encoded SHA-256 hashes of executables.
+    pub(crate) sha256: Vec<String>,
+    /// Executable paths, or directories containing executables.
+    ///
+    /// Example: `/Applications/Safari.app`
+    pub(crate) paths: Vec<PathBuf>,
+    /// Extra files to read more entries from.
+    ///
+    /// These contain one hash or absolute path per line. Empty lines and lines starting with `#` are skipped.
+    pub(crate) files: Vec<PathBuf>,
+}
+
+/// A loaded set of executables, ready to be matched against changers.
+#[derive(Debug, Default)]
+pub struct ExecutableList {
+    hashes: HashSet<String>,
+    path_prefixes: Vec<PathBuf>,
+}
+
+impl ExecutableList {
+    pub fn load(config: &ListConfig) -> Self {
+        let mut list = Self::default();
+
+        for hash in &config.sha256 {
+            list.add_hash(hash);
+        }
+
+        list.path_prefixes.extend(config.paths.iter().cloned());
+
+        for file in &config.files {
+            list.read_file(file);
+        }
+
+        list
+    }
+
+    fn add_hash(&mut self, hash: &str) {
+        let hash = hash.trim().to_ascii_lowercase();
+
+        if hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            self.hashes.insert(hash);
+        } else {
+            log::warn!("ignoring malformed executable hash: {hash}");
+        }
+    }
+
+    fn read_file(&mut self, file: &Path) {
+        let contents = match fs::read_to_string(file) {
+            Ok(contents) => contents,
+            Err(e) => {
+                log::warn!("failed to read executable list {}: {e}", file.display());
+                return;
+            }
+        };
+
+        for line in contents.lines() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('/') {
+                self.path_prefixes.push(PathBuf::from(line));
+            } else {
+                self.add_hash(line);
+            }
+        }
+    }
+
+    fn match_path(&self, path: &Path) -> Option<&Path> {
+        self.path_prefixes
+            .iter()
+            .find(|prefix| path.starts_with(prefix))
+            .map(PathBuf::as_path)
+    }
+
+    /// Checks if the changer's own executable is on the list, describing why if so.
+    pub fn matches(&self, changer: &ProcessInfo) -> Option<String> {
+        if let Some(sha256) = &changer.sha256 {
+            if self.hashes.contains(sha256) {
+                return Some(format!("its executable hash {sha256} is listed"));
+            }
+        }
+
+        let path = changer.path.as_deref()?;
+        self.match_path(path)
+            .map(|prefix| format!("{} is listed under {}", path.display(), prefix.display()))
+    }
+
+    /// Like `matches`, but also checks the paths of every process that launched the changer.
+    pub fn matches_with_ancestry(&self, changer: &ProcessInfo) -> Option<String> {
+        if let Some(reason) = self.matches(changer) {
+            return Some(reason);
+        }
+
+        changer.ancestry.iter().find_map(|ancestor| {
+            let path = ancestor.path.as_deref()?;
+            self.match_path(path).map(|prefix| {
+                format!(
+                    "it was launched by {} [{}], which is listed under {}",
+                    path.display(),
+                    ancestor.pid,
+                    prefix.display()
+                )
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::Ancestor;
+
+    const HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    fn list(config: &str) -> ExecutableList {
+        ExecutableList::load(&toml::from_str(config).unwrap())
+    }
+
+    fn changer(path: &str, sha256: Option<&str>) -> ProcessInfo {
+        ProcessInfo {
+            path: Some(path.into()),
+            sha256: sha256.map(str::to_owned),
+            ..ProcessInfo::unknown(100)
+        }
+    }
+
+    #[test]
+    fn matches_hashes() {
+        let list = list(&format!(
+            r#"sha256 = ["{}", "not a hash", "{}"]"#,
+            HASH.to_ascii_uppercase(),
+            &HASH[1..]
+        ));
+
+        assert_eq!(list.hashes.len(), 1);
+        assert_eq!(
+            list.matches(&changer("/usr/bin/tool", Some(HASH))),
+            Some(format!("its executable hash {HASH} is listed"))
+        );
+        assert_eq!(list.matches(&changer("/usr/bin/tool", None)), None);
+    }
+
+    #[test]
+    fn matches_paths_and_the_directories_they_are_in() {
+        let list = list(r#"paths = ["/usr/local/bin", "/opt/tool/tool"]"#);
+
+        assert_eq!(
+            list.matches(&changer("/usr/local/bin/tool", Some(HASH))),
+            Some(String::from(
+                "/usr/local/bin/tool is listed under /usr/local/bin"
+            ))
+        );
+        assert!(list.matches(&changer("/opt/tool/tool", None)).is_some());
+        // Only whole path components count.
+        assert_eq!(
+            list.matches(&changer("/usr/local/binaries/tool", None)),
+            None
+        );
+        assert_eq!(list.matches(&changer("/opt/tool/tool-helper", None)), None);
+        assert_eq!(list.matches(&ProcessInfo::unknown(100)), None);
+    }
+
+    #[test]
+    fn reads_lists_from_files() {
+        let file = std::env::temp_dir().join(format!("keeper-list-{}", std::process::id()));
+        fs::write(
+            &file,
+            format!("# Build tools\n\n  /opt/build  \n{HASH}\nnot a hash\n"),
+        )
+        .unwrap();
+
+        let list = list(&format!("files = [{:?}, \"/nonexistent\"]", file));
+        let _ = fs::remove_file(&file);
+
+        assert_eq!(list.path_prefixes, [PathBuf::from("/opt/build")]);
+        assert!(list.hashes.contains(HASH));
+        assert!(list.matches(&changer("/opt/build/cc", None)).is_some());
+    }
+
+    #[test]
+    fn matches_what_launched_the_changer() {
+        let list = list(r#"paths = ["/usr/bin/sshd"]"#);
+        let mut changer = changer("/usr/bin/security", None);
+        changer.ancestry = vec![
+            Ancestor {
+                pid: 50,
+                name: Some(String::from("bash")),
+                path: Some(PathBuf::from("/usr/bin/bash")),
+            },
+            Ancestor {
+                pid: 10,
+                name: Some(String::from("sshd")),
+                path: None,
+            },
+            Ancestor {
+                pid: 1,
+                name: Some(String::from("sshd")),
+                path: Some(PathBuf::from("/usr/bin/sshd")),
+            },
+        ];
+
+        assert_eq!(list.matches(&changer), None);
+        assert_eq!(
+            list.matches_with_ancestry(&changer),
+            Some(String::from(
+                "it was launched by /usr/bin/sshd [1], which is listed under /usr/bin/sshd"
+            ))
+        );
+
+        // The changer itself is what's reported when it's listed too.
+        changer.path = Some(PathBuf::from("/usr/bin/sshd"));
+        assert_eq!(
+            list.matches_with_ancestry(&changer),
+            Some(String::from("/usr/bin/sshd is listed under /usr/bin/sshd"))
+        );
+
+        changer.ancestry.clear();
+        changer.path = Some(PathBuf::from("/usr/bin/security"));
+        assert_eq!(list.matches_with_ancestry(&changer), None);
+    }
+}
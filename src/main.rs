@@ -1,37 +1,68 @@
-#[cfg(not(target_os = "macos"))]
-compile_error!("only available on macOS");
-
+#[cfg(target_os = "macos")]
 use bytemuck::{Pod, Zeroable};
+#[cfg(target_os = "macos")]
 use const_format::formatcp;
-use core_foundation::{
-    base::TCFType,
-    runloop::CFRunLoop,
-    url::{CFURLRef, CFURL},
+#[cfg(target_os = "macos")]
+use core_foundation::{base::TCFType, runloop::CFRunLoop, url::CFURL};
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "macos")]
+use std::{ffi::OsStr, fs, thread, time::Duration};
+#[cfg(target_os = "linux")]
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
 };
-use objc::{msg_send, runtime::Class, sel, sel_impl};
-use objc_foundation::{INSString, NSString};
-use objc_id::Id;
-use std::{ffi::OsStr, fs, path::Path, ptr::NonNull, thread, time::Duration};
 
+mod access;
+mod baseline;
+#[cfg(target_os = "macos")]
 mod bindings;
+mod bursts;
+mod canary;
+mod capture;
+mod clock;
 mod config;
 use config::Config;
+#[cfg(target_os = "linux")]
+mod dbus;
 mod events;
-use events::{EventData, EventDetails, FilteredEventData};
+mod fingerprint;
+mod inventory;
+mod items;
+mod lists;
 
+#[cfg(target_os = "macos")]
 mod messaging;
+#[cfg(target_os = "macos")]
 use messaging::{Sender, Server};
 
+mod pipeline;
+use pipeline::{Pipeline, Severity};
+mod process;
+
+#[cfg(target_os = "macos")]
 mod sandbox;
+mod scoring;
+mod sinks;
+use sinks::{LogSink, Sink};
+mod state;
+use state::Stores;
+#[cfg(target_os = "macos")]
 mod version;
 
+#[cfg(target_os = "macos")]
 const BUNDLE_ID: &str = "org.blackholefox.keeperofkeys";
+#[cfg(target_os = "macos")]
 const SERVICE_NAME: &str = formatcp!("{BUNDLE_ID}.pinger");
+#[cfg(target_os = "macos")]
+const CONTROL_SERVICE_NAME: &str = formatcp!("{BUNDLE_ID}.control");
 
+#[cfg(target_os = "macos")]
 #[derive(Clone, Copy, Debug, Pod, Zeroable, PartialEq, Eq)]
 #[repr(transparent)]
 struct ClientRequest(u8);
 
+#[cfg(target_os = "macos")]
 impl ClientRequest {
     #![allow(non_upper_case_globals, non_snake_case)]
 
@@ -39,10 +70,12 @@ impl ClientRequest {
     const Shutdown: Self = Self(1);
 }
 
+#[cfg(target_os = "macos")]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 #[repr(C)]
 struct ClientRequestShutdown;
 
+#[cfg(target_os = "macos")]
 #[derive(Clone, Copy, Debug, Pod, Zeroable, PartialEq, PartialOrd)]
 #[repr(C)]
 struct AppVersion {
@@ -51,22 +84,48 @@ struct AppVersion {
     patch: u16,
 }
 
+#[cfg(target_os = "macos")]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 #[repr(C)]
 struct ShuttingDown;
 
+#[cfg(target_os = "macos")]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct SnoozeRequest {
+    target_kind: u32,
+    minutes: u32,
+    target_len: u32,
+    target: [u8; 256],
+}
+
+#[cfg(target_os = "macos")]
+impl SnoozeRequest {
+    const CHANGER: u32 = 0;
+    const ITEM: u32 = 1;
+    const CLEAR: u32 = 2;
+
+    fn target(&self) -> Option<&str> {
+        let len = (self.target_len as usize).min(self.target.len());
+        std::str::from_utf8(&self.target[..len]).ok()
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct Snoozed {
+    /// Seconds since the Unix epoch that the snooze ends at, or 0 if it was rejected.
+    until: u64,
+}
+
+#[cfg(target_os = "macos")]
 embed_plist::embed_launchd_plist!("../target/launchd.plist");
 
 fn main() -> Result<(), ()> {
     let logger = flexi_logger::Logger::try_with_env_or_str("info").unwrap();
 
-    let home = unsafe {
-        CFURL::wrap_under_create_rule(bindings::CFCopyHomeDirectoryURL())
-            .to_path()
-            .unwrap()
-    };
-
-    let data_home = home.join(formatcp!("Library/Containers/{BUNDLE_ID}/Data"));
+    let (home, data_home) = platform_dirs();
 
     let file_log_spec = {
         let log_dir = data_home.join("Logs");
@@ -93,22 +152,86 @@ fn main() -> Result<(), ()> {
     let mut args = std::env::args().skip(1);
 
     match args.next() {
-        Some(arg) if arg == "monitor" => sandbox::init_sandbox(&home, &data_home, SERVICE_NAME),
-        _ => return register_service(&home),
+        Some(arg) if arg == "monitor" => run_monitor(&home, &data_home),
+        Some(arg) if arg == "replay" => replay(args, &data_home),
+        Some(arg) if arg == "baseline" => baseline::run_command(args, &data_home),
+        Some(arg) if arg == "snooze" => snooze(args),
+        Some(arg) if arg == "inventory" => inventory::run_command(args, &data_home),
+        Some(arg) if arg == "canary" => {
+            canary::run_command(args, &Config::read_from_dir(&data_home).canaries)
+        }
+        _ => register_service(&home),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn platform_dirs() -> (PathBuf, PathBuf) {
+    let home = unsafe {
+        CFURL::wrap_under_create_rule(bindings::CFCopyHomeDirectoryURL())
+            .to_path()
+            .unwrap()
+    };
+
+    let data_home = home.join(formatcp!("Library/Containers/{BUNDLE_ID}/Data"));
+
+    (home, data_home)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn platform_dirs() -> (PathBuf, PathBuf) {
+    let home = PathBuf::from(std::env::var_os("HOME").expect("no home directory set"));
+
+    let data_home = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home.join(".local/share"),
     };
 
-    let config = Config::read_from_dir(&data_home);
+    (home, data_home.join("keeper_of_keys"))
+}
+
+#[cfg(target_os = "macos")]
+fn run_monitor(home: &Path, data_home: &Path) -> Result<(), ()> {
+    sandbox::init_sandbox(home, data_home, SERVICE_NAME, CONTROL_SERVICE_NAME);
+
+    let config = Config::read_from_dir(data_home);
 
-    Config::setup_home_link(&data_home, &home);
+    Config::setup_home_link(data_home, home);
 
-    // LEAK NOTE: 1 (16 bytes) ROOT LEAK: <NSArray 0x600002a80360> [16]
-    mac_notification_sys::set_application(BUNDLE_ID).unwrap();
+    if config.access_proxy.enabled {
+        log::warn!("the access proxy is only available on Linux, ignoring it");
+    }
+
+    if config.file_access.enabled {
+        log::warn!("watching keystore files is only available on Linux, ignoring it");
+    }
 
-    let event_source = events::start_keychain_monitor();
+    if config.ssh_agent.enabled {
+        log::warn!("the SSH agent proxy is only available on Linux, ignoring it");
+    }
+
+    if config.gpg_agent.enabled {
+        log::warn!("the GnuPG agent proxy is only available on Linux, ignoring it");
+    }
+
+    let event_source = events::start_desktop_monitors(&config, home);
+
+    let snoozes = sinks::Snoozes::default();
+    let control_snoozes = snoozes.clone();
 
     thread::Builder::new()
         .name(String::from("Status Listener"))
         .spawn(move || {
+            let control = Server::<Snoozed, SnoozeRequest, _, _>::create(
+                CONTROL_SERVICE_NAME,
+                move |request| {
+                    messaging::ReplyWith::new(
+                        Some(handle_snooze(&control_snoozes, request)),
+                        None::<fn()>,
+                    )
+                },
+            );
+            control.listen();
+
             let mut listener =
                 Server::<AppVersion, ClientRequest, _, _>::create(SERVICE_NAME, |msg| match msg {
                     ClientRequest::VersionInfo => {
@@ -135,82 +258,376 @@ fn main() -> Result<(), ()> {
         })
         .expect("failed to start status listener");
 
-    let nsapp_class = objc::class!(NSRunningApplication);
+    watch(event_source, config, snoozes, data_home)
+}
+
+/// Sends events from a live monitor through the pipeline until the monitor stops.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn watch(
+    event_source: std::sync::mpsc::Receiver<events::EventData>,
+    config: Config,
+    snoozes: sinks::Snoozes,
+    data_home: &Path,
+) -> Result<(), ()> {
+    let capture_events = config.capture_events;
+    let sink_config = config.sinks;
+    let digest_config = config.digest.clone();
+    let quiet_hours = config.quiet_hours.clone();
+    let inventory_config = config.inventory.clone();
+
+    let stores = Stores::load(data_home, &config);
+    let mut pipeline = Pipeline::new(config, stores);
+    if let Some(store) = canary::platform_store() {
+        pipeline.set_canary_store(store);
+    }
+    if inventory_config.enabled {
+        if let Some(source) = inventory::platform_source() {
+            pipeline.set_inventory(inventory::Scanner::load(
+                source,
+                &inventory_config,
+                data_home,
+            ));
+        }
+    }
+    pipeline.add_sink(Severity::Log, Box::new(LogSink));
+    match sinks::JournalSink::open(data_home) {
+        Ok(journal) => pipeline.add_sink(sink_config.journal, Box::new(journal)),
+        Err(e) => log::warn!("failed to open event journal, continuing without it: {e}"),
+    }
+
+    let mut notifications: Box<dyn Sink> = Box::new(sinks::ScheduleSink::load(
+        Box::new(sinks::NotificationSink::new()),
+        quiet_hours,
+        snoozes,
+        data_home,
+    ));
+    if digest_config.enabled {
+        notifications = Box::new(sinks::DigestSink::load(
+            notifications,
+            digest_config,
+            data_home,
+        ));
+    }
+    pipeline.add_sink(sink_config.notifications, notifications);
 
     log::info!("setup done, waiting for events...");
 
-    while let Ok(original_event) = event_source.recv() {
-        log::trace!("raw keychain event: {:?}", original_event);
-
-        // On at least newer versions of macOS, keychain item "editing", both through the APIs directly and Keychain Access.app, are
-        // impplemented via delete -> add event sequences for every supported item type. To send a sensible notification saying something
-        // was "changed", the two events need squashed into a single `Update` event.
-        //
-        // The events come in nearly exactly right after eachother and have identical edit timestamps.
-        let ev = if let Ok(next_event) = event_source.recv_timeout(Duration::from_millis(100)) {
-            log::trace!("received next event");
-
-            match original_event {
-                // If the first and second events came from the same process at the same time...
-                EventData::RemovedOrUpdate {
-                    seen_at,
-                    modified_by,
-                } if seen_at == original_event.changed_at()
-                    && modified_by == original_event.changer_pid() =>
-                {
-                    match next_event {
-                        // ... an updated occured
-                        EventData::AddOrUpdate(EventDetails { details, .. }) => {
-                            log::debug!("skipped duplicate event");
-                            FilteredEventData::Updated(details)
-                        }
-                        // ... the item was actually deleted, though this branch should be unreachable with "normal" apps.
-                        EventData::RemovedOrUpdate { .. } => original_event.assume_filtered(),
-                    }
+    if capture_events {
+        match capture::Recorder::create(event_source, data_home) {
+            Ok(recorder) => pipeline.run(recorder),
+            Err(e) => {
+                log::error!("failed to start event capture: {e}");
+                return Err(());
+            }
+        }
+    } else {
+        pipeline.run(event_source);
+    }
+
+    log::info!("event stream closed, shutting down");
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn handle_snooze(snoozes: &sinks::Snoozes, request: SnoozeRequest) -> Snoozed {
+    let target = match (request.target_kind, request.target()) {
+        (SnoozeRequest::CLEAR, _) => {
+            snoozes.clear();
+            return Snoozed { until: 0 };
+        }
+        (SnoozeRequest::CHANGER, Some(target)) => sinks::SnoozeTarget::Changer(target.to_owned()),
+        (SnoozeRequest::ITEM, Some(target)) => sinks::SnoozeTarget::Item(target.to_owned()),
+        _ => {
+            log::warn!("received bogus snooze request");
+            return Snoozed { until: 0 };
+        }
+    };
+
+    Snoozed {
+        until: snoozes.add(target, request.minutes),
+    }
+}
+
+/// Reads what `snooze` was asked to do, which is `None` for clearing every snooze.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn snooze_request(
+    mut args: impl Iterator<Item = String>,
+) -> Result<Option<(sinks::SnoozeTarget, u32)>, ()> {
+    let target = match args.next().as_deref() {
+        Some("changer") => sinks::SnoozeTarget::Changer,
+        Some("item") => sinks::SnoozeTarget::Item,
+        Some("clear") => return Ok(None),
+        other => {
+            log::error!(
+                "unknown snooze target {:?}, expected one of changer, item or clear",
+                other.unwrap_or_default()
+            );
+            return Err(());
+        }
+    };
+
+    match (args.next(), args.next().map(|m| m.parse::<u32>())) {
+        (Some(name), Some(Ok(minutes))) => Ok(Some((target(name), minutes))),
+        _ => {
+            log::error!("snoozing needs a name and a number of minutes");
+            Err(())
+        }
+    }
+}
+
+/// Tells the user how a snooze went, given when the monitor said it ends.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn report_snooze(cleared: bool, until: u64) -> Result<(), ()> {
+    match (cleared, until) {
+        (true, _) => log::info!("cleared every snooze"),
+        (_, 0) => {
+            log::error!("the monitor rejected the snooze");
+            return Err(());
+        }
+        (_, until) => log::info!("snoozed until {until} (seconds since the Unix epoch)"),
+    }
+
+    Ok(())
+}
+
+/// Asks the running monitor to stop notifying about something for a while.
+///
+/// Usage: `snooze <changer|item> <name> <minutes>` or `snooze clear`
+#[cfg(target_os = "macos")]
+fn snooze(args: impl Iterator<Item = String>) -> Result<(), ()> {
+    let mut request = SnoozeRequest::zeroed();
+
+    match snooze_request(args)? {
+        None => request.target_kind = SnoozeRequest::CLEAR,
+        Some((target, minutes)) => {
+            let (target_kind, target) = match &target {
+                sinks::SnoozeTarget::Changer(target) => (SnoozeRequest::CHANGER, target),
+                sinks::SnoozeTarget::Item(target) => (SnoozeRequest::ITEM, target),
+            };
+
+            if target.len() > request.target.len() {
+                log::error!("{target} is too long to snooze");
+                return Err(());
+            }
+
+            request.target_kind = target_kind;
+            request.target[..target.len()].copy_from_slice(target.as_bytes());
+            request.target_len = target.len() as u32;
+            request.minutes = minutes;
+        }
+    }
+
+    let mut sender = match Sender::connect(CONTROL_SERVICE_NAME) {
+        Some(sender) => sender,
+        None => {
+            log::error!("the monitor isn't running");
+            return Err(());
+        }
+    };
+
+    let reply: Snoozed = sender.send(request);
+
+    report_snooze(request.target_kind == SnoozeRequest::CLEAR, reply.until)
+}
+
+/// Asks the running monitor to stop notifying about something for a while.
+///
+/// Usage: `snooze <changer|item> <name> <minutes>` or `snooze clear`
+#[cfg(target_os = "linux")]
+fn snooze(args: impl Iterator<Item = String>) -> Result<(), ()> {
+    let request = snooze_request(args)?;
+    let line = match &request {
+        None => String::from("clear"),
+        Some((sinks::SnoozeTarget::Changer(name), minutes)) => format!("changer {minutes} {name}"),
+        Some((sinks::SnoozeTarget::Item(name), minutes)) => format!("item {minutes} {name}"),
+    };
+
+    if line.contains('\n') {
+        log::error!("names to snooze can't have line breaks in them");
+        return Err(());
+    }
+
+    let until =
+        access::proxy_socket(None, CONTROL_SOCKET).and_then(|socket| send_snooze(&socket, &line));
+
+    match until {
+        Ok(until) => report_snooze(request.is_none(), until),
+        Err(e) => {
+            log::error!("couldn't reach the monitor, is it running? {e}");
+            Err(())
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn snooze(_args: impl Iterator<Item = String>) -> Result<(), ()> {
+    log::error!("there is no monitor to snooze on this platform");
+    Err(())
+}
+
+/// The control socket's name in the runtime directory. Requests on it are a line like
+/// `item <minutes> <name>`, `changer <minutes> <name>` or `clear`, and are answered with a
+/// line saying when the snooze ends, in seconds since the Unix epoch, or 0 if it was rejected.
+#[cfg(target_os = "linux")]
+const CONTROL_SOCKET: &str = "control";
+
+/// Takes snoozes from `snooze` on the control socket.
+#[cfg(target_os = "linux")]
+fn serve_snoozes(socket: &Path, snoozes: sinks::Snoozes) -> io::Result<()> {
+    access::serve_proxy(socket, "Control", move |client, _| {
+        let mut request = String::new();
+        BufReader::new(&client).read_line(&mut request)?;
+
+        writeln!(&client, "{}", handle_snooze(&snoozes, &request))
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn handle_snooze(snoozes: &sinks::Snoozes, request: &str) -> u64 {
+    let mut fields = request.trim_end_matches('\n').splitn(3, ' ');
+
+    let (target, minutes) = match (fields.next(), fields.next().map(str::parse), fields.next()) {
+        (Some("clear"), None, None) => {
+            snoozes.clear();
+            return 0;
+        }
+        (Some("changer"), Some(Ok(minutes)), Some(name)) => {
+            (sinks::SnoozeTarget::Changer(name.to_owned()), minutes)
+        }
+        (Some("item"), Some(Ok(minutes)), Some(name)) => {
+            (sinks::SnoozeTarget::Item(name.to_owned()), minutes)
+        }
+        _ => {
+            log::warn!("received bogus snooze request");
+            return 0;
+        }
+    };
+
+    snoozes.add(target, minutes)
+}
+
+#[cfg(target_os = "linux")]
+fn send_snooze(socket: &Path, line: &str) -> io::Result<u64> {
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{line}")?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+
+    reply
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(target_os = "linux")]
+fn run_monitor(home: &Path, data_home: &Path) -> Result<(), ()> {
+    let config = Config::read_from_dir(data_home);
+
+    Config::setup_home_link(data_home, home);
+
+    let event_source = events::start_desktop_monitors(&config, home);
+
+    let snoozes = sinks::Snoozes::default();
+    let served = access::proxy_socket(None, CONTROL_SOCKET)
+        .and_then(|socket| serve_snoozes(&socket, snoozes.clone()));
+    if let Err(e) = served {
+        log::warn!("failed to set up the control socket, snoozing won't work: {e}");
+    }
+
+    watch(event_source, config, snoozes, data_home)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn run_monitor(_home: &Path, _data_home: &Path) -> Result<(), ()> {
+    log::error!("there is no keychain monitor available for this platform");
+    Err(())
+}
+
+/// Feeds a capture file back through the pipeline, exactly like a live monitor would.
+///
+/// Usage: `replay <file> [--realtime] [--notify] [--config <path>]`
+fn replay(mut args: impl Iterator<Item = String>, data_home: &Path) -> Result<(), ()> {
+    let mut capture_path = None;
+    let mut config_path = None;
+    let mut realtime = false;
+    let mut notify = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--realtime" => realtime = true,
+            "--notify" => notify = true,
+            "--config" => match args.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => {
+                    log::error!("--config needs a path");
+                    return Err(());
                 }
-                // If the changer and timestamp weren't identical, can't assume this was a related item and it needs processed on its own
-                // during both removals or additions.
-                EventData::RemovedOrUpdate { .. } => original_event.assume_filtered(),
-                EventData::AddOrUpdate(_) => original_event.assume_filtered(),
+            },
+            _ if capture_path.is_none() => capture_path = Some(PathBuf::from(arg)),
+            _ => {
+                log::error!("unexpected replay argument: {arg}");
+                return Err(());
             }
-        } else {
-            original_event.assume_filtered()
-        };
-
-        let item_title = ev.item_title().unwrap_or("Unknown");
-
-        if config
-            .ignored_items
-            .iter()
-            .any(|ignored| ignored == item_title)
-        {
-            log::debug!("skipping change, it had an ignored item title");
-            continue;
         }
+    }
+
+    let capture_path = match capture_path {
+        Some(path) => path,
+        None => {
+            log::error!("no capture file given to replay");
+            return Err(());
+        }
+    };
+
+    let events = match capture::read_capture(&capture_path) {
+        Ok(events) => events,
+        Err(e) => {
+            log::error!("failed to read {}: {e}", capture_path.display());
+            return Err(());
+        }
+    };
 
-        let subtitle = format!("Item: {}", item_title);
-        log::debug!("sending notification about {}", subtitle);
+    let config = match config_path {
+        Some(path) => Config::read_from_file(&path),
+        None => Config::read_from_dir(data_home),
+    };
 
-        let mut builder = mac_notification_sys::Notification::new();
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    let sink_config = config.sinks;
 
-        builder.title(match &ev {
-            FilteredEventData::Added { .. } => "A new keychain item was added",
-            FilteredEventData::Updated { .. } => "A keychain item was updated",
-            FilteredEventData::Removed { .. } => "A keychain item was removed",
-        });
+    // Replays shouldn't teach the real stores anything, so they get scratch ones.
+    let stores = Stores::scratch(data_home, &config);
+    let mut pipeline = Pipeline::new(config, stores);
+    pipeline.add_sink(Severity::Log, Box::new(LogSink));
 
-        let message = get_changer_message(nsapp_class, ev.changer_pid());
-        builder.message(&message);
+    if notify {
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        pipeline.add_sink(
+            sink_config.notifications,
+            Box::new(sinks::NotificationSink::new()),
+        );
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+        log::warn!("notifications aren't available on this platform, ignoring --notify");
+    }
 
-        builder.subtitle(&subtitle);
+    log::info!(
+        "replaying {} events from {}",
+        events.len(),
+        capture_path.display()
+    );
 
-        builder.send().unwrap();
+    if realtime {
+        pipeline.run(capture::replay_realtime(events));
+    } else {
+        pipeline.run(capture::FastReplay::new(events));
     }
 
-    log::info!("event stream closed, shutting down");
+    log::info!("replay finished");
     Ok(())
 }
 
+#[cfg(target_os = "macos")]
 fn register_service(home: &Path) -> Result<(), ()> {
     let launchd_plist = embed_plist::get_launchd_plist();
 
@@ -254,6 +671,13 @@ fn register_service(home: &Path) -> Result<(), ()> {
     Ok(())
 }
 
+#[cfg(not(target_os = "macos"))]
+fn register_service(_home: &Path) -> Result<(), ()> {
+    log::error!("running as a background service isn't supported on this platform");
+    Err(())
+}
+
+#[cfg(target_os = "macos")]
 fn run_launchctl_command(command: &str, arg: impl AsRef<OsStr>) -> Result<(), ()> {
     match std::process::Command::new("launchctl")
         .arg(command)
@@ -282,41 +706,43 @@ fn run_launchctl_command(command: &str, arg: impl AsRef<OsStr>) -> Result<(), ()
     }
 }
 
-fn get_changer_message(nsapp_class: &Class, modifier: i32) -> String {
-    const BASE_MSG: &str = "Changer:";
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
 
-    let running_app: Option<NonNull<objc::runtime::Object>> = unsafe {
-        msg_send![
-            nsapp_class,
-            runningApplicationWithProcessIdentifier: modifier
-        ]
-    };
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| (*arg).to_owned())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
 
-    match running_app {
-        Some(app) => {
-            let app_name: Option<NonNull<NSString>> =
-                unsafe { msg_send![app.as_ptr(), localizedName] };
-            let app_name = app_name.map(|obj| unsafe { Id::<NSString>::from_ptr(obj.as_ptr()) });
-
-            let exe_path: Option<NonNull<CFURLRef>> =
-                unsafe { msg_send![app.as_ptr(), executableURL] };
-            let exe_path = exe_path.map(|obj| unsafe {
-                CFURL::wrap_under_get_rule(obj.as_ptr().cast())
-                    .to_path()
-                    .unwrap()
-            });
-
-            let changer = if let Some(app_name) = &app_name {
-                app_name.as_str()
-            } else if let Some(exe_path) = &exe_path {
-                exe_path.file_name().unwrap().to_str().unwrap()
-            } else {
-                "Unknown Application"
-            };
+    #[test]
+    fn reads_snooze_arguments() {
+        assert!(matches!(
+            snooze_request(args(&["item", "github.com", "30"])),
+            Ok(Some((sinks::SnoozeTarget::Item(name), 30))) if name == "github.com"
+        ));
+        assert!(matches!(snooze_request(args(&["clear"])), Ok(None)));
+        assert!(snooze_request(args(&["changer", "/usr/bin/security"])).is_err());
+        assert!(snooze_request(args(&["everything", "30"])).is_err());
+    }
+
+    #[test]
+    fn snoozes_over_the_control_socket() {
+        let dir = std::env::temp_dir().join(format!("keeper-control-{}", std::process::id()));
+        let socket = dir.join(CONTROL_SOCKET);
+        serve_snoozes(&socket, sinks::Snoozes::default()).unwrap();
 
-            format!("{BASE_MSG} {changer}")
+        let asked_at = clock::unix_now();
+        let until = send_snooze(&socket, "item 5 github.com (fox)").unwrap();
+        assert!((asked_at + 5 * 60..=clock::unix_now() + 5 * 60).contains(&until));
+
+        for bogus in ["item soon github.com", "hush 5 github.com", "item 5"] {
+            assert_eq!(send_snooze(&socket, bogus).unwrap(), 0, "{bogus}");
         }
-        // TODO: maybe grab the executable path instead
-        None => format!("{BASE_MSG} Private Application ({modifier})"),
+        assert_eq!(send_snooze(&socket, "clear").unwrap(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
@@ -102,7 +102,8 @@ where
         }
     }
 
-    pub fn recv_messages(&mut self) {
+    /// Starts handling messages on the current thread's runloop, without running it.
+    pub fn listen(&self) {
         let rl_source = unsafe {
             CFRunLoopSource::wrap_under_create_rule(CFMessagePortCreateRunLoopSource(
                 ptr::null_mut(),
@@ -113,6 +114,10 @@ where
 
         let current_loop = CFRunLoop::get_current();
         current_loop.add_source(&rl_source, unsafe { runloop::kCFRunLoopDefaultMode });
+    }
+
+    pub fn recv_messages(&mut self) {
+        self.listen();
         CFRunLoop::run_current()
     }
 
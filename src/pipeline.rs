@@ -0,0 +1,542 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use crate::{
+    baseline::Verdict,
+    bursts::{BurstAggregator, BurstSummary},
+    canary::{Canaries, CanaryStore},
+    config::Config,
+    events::{EventData, EventDetails, FilteredEventData},
+    inventory::Scanner,
+    lists::ExecutableList,
+    scoring::{self, Scorer},
+    sinks::Sink,
+    state::Stores,
+};
+
+/// How long to wait for a follow up event that may turn a removal into an update.
+const COALESCE_WINDOW: Duration = Duration::from_millis(100);
+
+/// How often to check on held back events while no new ones are arriving.
+const IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Something that produces raw events, either live from a monitor or from a recording.
+pub trait EventSource {
+    /// Blocks until the next event arrives, returning `None` once the source is exhausted.
+    fn recv(&mut self) -> Option<EventData>;
+
+    /// Like `recv`, but gives up after `timeout` has passed without an event.
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<EventData, RecvTimeoutError>;
+}
+
+impl EventSource for mpsc::Receiver<EventData> {
+    fn recv(&mut self) -> Option<EventData> {
+        mpsc::Receiver::recv(self).ok()
+    }
+
+    fn recv_timeout(&mut self, timeout: Duration) -> Result<EventData, RecvTimeoutError> {
+        mpsc::Receiver::recv_timeout(self, timeout)
+    }
+}
+
+/// Squashes the raw event stream of a source into the events a user cares about.
+pub struct Coalescer<S> {
+    source: S,
+    /// An event that was read while looking for a pair, but turned out to be unrelated.
+    pending: Option<EventData>,
+}
+
+impl<S: EventSource> Coalescer<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            pending: None,
+        }
+    }
+
+    /// Like `next`, but gives up after `timeout` has passed without an event.
+    pub fn next_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<FilteredEventData, RecvTimeoutError> {
+        let original_event = match self.pending.take() {
+            Some(event) => event,
+            None => self.source.recv_timeout(timeout)?,
+        };
+
+        Ok(self.coalesce(original_event))
+    }
+
+    fn coalesce(&mut self, original_event: EventData) -> FilteredEventData {
+        log::trace!("raw keychain event: {:?}", original_event);
+
+        // On at least newer versions of macOS, keychain item "editing", both through the APIs directly and Keychain Access.app, are
+        // impplemented via delete -> add event sequences for every supported item type. To send a sensible notification saying something
+        // was "changed", the two events need squashed into a single `Update` event.
+        //
+        // The events come in nearly exactly right after eachother and have identical edit timestamps.
+        let next_event = match self.source.recv_timeout(COALESCE_WINDOW) {
+            Ok(event) => event,
+            Err(_) => return original_event.assume_filtered(),
+        };
+
+        log::trace!("received next event");
+
+        // If the first and second events are the same process changing the same item at the
+        // same time, an update occurred.
+        if original_event.is_replaced_by(&next_event) {
+            if let EventData::AddOrUpdate(EventDetails { details, .. }) = next_event {
+                log::debug!("skipped duplicate event");
+                return FilteredEventData::Updated(details);
+            }
+        }
+
+        // Otherwise it can't be assumed the two are related, so each is processed on its own.
+        let ev = original_event.assume_filtered();
+
+        // The second event wasn't part of this one, so it gets its own turn next time around.
+        self.pending = Some(next_event);
+
+        ev
+    }
+}
+
+impl<S: EventSource> Iterator for Coalescer<S> {
+    type Item = FilteredEventData;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let original_event = match self.pending.take() {
+            Some(event) => event,
+            None => self.source.recv()?,
+        };
+
+        Some(self.coalesce(original_event))
+    }
+}
+
+/// How loudly an event gets reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Only kept in the log and journal.
+    Log,
+    /// A regular notification.
+    Notice,
+    /// Something about the change looks unusual.
+    Elevated,
+    /// Something about the change is known to be bad.
+    Critical,
+}
+
+/// An event that made it through the pipeline, along with how it should be reported.
+#[derive(Debug)]
+pub struct Alert {
+    pub event: FilteredEventData,
+    pub severity: Severity,
+    /// Why the severity is what it is, meant for showing to a user.
+    pub reasons: Vec<String>,
+    /// How unusual the event looked, if scoring is turned on.
+    pub score: Option<u32>,
+    /// If the change was found by an inventory scan, instead of being seen as it happened.
+    pub detected_by_scan: bool,
+}
+
+impl Alert {
+    fn new(event: FilteredEventData) -> Self {
+        Self {
+            event,
+            severity: Severity::Notice,
+            reasons: Vec::new(),
+            score: None,
+            detected_by_scan: false,
+        }
+    }
+
+    fn raise(&mut self, severity: Severity, reason: String) {
+        self.severity = self.severity.max(severity);
+        self.reasons.push(reason);
+    }
+}
+
+/// Takes raw events from a source through coalescing and the config's rules, handing
+/// everything that survives to each sink.
+pub struct Pipeline {
+    config: Config,
+    canaries: Canaries,
+    blocklist: ExecutableList,
+    allowlist: ExecutableList,
+    stores: Stores,
+    scorer: Scorer,
+    bursts: BurstAggregator,
+    /// Only present when there's something to take inventory of.
+    inventory: Option<Scanner>,
+    /// Every sink, along with the lowest severity it wants to hear about.
+    sinks: Vec<(Severity, Box<dyn Sink>)>,
+}
+
+impl Pipeline {
+    pub fn new(config: Config, stores: Stores) -> Self {
+        Self {
+            canaries: Canaries::new(&config.canaries),
+            blocklist: ExecutableList::load(&config.blocklist),
+            allowlist: ExecutableList::load(&config.allowlist),
+            config,
+            stores,
+            scorer: Scorer::default(),
+            bursts: BurstAggregator::default(),
+            inventory: None,
+            sinks: Vec::new(),
+        }
+    }
+
+    /// Lets removed canaries be noticed, by checking on them in `store`.
+    pub fn set_canary_store(&mut self, store: Box<dyn CanaryStore>) {
+        self.canaries.set_store(store);
+    }
+
+    /// Catches changes made while nothing was watching, by taking inventory with `scanner`
+    /// when the pipeline starts and then periodically.
+    pub fn set_inventory(&mut self, scanner: Scanner) {
+        self.inventory = Some(scanner);
+    }
+
+    /// Adds a sink that gets every alert at or above `min_severity`.
+    pub fn add_sink(&mut self, min_severity: Severity, sink: Box<dyn Sink>) {
+        self.sinks.push((min_severity, sink));
+    }
+
+    /// Processes events until the source runs dry.
+    pub fn run(&mut self, source: impl EventSource) {
+        let mut events = Coalescer::new(source);
+
+        loop {
+            if self.inventory.as_ref().is_some_and(Scanner::is_due) {
+                self.scan_inventory();
+            }
+
+            match events.next_timeout(IDLE_INTERVAL) {
+                Ok(ev) => self.process(ev),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            for summary in self.bursts.expire() {
+                self.deliver_summary(&summary);
+            }
+
+            for (_, sink) in &mut self.sinks {
+                sink.tick();
+            }
+        }
+
+        for summary in self.bursts.drain() {
+            self.deliver_summary(&summary);
+        }
+    }
+
+    fn scan_inventory(&mut self) {
+        let detected = match &mut self.inventory {
+            Some(scanner) => scanner.scan(),
+            None => return,
+        };
+
+        for ev in detected {
+            let mut alert = Alert::new(ev);
+            alert.detected_by_scan = true;
+            alert.reasons.push(String::from(
+                "Detected by scan: this changed while nothing was watching",
+            ));
+
+            self.judge(alert);
+        }
+    }
+
+    fn process(&mut self, ev: FilteredEventData) {
+        if let Some(scanner) = &mut self.inventory {
+            scanner.note_live(&ev);
+        }
+
+        self.judge(Alert::new(ev));
+    }
+
+    fn judge(&mut self, mut alert: Alert) {
+        // Nothing has any reason to touch a canary, so they always get through.
+        if let Some(canary) = self.canaries.touched_by(&alert.event) {
+            log::warn!("canary {canary} was {}", alert.event.kind_name());
+            alert.raise(
+                Severity::Critical,
+                format!("Canary: {canary} is a decoy that nothing should touch"),
+            );
+            self.deliver(&alert);
+            return;
+        }
+
+        // Known bad changers are always reported, no matter what else the config says.
+        if let Some(reason) = self.blocklist.matches_with_ancestry(alert.event.changer()) {
+            log::warn!("blocklisted changer: {reason}");
+            alert.raise(Severity::Critical, format!("Blocklisted: {reason}"));
+            self.deliver(&alert);
+            return;
+        }
+
+        if self
+            .config
+            .ignored_items
+            .iter()
+            .any(|ignored| ignored.matches(&alert.event))
+        {
+            log::debug!("skipping change, it matched an ignored item");
+            return;
+        }
+
+        if self.config.scoring.enabled {
+            let scoring = &self.config.scoring;
+
+            // This has to be checked before the fingerprints get a chance to remember this change.
+            let first_time = alert.event.item_title().is_some_and(|item_title| {
+                !self
+                    .stores
+                    .fingerprints
+                    .has_touched(item_title, alert.event.changer())
+            });
+
+            let signals = self.scorer.signals(scoring, &alert.event, first_time);
+            let score = scoring::score(&signals, &scoring.weights);
+            log::debug!("scored change at {score}: {signals:?}");
+
+            alert.severity = scoring.thresholds.severity_for(score);
+            alert.score = Some(score);
+
+            let described = signals.describe();
+            if !described.is_empty() {
+                alert
+                    .reasons
+                    .push(format!("Scored {score}: {}", described.join(", ")));
+            }
+        }
+
+        if let Some(item_title) = alert.event.item_title() {
+            if let Some(finding) = self
+                .stores
+                .fingerprints
+                .observe(item_title, alert.event.changer())
+            {
+                log::warn!("suspicious changer of {item_title}: {}", finding.describe());
+                alert.raise(Severity::Elevated, finding.describe());
+            }
+        }
+
+        if alert.severity <= Severity::Notice {
+            if let Some(reason) = self.allowlist.matches(alert.event.changer()) {
+                log::debug!("allowlisted changer: {reason}");
+                alert.severity = Severity::Log;
+                alert.reasons.push(format!("Allowlisted: {reason}"));
+            }
+        }
+
+        if let Some(baseline) = &mut self.stores.baseline {
+            match baseline.judge(&alert.event) {
+                // Only canaries and blocklisted changers get through while learning, and they were
+                // delivered already.
+                Verdict::Learning => {
+                    alert.severity = Severity::Log;
+                    alert
+                        .reasons
+                        .push(String::from("Learning what normal activity looks like"));
+                }
+                Verdict::Known if alert.severity <= Severity::Notice => {
+                    alert.severity = Severity::Log;
+                    alert
+                        .reasons
+                        .push(String::from("Matches the learned baseline"));
+                }
+                Verdict::Deviation => {
+                    alert.reasons.push(String::from(
+                        "This changer hasn't done this to this item before",
+                    ));
+                }
+                Verdict::Known => {}
+            }
+        }
+
+        self.deliver(&alert);
+    }
+
+    fn deliver(&mut self, alert: &Alert) {
+        let (held, ended) = self.bursts.observe(&self.config.bursts, alert);
+
+        for summary in ended {
+            self.deliver_summary(&summary);
+        }
+
+        for (min_severity, sink) in &mut self.sinks {
+            if alert.severity < *min_severity || (held && sink.interrupts()) {
+                continue;
+            }
+
+            sink.deliver(alert);
+        }
+    }
+
+    fn deliver_summary(&mut self, summary: &BurstSummary) {
+        for (min_severity, sink) in &mut self.sinks {
+            if summary.severity >= *min_severity {
+                sink.deliver_summary(summary);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, path::Path, rc::Rc};
+
+    use super::*;
+    use crate::{capture, events::Backend, fingerprint::FingerprintStore};
+
+    /// A capture of Safari editing and adding passwords, then `security` editing one of them.
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/capture-safari-then-security.jsonl"
+    );
+
+    /// A capture of KWallet, the Secret Service and a credential file changing one after another.
+    const DESKTOP_FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/capture-kwallet-and-secret-service.jsonl"
+    );
+
+    fn replay() -> capture::FastReplay {
+        replay_from(FIXTURE)
+    }
+
+    fn replay_from(fixture: &str) -> capture::FastReplay {
+        let events = capture::read_capture(Path::new(fixture)).expect("fixture is readable");
+        capture::FastReplay::new(events)
+    }
+
+    /// What kind of change an alert was for, what it was to, and how loud it was.
+    type Delivered = (&'static str, Option<String>, Severity);
+
+    /// Keeps what it's given, so it can be looked at after the pipeline ran.
+    struct Collector(Rc<RefCell<Vec<Delivered>>>);
+
+    impl Sink for Collector {
+        fn deliver(&mut self, alert: &Alert) {
+            self.0.borrow_mut().push((
+                alert.event.kind_name(),
+                alert.event.item_title().map(str::to_owned),
+                alert.severity,
+            ));
+        }
+
+        fn deliver_summary(&mut self, _summary: &BurstSummary) {}
+    }
+
+    #[test]
+    fn coalesces_removals_followed_by_additions() {
+        let events: Vec<(&str, Option<String>)> = Coalescer::new(replay())
+            .map(|ev| (ev.kind_name(), ev.item_title().map(str::to_owned)))
+            .collect();
+
+        assert_eq!(
+            events,
+            [
+                ("updated", Some(String::from("github.com"))),
+                ("added", Some(String::from("news.ycombinator.com"))),
+                ("updated", Some(String::from("github.com"))),
+                ("updated", Some(String::from("handoff-own-encryption-key"))),
+                ("removed", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_coalesces_removals_and_additions_of_the_same_item() {
+        let events: Vec<(&str, Backend, Option<String>)> =
+            Coalescer::new(replay_from(DESKTOP_FIXTURE))
+                .map(|ev| {
+                    (
+                        ev.kind_name(),
+                        ev.backend(),
+                        ev.item_title().map(str::to_owned),
+                    )
+                })
+                .collect();
+
+        assert_eq!(
+            events,
+            [
+                // Different keystores that happened to change an item of the same name together.
+                (
+                    "removed",
+                    Backend::KWallet,
+                    Some(String::from("github.com"))
+                ),
+                (
+                    "added",
+                    Backend::SecretService,
+                    Some(String::from("github.com"))
+                ),
+                // Different entries of the same file.
+                (
+                    "removed",
+                    Backend::CredentialFile,
+                    Some(String::from("prod"))
+                ),
+                (
+                    "added",
+                    Backend::CredentialFile,
+                    Some(String::from("example.com"))
+                ),
+                (
+                    "updated",
+                    Backend::SecretService,
+                    Some(String::from("gitlab.com"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn replays_fixture_through_pipeline() {
+        let config: Config = toml::from_str(r#"ignored_items = ["handoff-own-encryption-key"]"#)
+            .expect("config is valid");
+        let stores = Stores {
+            fingerprints: FingerprintStore::in_memory(),
+            baseline: None,
+        };
+
+        let alerts = Rc::new(RefCell::new(Vec::new()));
+
+        let mut pipeline = Pipeline::new(config, stores);
+        pipeline.add_sink(Severity::Log, Box::new(Collector(Rc::clone(&alerts))));
+        pipeline.run(replay());
+
+        assert_eq!(
+            *alerts.borrow(),
+            [
+                (
+                    "updated",
+                    Some(String::from("github.com")),
+                    Severity::Notice
+                ),
+                (
+                    "added",
+                    Some(String::from("news.ycombinator.com")),
+                    Severity::Notice
+                ),
+                // A different executable touching an item Safari looks after is suspicious.
+                (
+                    "updated",
+                    Some(String::from("github.com")),
+                    Severity::Elevated
+                ),
+                ("removed", None, Severity::Notice),
+            ]
+        );
+    }
+}
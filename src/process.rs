@@ -0,0 +1,473 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::fingerprint::HashCache;
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux as platform;
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "macos")]
+use macos as platform;
+
+/// What was known about a process at the time it changed something.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ProcessInfo {
+    pub pid: i32,
+    /// Full path to the executable the process was running.
+    pub path: Option<PathBuf>,
+    /// The short name the kernel knows the process by.
+    pub name: Option<String>,
+    /// The user-facing name of the app, if the process was a GUI application.
+    pub app_name: Option<String>,
+    pub parent_pid: Option<i32>,
+    pub uid: Option<u32>,
+    /// When the process started, in a platform-specific unit.
+    ///
+    /// This is only meaningful for telling apart two processes that had the same pid.
+    pub start_time: Option<u64>,
+    /// Hex encoded SHA-256 of the executable, as it was on disk when the process was seen.
+    pub sha256: Option<String>,
+    /// If the executable had a valid code signature. Only known on macOS.
+    pub signed: Option<bool>,
+    /// The chain of processes that launched this one, starting with its parent and ending
+    /// at `launchd` or `init` when the whole chain could be followed.
+    pub ancestry: Vec<Ancestor>,
+}
+
+/// A process somewhere above the changer in its process tree.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Ancestor {
+    pub pid: i32,
+    pub name: Option<String>,
+    pub path: Option<PathBuf>,
+}
+
+impl Ancestor {
+    fn display_name(&self) -> &str {
+        self.path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .and_then(|name| name.to_str())
+            .or(self.name.as_deref())
+            .unwrap_or("Unknown")
+    }
+}
+
+impl From<&ProcessInfo> for Ancestor {
+    fn from(info: &ProcessInfo) -> Self {
+        Self {
+            pid: info.pid,
+            name: info.name.clone(),
+            path: info.path.clone(),
+        }
+    }
+}
+
+impl ProcessInfo {
+    /// A stub for a process that couldn't be looked at anymore.
+    ///
+    /// Changes that nothing says the maker of, like KWallet signals, kernel key notifications and
+    /// rewritten credential files, are put down to one with a pid of 0.
+    pub fn unknown(pid: i32) -> Self {
+        Self {
+            pid,
+            ..Self::default()
+        }
+    }
+
+    /// Looks up everything about `pid` right now, without any caching.
+    pub fn snapshot(pid: i32) -> Self {
+        platform::snapshot(pid)
+    }
+
+    /// A short rendering of who launched this process, meant for places with little room.
+    ///
+    /// Example: `zsh ← Terminal`
+    pub fn compact_chain(&self) -> Option<String> {
+        const SHOWN: usize = 2;
+
+        // Everything ends up at launchd or init eventually, so they aren't worth the space.
+        let ancestors: Vec<&str> = self
+            .ancestry
+            .iter()
+            .filter(|ancestor| ancestor.pid != 1)
+            .map(Ancestor::display_name)
+            .collect();
+
+        if ancestors.is_empty() {
+            return None;
+        }
+
+        let mut chain = ancestors[..ancestors.len().min(SHOWN)].join(" ← ");
+        if ancestors.len() > SHOWN {
+            chain.push_str(" ← …");
+        }
+
+        Some(chain)
+    }
+
+    /// Renders the whole chain from this process up to the top of the tree, with every detail known.
+    ///
+    /// Example: `security [812] /usr/bin/security ← zsh [640] /bin/zsh ← launchd [1] /sbin/launchd`
+    pub fn full_chain(&self) -> String {
+        let mut chain = String::new();
+
+        let this = Ancestor::from(self);
+        for (idx, process) in std::iter::once(&this).chain(&self.ancestry).enumerate() {
+            if idx > 0 {
+                chain.push_str(" ← ");
+            }
+
+            let _ = write!(chain, "{} [{}]", process.display_name(), process.pid);
+
+            if let Some(path) = &process.path {
+                let _ = write!(chain, " {}", path.display());
+            }
+        }
+
+        chain
+    }
+
+    /// If the process is known not to be an app a user would interact with. Only macOS can tell
+    /// apps apart, and only for processes it got to look at.
+    pub fn is_background(&self) -> bool {
+        platform::KNOWS_APPS && self.path.is_some() && self.app_name.is_none()
+    }
+
+    /// The best name available for showing this process to a user.
+    pub fn display_name(&self) -> Option<&str> {
+        self.app_name
+            .as_deref()
+            .or_else(|| {
+                self.path
+                    .as_ref()
+                    .and_then(|path| path.file_name())
+                    .and_then(|name| name.to_str())
+            })
+            .or(self.name.as_deref())
+    }
+}
+
+/// How far up the process tree ancestry is followed before giving up.
+const MAX_ANCESTRY_DEPTH: usize = 32;
+
+/// Walks up the process tree from the parent of `child`, using `lookup` to find each process.
+///
+/// The walk stops at the top of the tree, when a process can't be found anymore, or if a
+/// parent claims to be younger than its child, which means its pid was reused.
+pub fn walk_ancestry(
+    child: &ProcessInfo,
+    mut lookup: impl FnMut(i32) -> Option<ProcessInfo>,
+) -> Vec<Ancestor> {
+    let mut ancestry = Vec::new();
+
+    let mut next_pid = child.parent_pid;
+    let mut child_start = child.start_time;
+
+    while let Some(pid) = next_pid {
+        if pid <= 0 || ancestry.len() >= MAX_ANCESTRY_DEPTH {
+            break;
+        }
+
+        if ancestry.iter().any(|known: &Ancestor| known.pid == pid) {
+            break;
+        }
+
+        let parent = match lookup(pid) {
+            Some(parent) => parent,
+            None => break,
+        };
+
+        if let (Some(parent_start), Some(child_start)) = (parent.start_time, child_start) {
+            if parent_start > child_start {
+                break;
+            }
+        }
+
+        ancestry.push(Ancestor::from(&parent));
+
+        next_pid = parent.parent_pid;
+        child_start = parent.start_time;
+    }
+
+    ancestry
+}
+
+/// Captures of processes that were recently seen.
+///
+/// Entries are keyed by the process' start time alongside its pid, so a pid that gets reused
+/// by a new process never returns the details of the old one.
+#[derive(Default)]
+pub struct ProcessCache {
+    entries: HashMap<(i32, u64), ProcessInfo>,
+    hashes: HashCache,
+}
+
+impl ProcessCache {
+    /// How many processes are remembered before starting over.
+    const CAPACITY: usize = 256;
+
+    /// Finds details about `pid`, along with its fingerprint and the chain of processes that launched it.
+    pub fn lookup(&mut self, pid: i32) -> ProcessInfo {
+        let mut info = self.lookup_single(pid);
+
+        // This is checked every time, since the executable can be replaced while the process keeps running.
+        if let Some(fingerprint) = info
+            .path
+            .as_deref()
+            .and_then(|path| self.hashes.fingerprint(path))
+        {
+            info.sha256 = Some(fingerprint.sha256);
+            info.signed = fingerprint.signed;
+        }
+
+        info.ancestry = walk_ancestry(&info, |parent_pid| {
+            let parent = self.lookup_single(parent_pid);
+
+            // A process that couldn't be looked at will only have its pid filled in.
+            if parent.start_time.is_some() {
+                Some(parent)
+            } else {
+                None
+            }
+        });
+
+        info
+    }
+
+    fn lookup_single(&mut self, pid: i32) -> ProcessInfo {
+        let start_time = match platform::start_time(pid) {
+            Some(start_time) => start_time,
+            // The process is already gone, so there's nothing better to return.
+            None => return ProcessInfo::unknown(pid),
+        };
+
+        if let Some(info) = self.entries.get(&(pid, start_time)) {
+            return info.clone();
+        }
+
+        let info = ProcessInfo::snapshot(pid);
+
+        // If the process exited in between, its start time isn't useful for keying anymore.
+        if info.start_time == Some(start_time) {
+            if self.entries.len() >= Self::CAPACITY {
+                self.entries.clear();
+            }
+
+            self.entries.insert((pid, start_time), info.clone());
+        }
+
+        info
+    }
+}
+
+/// Checks if the executable at `path` has a valid code signature, if the platform has such a thing.
+pub fn code_signed(path: &Path) -> Option<bool> {
+    platform::code_signed(path)
+}
+
+/// Reads the changer of an event, accepting both full process details and the bare pids
+/// that older capture files contain.
+pub fn deserialize_changer<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ProcessInfo, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Changer {
+        Pid(i32),
+        Info(ProcessInfo),
+    }
+
+    Ok(match Changer::deserialize(deserializer)? {
+        Changer::Pid(pid) => ProcessInfo::unknown(pid),
+        Changer::Info(info) => info,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    use super::ProcessInfo;
+    use std::path::Path;
+
+    pub const KNOWS_APPS: bool = false;
+
+    pub fn start_time(_pid: i32) -> Option<u64> {
+        None
+    }
+
+    pub fn snapshot(pid: i32) -> ProcessInfo {
+        ProcessInfo::unknown(pid)
+    }
+
+    pub fn code_signed(_path: &Path) -> Option<bool> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ancestor(pid: i32, name: &str, path: Option<&str>) -> Ancestor {
+        Ancestor {
+            pid,
+            name: Some(name.to_owned()),
+            path: path.map(PathBuf::from),
+        }
+    }
+
+    fn security() -> ProcessInfo {
+        ProcessInfo {
+            pid: 812,
+            path: Some(PathBuf::from("/usr/bin/security")),
+            name: Some(String::from("security")),
+            ancestry: vec![
+                ancestor(640, "zsh", Some("/bin/zsh")),
+                ancestor(1, "launchd", Some("/sbin/launchd")),
+            ],
+            ..ProcessInfo::default()
+        }
+    }
+
+    #[test]
+    fn renders_chains() {
+        let security = security();
+
+        assert_eq!(security.compact_chain().as_deref(), Some("zsh"));
+        assert_eq!(
+            security.full_chain(),
+            "security [812] /usr/bin/security ← zsh [640] /bin/zsh ← launchd [1] /sbin/launchd"
+        );
+    }
+
+    #[test]
+    fn renders_long_chains() {
+        let mut deep = security();
+        deep.ancestry = vec![
+            ancestor(640, "zsh", Some("/bin/zsh")),
+            ancestor(600, "tmux: server", None),
+            ancestor(550, "sshd", Some("/usr/sbin/sshd")),
+            ancestor(1, "init", None),
+        ];
+
+        assert_eq!(
+            deep.compact_chain().as_deref(),
+            Some("zsh ← tmux: server ← …")
+        );
+        assert_eq!(
+            deep.full_chain(),
+            "security [812] /usr/bin/security ← zsh [640] /bin/zsh ← tmux: server [600] ← \
+             sshd [550] /usr/sbin/sshd ← init [1]"
+        );
+
+        let orphan = ProcessInfo::unknown(99);
+        assert_eq!(orphan.compact_chain(), None);
+        assert_eq!(orphan.full_chain(), "Unknown [99]");
+    }
+
+    /// A process tree of `(pid, parent, start time)`.
+    fn tree(processes: &[(i32, i32, u64)]) -> impl FnMut(i32) -> Option<ProcessInfo> + '_ {
+        |pid| {
+            let (pid, parent, start) = processes.iter().find(|process| process.0 == pid)?;
+            Some(ProcessInfo {
+                pid: *pid,
+                parent_pid: Some(*parent),
+                start_time: Some(*start),
+                ..ProcessInfo::default()
+            })
+        }
+    }
+
+    fn pids(ancestry: &[Ancestor]) -> Vec<i32> {
+        ancestry.iter().map(|ancestor| ancestor.pid).collect()
+    }
+
+    #[test]
+    fn walks_up_to_the_top() {
+        let child = ProcessInfo {
+            pid: 812,
+            parent_pid: Some(640),
+            start_time: Some(300),
+            ..ProcessInfo::default()
+        };
+
+        let processes = [(640, 550, 200), (550, 1, 100), (1, 0, 0)];
+        assert_eq!(
+            pids(&walk_ancestry(&child, tree(&processes))),
+            [640, 550, 1]
+        );
+
+        // A parent younger than its child got a pid that was reused.
+        let processes = [(640, 550, 200), (550, 1, 250), (1, 0, 0)];
+        assert_eq!(pids(&walk_ancestry(&child, tree(&processes))), [640]);
+
+        // Gone parents, and loops, end the walk.
+        let processes = [(640, 550, 200)];
+        assert_eq!(pids(&walk_ancestry(&child, tree(&processes))), [640]);
+        let processes = [(640, 550, 200), (550, 640, 100)];
+        assert_eq!(pids(&walk_ancestry(&child, tree(&processes))), [640, 550]);
+    }
+
+    #[test]
+    fn stops_walking_deep_trees() {
+        let child = ProcessInfo {
+            pid: 1000,
+            parent_pid: Some(999),
+            ..ProcessInfo::default()
+        };
+
+        let processes: Vec<_> = (2..1000).map(|pid| (pid, pid - 1, 0)).collect();
+        assert_eq!(
+            walk_ancestry(&child, tree(&processes)).len(),
+            MAX_ANCESTRY_DEPTH
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn walks_this_process_ancestry() {
+        let this = ProcessCache::default().lookup(std::process::id() as i32);
+
+        assert_eq!(this.ancestry[0].pid, unsafe { libc::getppid() });
+
+        let mut seen: Vec<i32> = pids(&this.ancestry);
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!(seen.len(), this.ancestry.len());
+        assert!(this.full_chain().starts_with(&format!(
+            "{} [{}]",
+            this.display_name().unwrap(),
+            this.pid
+        )));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reused_pids_are_looked_up_again() {
+        let pid = std::process::id() as i32;
+        let start_time = platform::start_time(pid).unwrap();
+        let stale = ProcessInfo {
+            name: Some(String::from("exited")),
+            ..ProcessInfo::unknown(pid)
+        };
+
+        // An earlier process that had the same pid.
+        let mut cache = ProcessCache::default();
+        cache.entries.insert((pid, start_time - 1), stale.clone());
+        assert_eq!(cache.lookup_single(pid), ProcessInfo::snapshot(pid));
+
+        // The same process is only looked at once.
+        cache.entries.insert((pid, start_time), stale.clone());
+        assert_eq!(cache.lookup_single(pid), stale);
+    }
+}
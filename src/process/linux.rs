@@ -0,0 +1,111 @@
+use std::{fs, path::Path};
+
+use super::ProcessInfo;
+
+/// Linux has no notion of which processes are apps, just windows.
+pub const KNOWS_APPS: bool = false;
+
+/// The parts of `/proc/<pid>/stat` that are interesting.
+struct Stat {
+    name: String,
+    parent_pid: i32,
+    start_time: u64,
+}
+
+fn read_stat(pid: i32) -> Option<Stat> {
+    parse_stat(&fs::read_to_string(format!("/proc/{pid}/stat")).ok()?)
+}
+
+fn parse_stat(stat: &str) -> Option<Stat> {
+    // The name is wrapped in parens but can contain anything, including more parens,
+    // so the last one is the only reliable end marker.
+    let name_start = stat.find('(')?;
+    let name_end = stat.rfind(')')?;
+    let name = stat.get(name_start + 1..name_end)?.to_owned();
+
+    // Fields after the name, starting at field 3 (`state`).
+    let mut fields = stat.get(name_end + 1..)?.split_whitespace();
+
+    let parent_pid = fields.nth(1)?.parse().ok()?;
+    // `starttime` is field 22, in clock ticks since boot.
+    let start_time = fields.nth(17)?.parse().ok()?;
+
+    Some(Stat {
+        name,
+        parent_pid,
+        start_time,
+    })
+}
+
+fn read_uid(pid: i32) -> Option<u32> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().next())
+        .and_then(|real_uid| real_uid.parse().ok())
+}
+
+pub fn start_time(pid: i32) -> Option<u64> {
+    read_stat(pid).map(|stat| stat.start_time)
+}
+
+pub fn snapshot(pid: i32) -> ProcessInfo {
+    let stat = match read_stat(pid) {
+        Some(stat) => stat,
+        None => return ProcessInfo::unknown(pid),
+    };
+
+    ProcessInfo {
+        pid,
+        path: fs::read_link(format!("/proc/{pid}/exe")).ok(),
+        name: Some(stat.name),
+        app_name: None,
+        parent_pid: Some(stat.parent_pid),
+        uid: read_uid(pid),
+        start_time: Some(stat.start_time),
+        sha256: None,
+        signed: None,
+        ancestry: Vec::new(),
+    }
+}
+
+/// Linux has no system-wide notion of signed executables.
+pub fn code_signed(_path: &Path) -> Option<bool> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_names_with_spaces_and_parens() {
+        let stat = parse_stat(
+            "4242 (tmux: server (1)) S 640 4242 4242 34816 4242 4194304 120 0 0 0 3 1 0 0 20 0 1 \
+             0 987654 10723328 1066 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 17 3 0 0 0 0 0\n",
+        )
+        .unwrap();
+
+        assert_eq!(stat.name, "tmux: server (1)");
+        assert_eq!(stat.parent_pid, 640);
+        assert_eq!(stat.start_time, 987654);
+    }
+
+    #[test]
+    fn refuses_cut_short_stats() {
+        assert!(parse_stat("4242 (sh) S 640 4242").is_none());
+        assert!(parse_stat("4242 sh S 640").is_none());
+    }
+
+    #[test]
+    fn reads_this_process() {
+        let this = snapshot(std::process::id() as i32);
+
+        assert_eq!(this.parent_pid, Some(unsafe { libc::getppid() }));
+        assert_eq!(this.path, std::env::current_exe().ok());
+        assert_eq!(this.uid, Some(unsafe { libc::getuid() }));
+        assert!(this.start_time.is_some());
+    }
+}
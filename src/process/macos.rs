@@ -0,0 +1,139 @@
+use core_foundation::{
+    base::{CFRelease, CFTypeRef, TCFType},
+    url::{CFURLRef, CFURL},
+};
+use objc::{msg_send, sel, sel_impl};
+use objc_foundation::{INSString, NSString};
+use objc_id::Id;
+use security_framework_sys::base::errSecSuccess;
+use std::{
+    ffi::{CStr, OsStr},
+    mem,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    ptr::{self, NonNull},
+};
+
+use super::ProcessInfo;
+use crate::bindings::{
+    kSecCSBasicValidateOnly, kSecCSDefaultFlags, SecStaticCodeCheckValidity,
+    SecStaticCodeCreateWithPath, SecStaticCodeRef,
+};
+
+/// GUI apps have an `NSRunningApplication`, anything else doesn't.
+pub const KNOWS_APPS: bool = true;
+
+fn bsd_info(pid: i32) -> Option<libc::proc_bsdinfo> {
+    let mut info: libc::proc_bsdinfo = unsafe { mem::zeroed() };
+    let size = mem::size_of::<libc::proc_bsdinfo>() as i32;
+
+    let written = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            libc::PROC_PIDTBSDINFO,
+            0,
+            (&mut info as *mut libc::proc_bsdinfo).cast(),
+            size,
+        )
+    };
+
+    if written == size {
+        Some(info)
+    } else {
+        None
+    }
+}
+
+fn start_time_of(info: &libc::proc_bsdinfo) -> u64 {
+    info.pbi_start_tvsec * 1_000_000 + info.pbi_start_tvusec
+}
+
+fn executable_path(pid: i32) -> Option<PathBuf> {
+    let mut buf = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+
+    let len = unsafe { libc::proc_pidpath(pid, buf.as_mut_ptr().cast(), buf.len() as u32) };
+
+    if len > 0 {
+        buf.truncate(len as usize);
+        Some(PathBuf::from(OsStr::from_bytes(&buf)))
+    } else {
+        None
+    }
+}
+
+/// Finds the user-facing name of a GUI application, if `pid` is one.
+fn running_app_name(pid: i32) -> Option<String> {
+    let nsapp_class = objc::class!(NSRunningApplication);
+
+    let running_app: Option<NonNull<objc::runtime::Object>> =
+        unsafe { msg_send![nsapp_class, runningApplicationWithProcessIdentifier: pid] };
+
+    let app = running_app?;
+
+    let app_name: Option<NonNull<NSString>> = unsafe { msg_send![app.as_ptr(), localizedName] };
+    let app_name = app_name.map(|obj| unsafe { Id::<NSString>::from_ptr(obj.as_ptr()) });
+
+    if let Some(app_name) = app_name {
+        return Some(app_name.as_str().to_owned());
+    }
+
+    let exe_path: Option<NonNull<CFURLRef>> = unsafe { msg_send![app.as_ptr(), executableURL] };
+    exe_path
+        .and_then(|obj| unsafe { CFURL::wrap_under_get_rule(obj.as_ptr().cast()).to_path() })
+        .and_then(|path| Some(path.file_name()?.to_str()?.to_owned()))
+}
+
+pub fn start_time(pid: i32) -> Option<u64> {
+    bsd_info(pid).map(|info| start_time_of(&info))
+}
+
+pub fn snapshot(pid: i32) -> ProcessInfo {
+    let info = match bsd_info(pid) {
+        Some(info) => info,
+        None => return ProcessInfo::unknown(pid),
+    };
+
+    // `pbi_name` is the longer of the two, but is sometimes left empty.
+    let name = [&info.pbi_name[..], &info.pbi_comm[..]]
+        .into_iter()
+        .filter(|raw| raw.contains(&0))
+        .map(|raw| unsafe { CStr::from_ptr(raw.as_ptr()) })
+        .find(|name| !name.to_bytes().is_empty())
+        .map(|name| name.to_string_lossy().into_owned());
+
+    ProcessInfo {
+        pid,
+        path: executable_path(pid),
+        name,
+        app_name: running_app_name(pid),
+        parent_pid: Some(info.pbi_ppid as i32),
+        uid: Some(info.pbi_uid),
+        start_time: Some(start_time_of(&info)),
+        sha256: None,
+        signed: None,
+        ancestry: Vec::new(),
+    }
+}
+
+/// Validates the code signature of an executable, without checking the contents of its bundle
+/// resources since that's too slow to do for every event.
+pub fn code_signed(path: &Path) -> Option<bool> {
+    let url = CFURL::from_path(path, false)?;
+
+    let mut code: SecStaticCodeRef = ptr::null_mut();
+    let status = unsafe {
+        SecStaticCodeCreateWithPath(url.as_concrete_TypeRef(), kSecCSDefaultFlags, &mut code)
+    };
+
+    if status != errSecSuccess || code.is_null() {
+        log::debug!("can't check the signature of {}: {status}", path.display());
+        return None;
+    }
+
+    let status =
+        unsafe { SecStaticCodeCheckValidity(code, kSecCSBasicValidateOnly, ptr::null_mut()) };
+    unsafe { CFRelease(code as CFTypeRef) };
+
+    // Unsigned code and code with a broken signature are both reported as invalid here.
+    Some(status == errSecSuccess)
+}
@@ -1,6 +1,11 @@
 use std::{ffi::CStr, os::unix::prelude::OsStrExt, path::Path, ptr};
 
-pub fn init_sandbox(home: &Path, data_dir: &Path, service_name: &'static str) {
+pub fn init_sandbox(
+    home: &Path,
+    data_dir: &Path,
+    service_name: &'static str,
+    control_service_name: &'static str,
+) {
     log::debug!("wrapping sandbox...");
 
     static PROFILE: &str = concat!(include_str!("../resources/sandbox.sb"), "\0");
@@ -32,6 +37,9 @@ pub fn init_sandbox(home: &Path, data_dir: &Path, service_name: &'static str) {
     let mut service_name = service_name.as_bytes().to_vec();
     service_name.push(0);
 
+    let mut control_service_name = control_service_name.as_bytes().to_vec();
+    control_service_name.push(0);
+
     let params = [
         b"BUNDLE_PATH\0".as_ptr().cast(),
         bundle_dir.as_ptr().cast(),
@@ -41,6 +49,8 @@ pub fn init_sandbox(home: &Path, data_dir: &Path, service_name: &'static str) {
         data_dir.as_ptr().cast(),
         b"PING_SERVICE_NAME\0".as_ptr().cast(),
         service_name.as_ptr().cast(),
+        b"CONTROL_SERVICE_NAME\0".as_ptr().cast(),
+        control_service_name.as_ptr().cast(),
         ptr::null(),
     ];
 
@@ -0,0 +1,391 @@
+use serde::Deserialize;
+
+use crate::{
+    bursts::{BurstAggregator, BurstConfig},
+    clock::{self, LocalTime},
+    events::FilteredEventData,
+    pipeline::Severity,
+};
+
+/// Settings for scoring how unusual each event is.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct ScoringConfig {
+    /// If scores should decide the severity of events, instead of everything being a regular notification.
+    pub(crate) enabled: bool,
+    pub(crate) weights: Weights,
+    pub(crate) thresholds: Thresholds,
+    pub(crate) off_hours: OffHours,
+    pub(crate) burst: BurstConfig,
+}
+
+/// How many points each signal adds to an event's score.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct Weights {
+    pub(crate) first_time: u32,
+    pub(crate) removal: u32,
+    pub(crate) burst: u32,
+    pub(crate) not_gui_app: u32,
+    pub(crate) unsigned: u32,
+    pub(crate) unknown_binary: u32,
+    pub(crate) off_hours: u32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            first_time: 30,
+            removal: 15,
+            burst: 20,
+            not_gui_app: 15,
+            unsigned: 30,
+            unknown_binary: 25,
+            off_hours: 15,
+        }
+    }
+}
+
+/// The lowest score that results in each severity. Anything lower than `notice` is only logged.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct Thresholds {
+    pub(crate) notice: u32,
+    pub(crate) elevated: u32,
+    pub(crate) critical: u32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            notice: 20,
+            elevated: 60,
+            critical: 90,
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn severity_for(&self, score: u32) -> Severity {
+        if score >= self.critical {
+            Severity::Critical
+        } else if score >= self.elevated {
+            Severity::Elevated
+        } else if score >= self.notice {
+            Severity::Notice
+        } else {
+            Severity::Log
+        }
+    }
+}
+
+/// The part of the day that changes are unexpected in, in local time.
+///
+/// The range wraps around midnight when `start_hour` is after `end_hour`.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct OffHours {
+    pub(crate) start_hour: u8,
+    pub(crate) end_hour: u8,
+}
+
+impl Default for OffHours {
+    fn default() -> Self {
+        Self {
+            start_hour: 22,
+            end_hour: 7,
+        }
+    }
+}
+
+impl OffHours {
+    pub fn contains(&self, time: &LocalTime) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&time.hour)
+        } else {
+            time.hour >= self.start_hour || time.hour < self.end_hour
+        }
+    }
+}
+
+/// Everything about an event that can make it look unusual.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Signals {
+    /// The changer has never touched this item before.
+    pub first_time: bool,
+    pub removal: bool,
+    /// The changer made lots of changes in a short amount of time.
+    pub burst: bool,
+    /// The changer isn't an app that a user would interact with.
+    pub not_gui_app: bool,
+    /// The changer's executable doesn't have a valid code signature.
+    pub unsigned: bool,
+    /// The changer's executable couldn't be found or fingerprinted.
+    pub unknown_binary: bool,
+    pub off_hours: bool,
+}
+
+impl Signals {
+    /// Describes every signal that's present.
+    pub fn describe(&self) -> Vec<&'static str> {
+        [
+            (self.first_time, "first change to this item by this changer"),
+            (self.removal, "item removed"),
+            (self.burst, "part of a burst of changes"),
+            (self.not_gui_app, "not made by a GUI app"),
+            (self.unsigned, "changer isn't code signed"),
+            (self.unknown_binary, "changer's executable is unknown"),
+            (self.off_hours, "made during off hours"),
+        ]
+        .into_iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, description)| description)
+        .collect()
+    }
+}
+
+/// Adds up the weights of every signal that's present.
+pub fn score(signals: &Signals, weights: &Weights) -> u32 {
+    [
+        (signals.first_time, weights.first_time),
+        (signals.removal, weights.removal),
+        (signals.burst, weights.burst),
+        (signals.not_gui_app, weights.not_gui_app),
+        (signals.unsigned, weights.unsigned),
+        (signals.unknown_binary, weights.unknown_binary),
+        (signals.off_hours, weights.off_hours),
+    ]
+    .into_iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, weight)| weight)
+    .sum()
+}
+
+/// Works out the signals of each event, keeping track of the recent history needed to do so.
+#[derive(Default)]
+pub struct Scorer {
+    /// Only used to count recent changes, so it never holds anything back.
+    recent: BurstAggregator,
+}
+
+impl Scorer {
+    /// `first_time` has to come from somewhere that remembers which changers touched which items.
+    pub fn signals(
+        &mut self,
+        config: &ScoringConfig,
+        event: &FilteredEventData,
+        first_time: bool,
+    ) -> Signals {
+        let changer = event.changer();
+        let changed_at = event.changed_at();
+
+        Signals {
+            first_time,
+            removal: matches!(event, FilteredEventData::Removed { .. }),
+            burst: self.recent.record(changer, changed_at, &config.burst) > config.burst.events,
+            not_gui_app: changer.is_background(),
+            unsigned: changer.signed == Some(false),
+            unknown_binary: changer.path.is_none() || changer.sha256.is_none(),
+            off_hours: config
+                .off_hours
+                .contains(&LocalTime::at_unix(clock::cf_to_unix(changed_at))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{
+        events::{Backend, InnerDetails},
+        process::ProcessInfo,
+    };
+
+    fn changer(path: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid: 812,
+            path: Some(PathBuf::from(path)),
+            sha256: Some(String::from("c3")),
+            signed: Some(true),
+            ..ProcessInfo::default()
+        }
+    }
+
+    fn added(by: &ProcessInfo, at: f64) -> FilteredEventData {
+        FilteredEventData::Added(InnerDetails::new(
+            Backend::Keychain,
+            String::from("github.com"),
+            at,
+            by.clone(),
+        ))
+    }
+
+    /// Off hours that never start, so the time zone tests run in doesn't matter.
+    fn config() -> ScoringConfig {
+        ScoringConfig {
+            off_hours: OffHours {
+                start_hour: 0,
+                end_hour: 0,
+            },
+            ..ScoringConfig::default()
+        }
+    }
+
+    #[test]
+    fn scores_add_up_present_weights() {
+        let weights = Weights::default();
+
+        assert_eq!(score(&Signals::default(), &weights), 0);
+        assert_eq!(
+            score(
+                &Signals {
+                    first_time: true,
+                    unsigned: true,
+                    off_hours: true,
+                    ..Signals::default()
+                },
+                &weights
+            ),
+            30 + 30 + 15
+        );
+
+        let everything = Signals {
+            first_time: true,
+            removal: true,
+            burst: true,
+            not_gui_app: true,
+            unsigned: true,
+            unknown_binary: true,
+            off_hours: true,
+        };
+        assert_eq!(score(&everything, &weights), 150);
+        assert_eq!(everything.describe().len(), 7);
+    }
+
+    #[test]
+    fn thresholds_pick_the_highest_severity_reached() {
+        let thresholds = Thresholds::default();
+
+        assert_eq!(thresholds.severity_for(0), Severity::Log);
+        assert_eq!(thresholds.severity_for(19), Severity::Log);
+        assert_eq!(thresholds.severity_for(20), Severity::Notice);
+        assert_eq!(thresholds.severity_for(59), Severity::Notice);
+        assert_eq!(thresholds.severity_for(60), Severity::Elevated);
+        assert_eq!(thresholds.severity_for(90), Severity::Critical);
+        assert_eq!(thresholds.severity_for(u32::MAX), Severity::Critical);
+    }
+
+    #[test]
+    fn off_hours_wrap_around_midnight() {
+        let at = |hour| LocalTime {
+            weekday: 3,
+            hour,
+            minute: 30,
+            second: 0,
+        };
+
+        let night = OffHours::default();
+        assert!(night.contains(&at(22)));
+        assert!(night.contains(&at(3)));
+        assert!(!night.contains(&at(7)));
+        assert!(!night.contains(&at(12)));
+
+        let lunch = OffHours {
+            start_hour: 12,
+            end_hour: 13,
+        };
+        assert!(lunch.contains(&at(12)));
+        assert!(!lunch.contains(&at(13)));
+        assert!(!lunch.contains(&at(11)));
+    }
+
+    #[test]
+    fn signals_come_from_the_event() {
+        let config = config();
+        let mut scorer = Scorer::default();
+        let security = changer("/usr/bin/security");
+
+        let signals = scorer.signals(&config, &added(&security, 100.0), true);
+        assert_eq!(
+            signals,
+            Signals {
+                first_time: true,
+                // Only macOS can tell that `security` isn't an app.
+                not_gui_app: cfg!(target_os = "macos"),
+                ..Signals::default()
+            }
+        );
+
+        let removed = FilteredEventData::Removed {
+            backend: Backend::Keychain,
+            seen_at: 101.0,
+            modified_by: ProcessInfo::unknown(900),
+            item_name: None,
+        };
+        let signals = scorer.signals(&config, &removed, false);
+        assert!(signals.removal);
+        assert!(signals.unknown_binary);
+        // Nothing can be known about an app that couldn't be looked at.
+        assert!(!signals.not_gui_app);
+
+        let unsigned = ProcessInfo {
+            signed: Some(false),
+            ..changer("/tmp/dropper")
+        };
+        assert!(
+            scorer
+                .signals(&config, &added(&unsigned, 102.0), false)
+                .unsigned
+        );
+    }
+
+    #[test]
+    fn signals_bursts_from_one_changer() {
+        let config = config();
+        let mut scorer = Scorer::default();
+        let security = changer("/usr/bin/security");
+        let safari = changer("/Applications/Safari.app/Contents/MacOS/Safari");
+
+        for at in 0..config.burst.events {
+            let signals = scorer.signals(&config, &added(&security, at as f64), false);
+            assert!(!signals.burst);
+        }
+
+        // Someone else being busy doesn't matter.
+        assert!(!scorer.signals(&config, &added(&safari, 20.0), false).burst);
+        assert!(
+            scorer
+                .signals(&config, &added(&security, 20.0), false)
+                .burst
+        );
+
+        // Going quiet for longer than the window is the end of it.
+        let later = 20.0 + config.burst.window_secs as f64 + 1.0;
+        assert!(
+            !scorer
+                .signals(&config, &added(&security, later), false)
+                .burst
+        );
+    }
+
+    #[test]
+    fn scoring_is_deterministic() {
+        let config = config();
+        let security = changer("/usr/bin/security");
+
+        let run = || {
+            let mut scorer = Scorer::default();
+            (0..30)
+                .map(|at| {
+                    let signals =
+                        scorer.signals(&config, &added(&security, at as f64), at % 3 == 0);
+                    score(&signals, &config.weights)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(), run());
+    }
+}
@@ -0,0 +1,122 @@
+mod digest;
+pub(crate) use digest::DigestConfig;
+pub use digest::{Digest, DigestSink};
+mod journal;
+pub use journal::JournalSink;
+mod schedule;
+pub(crate) use schedule::QuietHours;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub use schedule::SnoozeTarget;
+pub use schedule::{ScheduleSink, Snoozes};
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+mod notification;
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub use notification::NotificationSink;
+
+use serde::Deserialize;
+
+use crate::{
+    bursts::BurstSummary,
+    pipeline::{Alert, Severity},
+};
+
+/// The lowest severity each sink gets told about.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SinkConfig {
+    pub(crate) notifications: Severity,
+    pub(crate) journal: Severity,
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        Self {
+            notifications: Severity::Notice,
+            journal: Severity::Log,
+        }
+    }
+}
+
+/// A destination for events that made it through the pipeline.
+pub trait Sink {
+    fn deliver(&mut self, alert: &Alert);
+
+    /// Called once a burst of changes from one changer ends.
+    fn deliver_summary(&mut self, summary: &BurstSummary);
+
+    /// Called with a summary of everything that happened in a period, for sinks that are
+    /// wrapped in a `DigestSink`.
+    fn deliver_digest(&mut self, _digest: &Digest) {}
+
+    /// If this sink interrupts the user, in which case events that are part of a burst
+    /// are held back from it and only their summary gets delivered.
+    fn interrupts(&self) -> bool {
+        false
+    }
+
+    /// Called regularly, even while no events are arriving.
+    fn tick(&mut self) {}
+}
+
+/// Writes every event to the log.
+pub struct LogSink;
+
+impl Sink for LogSink {
+    fn deliver(&mut self, alert: &Alert) {
+        let event = &alert.event;
+
+        let level = if alert.severity >= Severity::Elevated {
+            log::Level::Warn
+        } else {
+            log::Level::Info
+        };
+
+        log::log!(
+            level,
+            "item {}: {} (changer: {})",
+            event.kind_name(),
+            event.item_title().unwrap_or("Unknown"),
+            event.changer().full_chain()
+        );
+
+        if let Some(item_class) = event.item_class() {
+            let mut described = event
+                .attributes()
+                .map(|attributes| attributes.describe())
+                .unwrap_or_default();
+            described.extend(
+                event
+                    .details()
+                    .iter()
+                    .map(|detail| format!("{} {}", detail.name, detail.value)),
+            );
+
+            if described.is_empty() {
+                log::log!(level, "  class: {item_class}");
+            } else {
+                log::log!(level, "  class: {item_class} ({})", described.join(", "));
+            }
+        }
+
+        for reason in &alert.reasons {
+            log::log!(level, "  {:?}: {reason}", alert.severity);
+        }
+    }
+
+    fn deliver_summary(&mut self, summary: &BurstSummary) {
+        let level = if summary.severity >= Severity::Elevated {
+            log::Level::Warn
+        } else {
+            log::Level::Info
+        };
+
+        log::log!(
+            level,
+            "burst of {} changes: {} (changer: {})",
+            summary.total(),
+            summary.describe(),
+            summary.changer.full_chain()
+        );
+        log::log!(level, "  items: {}", summary.items.join(", "));
+    }
+}
@@ -0,0 +1,364 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::Sink;
+use crate::{
+    bursts::BurstSummary,
+    clock::{unix_now, LocalTime},
+    pipeline::{Alert, Severity},
+    process::ProcessInfo,
+    state,
+};
+
+/// Settings for summarizing events on a schedule, instead of notifying about each one.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct DigestConfig {
+    pub(crate) enabled: bool,
+    pub(crate) period: DigestPeriod,
+    /// The local hour that daily digests are sent at.
+    pub(crate) at_hour: u8,
+    /// Events at or above this severity are still notified about right away.
+    pub(crate) realtime: Severity,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            period: DigestPeriod::Daily,
+            at_hour: 9,
+            realtime: Severity::Elevated,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DigestPeriod {
+    Hourly,
+    Daily,
+}
+
+impl DigestConfig {
+    /// When the digest after `now` is due, in seconds since the Unix epoch.
+    fn next_due(&self, now: u64) -> u64 {
+        let local = LocalTime::at_unix(now as i64);
+        let into_hour = u64::from(local.minute) * 60 + u64::from(local.second);
+
+        match self.period {
+            DigestPeriod::Hourly => now - into_hour + 60 * 60,
+            DigestPeriod::Daily => {
+                const DAY: u64 = 24 * 60 * 60;
+
+                let target = u64::from(self.at_hour.min(23)) * 60 * 60;
+                let into_day = local.second_of_day();
+
+                // Daylight saving changes can make this an hour off, which is fine for a digest.
+                if into_day < target {
+                    now + (target - into_day)
+                } else {
+                    now + (DAY - into_day) + target
+                }
+            }
+        }
+    }
+}
+
+/// Everything one changer did of one kind during a digest period.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DigestGroup {
+    pub changer: String,
+    /// `added`, `updated`, `removed`, `accessed`, `locked` or `unlocked`.
+    pub kind: String,
+    pub count: usize,
+    pub severity: Severity,
+}
+
+/// A summary of everything that happened during one period.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Digest {
+    /// Seconds since the Unix epoch that the period began at.
+    pub started_at: Option<u64>,
+    pub groups: Vec<DigestGroup>,
+}
+
+impl Digest {
+    pub fn add(&mut self, changer: &ProcessInfo, kind: &str, count: usize, severity: Severity) {
+        self.started_at.get_or_insert_with(unix_now);
+        let changer = match changer.display_name() {
+            Some(name) => name.to_owned(),
+            None => format!("Unknown Application ({})", changer.pid),
+        };
+
+        match self
+            .groups
+            .iter_mut()
+            .find(|group| group.changer == changer && group.kind == kind)
+        {
+            Some(group) => {
+                group.count += count;
+                group.severity = group.severity.max(severity);
+            }
+            None => self.groups.push(DigestGroup {
+                changer,
+                kind: kind.to_owned(),
+                count,
+                severity,
+            }),
+        }
+    }
+
+    pub fn add_summary(&mut self, summary: &BurstSummary) {
+        for (kind, count) in [
+            ("added", summary.added),
+            ("updated", summary.updated),
+            ("removed", summary.removed),
+            ("accessed", summary.accessed),
+            ("locked or unlocked", summary.lock_changes),
+        ] {
+            if count > 0 {
+                self.add(&summary.changer, kind, count, summary.severity);
+            }
+        }
+    }
+
+    /// Adds everything from `other` into this digest.
+    pub fn merge(&mut self, other: &Digest) {
+        if let Some(started_at) = other.started_at {
+            let ours = self.started_at.get_or_insert(started_at);
+            *ours = (*ours).min(started_at);
+        }
+
+        for theirs in &other.groups {
+            match self
+                .groups
+                .iter_mut()
+                .find(|group| group.changer == theirs.changer && group.kind == theirs.kind)
+            {
+                Some(group) => {
+                    group.count += theirs.count;
+                    group.severity = group.severity.max(theirs.severity);
+                }
+                None => self.groups.push(theirs.clone()),
+            }
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.groups.iter().map(|group| group.count).sum()
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.groups
+            .iter()
+            .map(|group| group.severity)
+            .max()
+            .unwrap_or(Severity::Log)
+    }
+
+    /// One line per changer, listing what it did.
+    ///
+    /// Example: `Chrome: added 12, updated 3`
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<(&str, String)> = Vec::new();
+
+        for group in &self.groups {
+            let part = format!("{} {}", group.kind, group.count);
+
+            match lines
+                .iter_mut()
+                .find(|(changer, _)| *changer == group.changer)
+            {
+                Some((_, line)) => {
+                    line.push_str(", ");
+                    line.push_str(&part);
+                }
+                None => lines.push((&group.changer, part)),
+            }
+        }
+
+        lines
+            .into_iter()
+            .map(|(changer, line)| format!("{changer}: {line}"))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct DigestState {
+    /// Seconds since the Unix epoch that the pending digest gets sent at.
+    next_due: Option<u64>,
+    pending: Digest,
+}
+
+/// Collects events into a digest that's handed to another sink on a schedule.
+///
+/// Events that are severe enough skip the digest and go to the other sink right away.
+pub struct DigestSink {
+    inner: Box<dyn Sink>,
+    config: DigestConfig,
+    state: DigestState,
+    path: PathBuf,
+}
+
+impl DigestSink {
+    const FILE_NAME: &'static str = "digest.json";
+
+    /// Wraps `inner`, picking up any digest that was still pending when the monitor last stopped.
+    pub fn load(inner: Box<dyn Sink>, config: DigestConfig, data_dir: &Path) -> Self {
+        let path = state::path_for(data_dir, Self::FILE_NAME);
+        let mut state: DigestState = state::load(&path);
+
+        if state.next_due.is_none() {
+            state.next_due = Some(config.next_due(unix_now()));
+        }
+
+        let sink = Self {
+            inner,
+            config,
+            state,
+            path,
+        };
+        sink.persist();
+        sink
+    }
+
+    fn persist(&self) {
+        if let Err(e) = state::save(&self.path, &self.state) {
+            log::warn!("failed to save pending digest: {e}");
+        }
+    }
+}
+
+impl Sink for DigestSink {
+    fn deliver(&mut self, alert: &Alert) {
+        if alert.severity >= self.config.realtime {
+            self.inner.deliver(alert);
+            return;
+        }
+
+        self.state.pending.add(
+            alert.event.changer(),
+            alert.event.kind_name(),
+            1,
+            alert.severity,
+        );
+        self.persist();
+    }
+
+    fn deliver_summary(&mut self, summary: &BurstSummary) {
+        if summary.severity >= self.config.realtime {
+            self.inner.deliver_summary(summary);
+            return;
+        }
+
+        self.state.pending.add_summary(summary);
+        self.persist();
+    }
+
+    fn interrupts(&self) -> bool {
+        self.inner.interrupts()
+    }
+
+    fn tick(&mut self) {
+        self.inner.tick();
+
+        let now = unix_now();
+        if self.state.next_due.is_some_and(|due| now < due) {
+            return;
+        }
+
+        let digest = std::mem::take(&mut self.state.pending);
+
+        if digest.groups.is_empty() {
+            log::debug!("nothing happened since the last digest");
+        } else {
+            log::info!("sending digest of {} changes", digest.total());
+            self.inner.deliver_digest(&digest);
+        }
+
+        self.state.next_due = Some(self.config.next_due(now));
+        self.persist();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 60 * 60;
+    const DAY: u64 = 24 * HOUR;
+
+    /// The local midnight starting a day well clear of any daylight saving change.
+    fn midnight() -> u64 {
+        // 2024-01-10 12:00 UTC, which is on the 10th or 11th wherever this runs.
+        let noon = 1_704_888_000;
+        noon - LocalTime::at_unix(noon as i64).second_of_day()
+    }
+
+    fn config(period: DigestPeriod, at_hour: u8) -> DigestConfig {
+        DigestConfig {
+            period,
+            at_hour,
+            ..DigestConfig::default()
+        }
+    }
+
+    #[test]
+    fn daily_digests_are_due_at_the_hour() {
+        let daily = config(DigestPeriod::Daily, 9);
+        let midnight = midnight();
+
+        assert_eq!(daily.next_due(midnight), midnight + 9 * HOUR);
+        assert_eq!(
+            daily.next_due(midnight + 8 * HOUR + 59),
+            midnight + 9 * HOUR
+        );
+        // Once it's due, the next one is tomorrow's.
+        assert_eq!(
+            daily.next_due(midnight + 9 * HOUR),
+            midnight + DAY + 9 * HOUR
+        );
+        assert_eq!(
+            daily.next_due(midnight + 9 * HOUR + 1),
+            midnight + DAY + 9 * HOUR
+        );
+        assert_eq!(
+            daily.next_due(midnight + DAY - 1),
+            midnight + DAY + 9 * HOUR
+        );
+    }
+
+    #[test]
+    fn daily_digests_at_midnight_are_due_the_next_day() {
+        let daily = config(DigestPeriod::Daily, 0);
+        let midnight = midnight();
+
+        assert_eq!(daily.next_due(midnight), midnight + DAY);
+        assert_eq!(daily.next_due(midnight + DAY - 1), midnight + DAY);
+
+        // Hours past the end of the day are the last hour.
+        let late = config(DigestPeriod::Daily, 30);
+        assert_eq!(late.next_due(midnight), midnight + 23 * HOUR);
+    }
+
+    #[test]
+    fn hourly_digests_are_due_on_the_hour() {
+        let hourly = config(DigestPeriod::Hourly, 9);
+        let midnight = midnight();
+
+        assert_eq!(
+            hourly.next_due(midnight + 8 * HOUR + 1),
+            midnight + 9 * HOUR
+        );
+        assert_eq!(hourly.next_due(midnight + 9 * HOUR), midnight + 10 * HOUR);
+        assert_eq!(
+            hourly.next_due(midnight + 9 * HOUR + 1),
+            midnight + 10 * HOUR
+        );
+        assert_eq!(hourly.next_due(midnight + DAY - 1), midnight + DAY);
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+
+use super::Sink;
+use crate::{
+    bursts::BurstSummary,
+    clock::unix_now,
+    items::{ItemAttributes, ItemDetail},
+    pipeline::{Alert, Severity},
+    process::ProcessInfo,
+};
+
+/// Subdirectory of the data directory that the journal is kept in.
+pub const JOURNAL_DIR: &str = "Journal";
+
+const FILE_NAME: &str = "events.jsonl";
+
+/// Keeps a permanent record of every event, with all the details known about it.
+///
+/// The journal is made of one JSON object per line.
+pub struct JournalSink {
+    file: File,
+}
+
+#[derive(Serialize)]
+struct JournalEntry<'a> {
+    /// Seconds since the Unix epoch when the event was handled.
+    recorded_at: u64,
+    kind: &'static str,
+    item: Option<&'a str>,
+    item_class: Option<&'a str>,
+    attributes: Option<&'a ItemAttributes>,
+    details: &'a [ItemDetail],
+    severity: Severity,
+    reasons: &'a [String],
+    score: Option<u32>,
+    detected_by_scan: bool,
+    /// The changer and everything that launched it, rendered for people reading the journal directly.
+    chain: String,
+    changer: &'a ProcessInfo,
+}
+
+/// Written once a burst ends, after each of the burst's events got its own entry.
+#[derive(Serialize)]
+struct BurstEntry<'a> {
+    recorded_at: u64,
+    /// Always `burst`.
+    kind: &'static str,
+    severity: Severity,
+    added: usize,
+    updated: usize,
+    removed: usize,
+    accessed: usize,
+    lock_changes: usize,
+    items: &'a [String],
+    chain: String,
+    changer: &'a ProcessInfo,
+}
+
+impl JournalSink {
+    pub fn open(data_dir: &Path) -> io::Result<Self> {
+        let dir = data_dir.join(JOURNAL_DIR);
+        fs::create_dir_all(&dir)?;
+
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(dir.join(FILE_NAME))?;
+
+        Ok(Self { file })
+    }
+
+    fn write<T: Serialize>(&mut self, entry: &T) {
+        let mut line =
+            serde_json::to_string(entry).expect("journal entries are always serializable");
+        line.push('\n');
+
+        if let Err(e) = self.file.write_all(line.as_bytes()) {
+            log::warn!("failed to write journal entry: {e}");
+        }
+    }
+}
+
+impl Sink for JournalSink {
+    fn deliver(&mut self, alert: &Alert) {
+        let event = &alert.event;
+        let changer = event.changer();
+
+        let entry = JournalEntry {
+            recorded_at: unix_now(),
+            kind: event.kind_name(),
+            item: event.item_title(),
+            item_class: event.item_class(),
+            attributes: event.attributes(),
+            details: event.details(),
+            severity: alert.severity,
+            reasons: &alert.reasons,
+            score: alert.score,
+            detected_by_scan: alert.detected_by_scan,
+            chain: changer.full_chain(),
+            changer,
+        };
+
+        self.write(&entry);
+    }
+
+    fn deliver_summary(&mut self, summary: &BurstSummary) {
+        let entry = BurstEntry {
+            recorded_at: unix_now(),
+            kind: "burst",
+            severity: summary.severity,
+            added: summary.added,
+            updated: summary.updated,
+            removed: summary.removed,
+            accessed: summary.accessed,
+            lock_changes: summary.lock_changes,
+            items: &summary.items,
+            chain: summary.changer.full_chain(),
+            changer: &summary.changer,
+        };
+
+        self.write(&entry);
+    }
+}
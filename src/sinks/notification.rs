@@ -0,0 +1,203 @@
+use super::{Digest, Sink};
+use crate::{
+    bursts::BurstSummary,
+    events::FilteredEventData,
+    items,
+    pipeline::{Alert, Severity},
+    process::ProcessInfo,
+};
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "linux")]
+use linux as platform;
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "macos")]
+use macos as platform;
+
+/// Shows a desktop notification for every event.
+pub struct NotificationSink {
+    notifier: platform::Notifier,
+}
+
+impl NotificationSink {
+    pub fn new() -> Self {
+        Self {
+            notifier: platform::Notifier::new(),
+        }
+    }
+}
+
+impl Sink for NotificationSink {
+    fn deliver(&mut self, alert: &Alert) {
+        let ev = &alert.event;
+        let item_title = ev.item_title().unwrap_or("Unknown");
+
+        // Lots of items share a title, like every login for one website, so the account tells them apart.
+        let subtitle = match ev.attributes().and_then(|a| a.account.as_deref()) {
+            Some(account) => format!("Item: {item_title} ({account})"),
+            None => format!("Item: {item_title}"),
+        };
+        log::debug!("sending notification about {}", subtitle);
+
+        let title = with_severity(alert.severity, &get_title(ev));
+
+        let mut message = get_changer_message(ev.changer());
+        // Notifications don't have much room, so only the most important reason is shown.
+        if let Some(reason) = alert.reasons.first() {
+            message.push('\n');
+            message.push_str(reason);
+        }
+
+        self.notifier.show(&title, &subtitle, &message);
+    }
+
+    fn deliver_summary(&mut self, summary: &BurstSummary) {
+        let name = match summary.changer.display_name() {
+            Some(name) => name.to_owned(),
+            None => format!("Unknown Application ({})", summary.changer.pid),
+        };
+
+        log::debug!("sending notification about a burst from {name}");
+
+        let title = with_severity(summary.severity, "Lots of items were changed");
+        let subtitle = format!("{name} {}", summary.describe());
+
+        self.notifier
+            .show(&title, &subtitle, "Every change is listed in the journal");
+    }
+
+    fn deliver_digest(&mut self, digest: &Digest) {
+        let title = with_severity(digest.severity(), "Digest of changes");
+        let subtitle = format!("{} changes to stored items", digest.total());
+
+        // Notifications don't have much room, so only the busiest changers are listed.
+        const SHOWN: usize = 3;
+        let lines = digest.lines();
+        let mut message = lines[..lines.len().min(SHOWN)].join("\n");
+        if lines.len() > SHOWN {
+            message.push_str(&format!("\n…and {} more", lines.len() - SHOWN));
+        }
+
+        self.notifier.show(&title, &subtitle, &message);
+    }
+
+    fn interrupts(&self) -> bool {
+        true
+    }
+}
+
+fn with_severity(severity: Severity, title: &str) -> String {
+    match severity {
+        Severity::Critical => format!("🚨 {title}"),
+        Severity::Elevated => format!("⚠️ {title}"),
+        Severity::Log | Severity::Notice => title.to_owned(),
+    }
+}
+
+/// Says what happened in which keystore, like "A new item was added to the keychain".
+fn get_title(ev: &FilteredEventData) -> String {
+    let noun = ev
+        .item_class()
+        .and_then(items::spec_named)
+        .map_or("item", |spec| spec.noun);
+
+    let noun_with_article = with_article(noun);
+    let keystore = ev.backend().described();
+    match ev {
+        FilteredEventData::Added { .. } => format!("A new {noun} was added to {keystore}"),
+        FilteredEventData::Updated { .. } => {
+            format!("{noun_with_article} in {keystore} was updated")
+        }
+        FilteredEventData::Removed { .. } => {
+            format!("{noun_with_article} was removed from {keystore}")
+        }
+        FilteredEventData::Accessed(_) => format!("{noun_with_article} in {keystore} was read"),
+        FilteredEventData::LockChanged { locked: true, .. } => {
+            format!("{} was locked", capitalized(keystore))
+        }
+        FilteredEventData::LockChanged { locked: false, .. } => {
+            format!("{} was unlocked", capitalized(keystore))
+        }
+    }
+}
+
+/// Puts "A" or "An" in front of a class noun, like "An identity".
+fn with_article(noun: &str) -> String {
+    match noun.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        true => format!("An {noun}"),
+        false => format!("A {noun}"),
+    }
+}
+
+fn capitalized(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn get_changer_message(changer: &ProcessInfo) -> String {
+    const BASE_MSG: &str = "Changer:";
+
+    let name = match changer.display_name() {
+        Some(name) => name.to_owned(),
+        None => format!("Unknown Application ({})", changer.pid),
+    };
+
+    match changer.compact_chain() {
+        Some(chain) => format!("{BASE_MSG} {name} (via {chain})"),
+        None => format!("{BASE_MSG} {name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Backend, InnerDetails};
+
+    #[test]
+    fn titles_name_the_keystore() {
+        let added = FilteredEventData::Added(InnerDetails::new(
+            Backend::Keychain,
+            String::from("github.com"),
+            0.0,
+            ProcessInfo::unknown(0),
+        ));
+        assert_eq!(get_title(&added), "A new item was added to the keychain");
+
+        let key = FilteredEventData::Accessed(
+            InnerDetails::new(
+                Backend::SshAgent,
+                String::from("laptop"),
+                0.0,
+                ProcessInfo::unknown(0),
+            )
+            .with_class("key", Default::default(), Vec::new()),
+        );
+        assert_eq!(get_title(&key), "A key in the SSH agent was read");
+
+        let removed = FilteredEventData::Removed {
+            backend: Backend::CredentialFile,
+            seen_at: 0.0,
+            modified_by: ProcessInfo::unknown(0),
+            item_name: None,
+        };
+        assert_eq!(
+            get_title(&removed),
+            "An item was removed from a credential file"
+        );
+
+        let locked = FilteredEventData::LockChanged {
+            backend: Backend::KWallet,
+            seen_at: 0.0,
+            modified_by: ProcessInfo::unknown(0),
+            keystore: String::from("kdewallet"),
+            locked: true,
+        };
+        assert_eq!(get_title(&locked), "KWallet was locked");
+    }
+}
@@ -0,0 +1,50 @@
+use crate::dbus::{Connection, Value};
+
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+/// Shows notifications through whatever notification daemon the desktop runs.
+pub struct Notifier {
+    connection: Option<Connection>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        let connection = Connection::session()
+            .map_err(|e| log::warn!("failed to connect to the session bus for notifications: {e}"))
+            .ok();
+
+        Self { connection }
+    }
+
+    pub fn show(&mut self, title: &str, subtitle: &str, message: &str) {
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        // There's no subtitle in freedesktop notifications, so it goes on top of the body instead.
+        let body = format!("{subtitle}\n{message}");
+
+        let result = connection.call(
+            NOTIFICATIONS_NAME,
+            NOTIFICATIONS_PATH,
+            NOTIFICATIONS_NAME,
+            "Notify",
+            vec![
+                Value::Str(String::from("Keeper of Keys")),
+                Value::Uint32(0),
+                Value::Str(String::from("dialog-password")),
+                Value::Str(title.to_owned()),
+                Value::Str(body),
+                Value::Array(String::from("s"), Vec::new()),
+                Value::dict("s", "v", Vec::new()),
+                Value::Int32(-1),
+            ],
+        );
+
+        if let Err(e) = result {
+            log::warn!("failed to show notification: {e}");
+        }
+    }
+}
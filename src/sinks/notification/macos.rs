@@ -0,0 +1,20 @@
+/// Shows banners in Notification Center.
+pub struct Notifier;
+
+impl Notifier {
+    pub fn new() -> Self {
+        // LEAK NOTE: 1 (16 bytes) ROOT LEAK: <NSArray 0x600002a80360> [16]
+        mac_notification_sys::set_application(crate::BUNDLE_ID).unwrap();
+
+        Self
+    }
+
+    pub fn show(&mut self, title: &str, subtitle: &str, message: &str) {
+        let mut builder = mac_notification_sys::Notification::new();
+        builder.title(title);
+        builder.subtitle(subtitle);
+        builder.message(message);
+
+        builder.send().unwrap();
+    }
+}
@@ -0,0 +1,389 @@
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use super::{Digest, Sink};
+use crate::{
+    bursts::BurstSummary,
+    clock::{unix_now, LocalTime},
+    pipeline::{Alert, Severity},
+    process::ProcessInfo,
+    state,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Weekday {
+    Sun,
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+}
+
+impl Weekday {
+    /// Days since Sunday, matching `LocalTime::weekday`.
+    fn index(self) -> u8 {
+        self as u8
+    }
+}
+
+/// A wall clock time, written as `HH:MM` in the config.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct TimeOfDay {
+    minute_of_day: u16,
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("{value:?} isn't a time formatted like 22:30");
+
+        let (hour, minute) = value.split_once(':').ok_or_else(invalid)?;
+        let hour: u16 = hour.parse().map_err(|_| invalid())?;
+        let minute: u16 = minute.parse().map_err(|_| invalid())?;
+
+        if hour > 23 || minute > 59 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            minute_of_day: hour * 60 + minute,
+        })
+    }
+}
+
+/// A stretch of time in which notifications get saved for later.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct QuietHours {
+    /// The days this starts on. Empty means every day.
+    #[serde(default)]
+    pub(crate) days: Vec<Weekday>,
+    pub(crate) start: TimeOfDay,
+    /// When this is before `start`, quiet hours run past midnight into the next day.
+    pub(crate) end: TimeOfDay,
+}
+
+impl QuietHours {
+    fn starts_on(&self, weekday: u8) -> bool {
+        self.days.is_empty() || self.days.iter().any(|day| day.index() == weekday)
+    }
+
+    fn contains(&self, time: &LocalTime) -> bool {
+        let now = time.minute_of_day();
+        let (start, end) = (self.start.minute_of_day, self.end.minute_of_day);
+
+        if start <= end {
+            self.starts_on(time.weekday) && (start..end).contains(&now)
+        } else {
+            let yesterday = (time.weekday + 6) % 7;
+
+            (self.starts_on(time.weekday) && now >= start)
+                || (self.starts_on(yesterday) && now < end)
+        }
+    }
+}
+
+/// What a snooze silences.
+// Snoozes only come in over the control channel, which only macOS and Linux have.
+#[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+#[derive(Clone, Debug)]
+pub enum SnoozeTarget {
+    /// An executable path, a directory containing executables, or a process or app name.
+    Changer(String),
+    /// An item title.
+    Item(String),
+}
+
+impl SnoozeTarget {
+    fn matches(&self, changer: &ProcessInfo, item: Option<&str>) -> bool {
+        match self {
+            SnoozeTarget::Changer(target) => {
+                let by_path = changer
+                    .path
+                    .as_deref()
+                    .is_some_and(|path| path.starts_with(Path::new(target)));
+
+                by_path || changer.display_name() == Some(target.as_str())
+            }
+            SnoozeTarget::Item(target) => item == Some(target.as_str()),
+        }
+    }
+}
+
+struct Snooze {
+    target: SnoozeTarget,
+    /// Seconds since the Unix epoch that the snooze ends at.
+    until: u64,
+}
+
+/// Changers and items that notifications are currently silenced for.
+///
+/// This is shared between the pipeline and the control channel, which adds to it.
+#[derive(Clone, Default)]
+pub struct Snoozes {
+    entries: Arc<Mutex<Vec<Snooze>>>,
+}
+
+impl Snoozes {
+    /// Silences `target` for the next `minutes`, returning when that ends.
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    pub fn add(&self, target: SnoozeTarget, minutes: u32) -> u64 {
+        let until = unix_now() + u64::from(minutes) * 60;
+
+        log::info!("snoozing notifications for {target:?} for {minutes} minutes");
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(Snooze { target, until });
+
+        until
+    }
+
+    #[cfg_attr(not(any(target_os = "macos", target_os = "linux")), allow(dead_code))]
+    pub fn clear(&self) {
+        log::info!("clearing every snooze");
+        self.entries.lock().unwrap().clear();
+    }
+
+    fn matching(&self, changer: &ProcessInfo, item: Option<&str>) -> Option<SnoozeTarget> {
+        let now = unix_now();
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|snooze| snooze.until > now);
+
+        entries
+            .iter()
+            .find(|snooze| snooze.target.matches(changer, item))
+            .map(|snooze| snooze.target.clone())
+    }
+}
+
+/// Holds back another sink's notifications during quiet hours and for snoozed changers or items.
+///
+/// Anything held back during quiet hours is handed over as a catch-up digest once they end.
+/// Critical events are never held back.
+pub struct ScheduleSink {
+    inner: Box<dyn Sink>,
+    quiet_hours: Vec<QuietHours>,
+    snoozes: Snoozes,
+    /// Everything held back during the current quiet hours.
+    catch_up: Digest,
+    path: PathBuf,
+}
+
+impl ScheduleSink {
+    const FILE_NAME: &'static str = "catch_up.json";
+
+    pub fn load(
+        inner: Box<dyn Sink>,
+        quiet_hours: Vec<QuietHours>,
+        snoozes: Snoozes,
+        data_dir: &Path,
+    ) -> Self {
+        let path = state::path_for(data_dir, Self::FILE_NAME);
+
+        Self {
+            inner,
+            quiet_hours,
+            snoozes,
+            catch_up: state::load(&path),
+            path,
+        }
+    }
+
+    fn is_quiet(&self) -> bool {
+        let now = LocalTime::at_unix(unix_now() as i64);
+        self.quiet_hours.iter().any(|quiet| quiet.contains(&now))
+    }
+
+    fn is_snoozed(&self, changer: &ProcessInfo, item: Option<&str>) -> bool {
+        match self.snoozes.matching(changer, item) {
+            Some(target) => {
+                log::debug!("not notifying, {target:?} is snoozed");
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn persist(&self) {
+        if let Err(e) = state::save(&self.path, &self.catch_up) {
+            log::warn!("failed to save notifications held back for quiet hours: {e}");
+        }
+    }
+}
+
+impl Sink for ScheduleSink {
+    fn deliver(&mut self, alert: &Alert) {
+        let changer = alert.event.changer();
+
+        if alert.severity < Severity::Critical {
+            if self.is_snoozed(changer, alert.event.item_title()) {
+                return;
+            }
+
+            if self.is_quiet() {
+                self.catch_up
+                    .add(changer, alert.event.kind_name(), 1, alert.severity);
+                self.persist();
+                return;
+            }
+        }
+
+        self.inner.deliver(alert);
+    }
+
+    fn deliver_summary(&mut self, summary: &BurstSummary) {
+        if summary.severity < Severity::Critical {
+            if self.is_snoozed(&summary.changer, None) {
+                return;
+            }
+
+            if self.is_quiet() {
+                self.catch_up.add_summary(summary);
+                self.persist();
+                return;
+            }
+        }
+
+        self.inner.deliver_summary(summary);
+    }
+
+    fn deliver_digest(&mut self, digest: &Digest) {
+        if digest.severity() < Severity::Critical && self.is_quiet() {
+            self.catch_up.merge(digest);
+            self.persist();
+            return;
+        }
+
+        self.inner.deliver_digest(digest);
+    }
+
+    fn interrupts(&self) -> bool {
+        self.inner.interrupts()
+    }
+
+    fn tick(&mut self) {
+        self.inner.tick();
+
+        if self.catch_up.groups.is_empty() || self.is_quiet() {
+            return;
+        }
+
+        let catch_up = std::mem::take(&mut self.catch_up);
+        log::info!(
+            "quiet hours are over, catching up on {} changes",
+            catch_up.total()
+        );
+
+        self.inner.deliver_digest(&catch_up);
+        self.persist();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_of_day(value: &str) -> Result<u16, String> {
+        TimeOfDay::try_from(value.to_owned()).map(|time| time.minute_of_day)
+    }
+
+    fn quiet_hours(config: &str) -> QuietHours {
+        toml::from_str(config).unwrap()
+    }
+
+    /// Days since Sunday, then the wall clock time.
+    fn at(weekday: u8, hour: u8, minute: u8) -> LocalTime {
+        LocalTime {
+            weekday,
+            hour,
+            minute,
+            second: 0,
+        }
+    }
+
+    #[test]
+    fn reads_times_of_day() {
+        assert_eq!(time_of_day("00:00"), Ok(0));
+        assert_eq!(time_of_day("7:05"), Ok(7 * 60 + 5));
+        assert_eq!(time_of_day("22:30"), Ok(22 * 60 + 30));
+        assert_eq!(time_of_day("23:59"), Ok(23 * 60 + 59));
+
+        for invalid in ["24:00", "12:60", "1230", "12:", ":30", "-1:30", "noon", ""] {
+            assert!(time_of_day(invalid).is_err(), "{invalid:?}");
+        }
+
+        assert!(toml::from_str::<QuietHours>(
+            r#"start = "25:00"
+end = "07:00""#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn contains_times_within_the_same_day() {
+        let lunch = quiet_hours(
+            r#"start = "12:00"
+end = "13:00""#,
+        );
+
+        assert!(!lunch.contains(&at(1, 11, 59)));
+        assert!(lunch.contains(&at(1, 12, 0)));
+        assert!(lunch.contains(&at(4, 12, 59)));
+        assert!(!lunch.contains(&at(1, 13, 0)));
+    }
+
+    #[test]
+    fn contains_times_past_midnight() {
+        let nights = quiet_hours(
+            r#"start = "22:00"
+end = "07:00""#,
+        );
+
+        assert!(!nights.contains(&at(3, 21, 59)));
+        assert!(nights.contains(&at(3, 22, 0)));
+        assert!(nights.contains(&at(3, 23, 59)));
+        assert!(nights.contains(&at(4, 0, 0)));
+        assert!(nights.contains(&at(4, 6, 59)));
+        assert!(!nights.contains(&at(4, 7, 0)));
+        assert!(!nights.contains(&at(4, 12, 0)));
+    }
+
+    #[test]
+    fn quiet_hours_past_midnight_belong_to_the_day_they_start() {
+        let weekend = quiet_hours(
+            r#"days = ["fri", "sat"]
+start = "20:00"
+end = "09:00""#,
+        );
+
+        // Neither Thursday night nor the Friday morning after it are quiet.
+        assert!(!weekend.contains(&at(4, 23, 0)));
+        assert!(!weekend.contains(&at(5, 8, 0)));
+        assert!(weekend.contains(&at(5, 23, 0)));
+        assert!(weekend.contains(&at(6, 8, 0)));
+        assert!(weekend.contains(&at(6, 23, 0)));
+        // Saturday night runs into Sunday, wrapping around the week.
+        assert!(weekend.contains(&at(0, 8, 0)));
+        assert!(!weekend.contains(&at(0, 23, 0)));
+        assert!(!weekend.contains(&at(1, 8, 0)));
+
+        let sundays = quiet_hours(
+            r#"days = ["sun"]
+start = "23:00"
+end = "01:00""#,
+        );
+
+        // Saturday night into Sunday morning isn't Sunday's.
+        assert!(!sundays.contains(&at(0, 0, 30)));
+        assert!(sundays.contains(&at(0, 23, 30)));
+        assert!(sundays.contains(&at(1, 0, 30)));
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{baseline::Baseline, config::Config, fingerprint::FingerprintStore};
+
+/// Subdirectory of the data directory that long-lived state is kept in.
+pub const STATE_DIR: &str = "State";
+
+/// Where the state file called `name` lives.
+pub fn path_for(data_dir: &Path, name: &str) -> PathBuf {
+    data_dir.join(STATE_DIR).join(name)
+}
+
+/// Reads a state file, starting fresh if it doesn't exist yet or can't be understood.
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> T {
+    match fs::read(path) {
+        Ok(contents) => match serde_json::from_slice(&contents) {
+            Ok(state) => state,
+            Err(e) => {
+                log::warn!("{} is corrupted, starting over: {e}", path.display());
+                T::default()
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => T::default(),
+        Err(e) => {
+            log::warn!("failed to read {}: {e}", path.display());
+            T::default()
+        }
+    }
+}
+
+/// Writes a state file, making sure a crash midway can never leave a half written file behind.
+pub fn save<T: Serialize>(path: &Path, state: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let contents = serde_json::to_vec(state).map_err(io::Error::from)?;
+
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)
+}
+
+/// Everything the pipeline remembers between events.
+pub struct Stores {
+    pub fingerprints: FingerprintStore,
+    /// Only present when learning mode is turned on.
+    pub baseline: Option<Baseline>,
+}
+
+impl Stores {
+    /// Loads the stores that a live monitor keeps up to date.
+    pub fn load(data_dir: &Path, config: &Config) -> Self {
+        Self {
+            fingerprints: FingerprintStore::load(data_dir),
+            baseline: config
+                .learning
+                .enabled
+                .then(|| Baseline::load(data_dir, &config.learning)),
+        }
+    }
+
+    /// Loads stores that can be used without ever changing what's saved on disk, for replays.
+    pub fn scratch(data_dir: &Path, config: &Config) -> Self {
+        Self {
+            fingerprints: FingerprintStore::in_memory(),
+            baseline: config
+                .learning
+                .enabled
+                .then(|| Baseline::load_read_only(data_dir, &config.learning)),
+        }
+    }
+}